@@ -0,0 +1,917 @@
+//! One builder per program instruction. Fields are the keys and arguments that cannot be derived,
+//! every PDA, associated token account and program id is filled in by `instruction()`.
+
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::{
+        bpf_loader_upgradeable::get_program_data_address, instruction::Instruction,
+        sysvar::instructions::ID as INSTRUCTIONS_SYSVAR_ID,
+    },
+    system_program, InstructionData, ToAccountMetas,
+};
+use anchor_spl::associated_token::{self, get_associated_token_address_with_program_id as ata};
+use capstone::{
+    accounts, instruction as data,
+    instructions::{CreateCouponArgs, CreateSubscriptionArgs, SubscribeArgs, UpdateConfigArgs},
+    states::{PayoutShare, TerminationReason},
+};
+
+use crate::pda;
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: capstone::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// payout recipients follow the instruction accounts, see `set_payout_split`
+fn with_recipients(mut instruction: Instruction, recipients: &[Pubkey]) -> Instruction {
+    instruction.accounts.extend(
+        recipients
+            .iter()
+            .map(|recipient| AccountMeta::new(*recipient, false)),
+    );
+    instruction
+}
+
+// admin
+
+pub struct Initialize {
+    pub signer: Pubkey, // upgrade authority of the program
+    pub task_queue: Pubkey,
+}
+
+impl Initialize {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::Initialize {
+                signer: self.signer,
+                global_state: pda::global_state(),
+                accepted_mints: pda::accepted_mints(),
+                queue_authority: pda::queue_authority(),
+                task_queue: self.task_queue,
+                tuktuk_program: tuktuk_program::tuktuk::ID,
+                system_program: system_program::ID,
+                program_data: get_program_data_address(&capstone::ID),
+                this_program: capstone::ID,
+            },
+            data::Initialize {},
+        )
+    }
+}
+
+// `add_accepted_mint` or `remove_accepted_mint`
+pub struct UpdateAcceptedMints {
+    pub signer: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub add: bool,
+}
+
+impl UpdateAcceptedMints {
+    pub fn instruction(&self) -> Instruction {
+        let accounts = accounts::UpdateAcceptedMints {
+            signer: self.signer,
+            global_state: pda::global_state(),
+            accepted_mints: pda::accepted_mints(),
+            mint: self.mint,
+            fee_token_vault: pda::fee_token_vault(&self.mint),
+            token_program: self.token_program,
+            system_program: system_program::ID,
+        };
+
+        if self.add {
+            build(accounts, data::AddAcceptedMint {})
+        } else {
+            build(accounts, data::RemoveAcceptedMint {})
+        }
+    }
+}
+
+pub struct UpdateConfig {
+    pub signer: Pubkey,
+    pub args: UpdateConfigArgs,
+}
+
+impl UpdateConfig {
+    pub fn instruction(self) -> Instruction {
+        build(
+            accounts::UpdateConfig {
+                signer: self.signer,
+                global_state: pda::global_state(),
+            },
+            data::UpdateConfig { args: self.args },
+        )
+    }
+}
+
+pub struct WithdrawFees {
+    pub signer: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+}
+
+impl WithdrawFees {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::WithdrawFees {
+                signer: self.signer,
+                global_state: pda::global_state(),
+                fees_vault: pda::fees_vault(),
+                mint: self.mint,
+                fee_token_vault: pda::fee_token_vault(&self.mint),
+                signer_ata: ata(&self.signer, &self.mint, &self.token_program),
+                associated_token_program: associated_token::ID,
+                token_program: self.token_program,
+                system_program: system_program::ID,
+            },
+            data::WithdrawFees {},
+        )
+    }
+}
+
+pub struct UpdateTaskQueue {
+    pub signer: Pubkey,
+    pub task_queue: Pubkey,
+}
+
+impl UpdateTaskQueue {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::UpdateTaskQueue {
+                signer: self.signer,
+                global_state: pda::global_state(),
+                task_queue: self.task_queue,
+                queue_authority: pda::queue_authority(),
+                task_queue_authority: pda::task_queue_authority(&self.task_queue),
+            },
+            data::UpdateTaskQueue {},
+        )
+    }
+}
+
+pub struct ProposeAdmin {
+    pub signer: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+impl ProposeAdmin {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::ProposeAdmin {
+                signer: self.signer,
+                global_state: pda::global_state(),
+            },
+            data::ProposeAdmin {
+                new_admin: self.new_admin,
+            },
+        )
+    }
+}
+
+pub struct AcceptAdmin {
+    pub signer: Pubkey,
+}
+
+impl AcceptAdmin {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::AcceptAdmin {
+                signer: self.signer,
+                global_state: pda::global_state(),
+            },
+            data::AcceptAdmin {},
+        )
+    }
+}
+
+// merchant
+
+pub struct CreateSubscription {
+    pub merchant: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub args: CreateSubscriptionArgs,
+}
+
+impl CreateSubscription {
+    pub fn instruction(self) -> Instruction {
+        build(
+            accounts::CreateSubscription {
+                merchant: self.merchant,
+                subscription_plan: pda::subscription_plan(&self.merchant, &self.args.name),
+                accepted_mints: pda::accepted_mints(),
+                mint: self.mint,
+                merchant_ata: ata(&self.merchant, &self.mint, &self.token_program),
+                fees_vault: pda::fees_vault(),
+                global_state: pda::global_state(),
+                associated_token_program: associated_token::ID,
+                token_program: self.token_program,
+                system_program: system_program::ID,
+            },
+            data::CreateSubscription { args: self.args },
+        )
+    }
+}
+
+pub struct CreateCoupon {
+    pub merchant: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub args: CreateCouponArgs,
+}
+
+impl CreateCoupon {
+    pub fn instruction(self) -> Instruction {
+        build(
+            accounts::CreateCoupon {
+                merchant: self.merchant,
+                subscription_plan: self.subscription_plan,
+                coupon: pda::coupon(&self.subscription_plan, &self.args.code),
+                system_program: system_program::ID,
+            },
+            data::CreateCoupon { args: self.args },
+        )
+    }
+}
+
+// `pause_plan`, `resume_plan`, `schedule_price_change` and `set_payout_split`
+pub struct UpdatePlan {
+    pub merchant: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub update: PlanUpdate,
+}
+
+pub enum PlanUpdate {
+    Pause,
+    Resume,
+    SchedulePriceChange { new_amount: u64 },
+    SetPayoutSplit { payout_split: Vec<PayoutShare> },
+}
+
+impl UpdatePlan {
+    pub fn instruction(self) -> Instruction {
+        let accounts = accounts::UpdatePlan {
+            merchant: self.merchant,
+            subscription_plan: self.subscription_plan,
+        };
+
+        match self.update {
+            PlanUpdate::Pause => build(accounts, data::PausePlan {}),
+            PlanUpdate::Resume => build(accounts, data::ResumePlan {}),
+            PlanUpdate::SchedulePriceChange { new_amount } => {
+                build(accounts, data::SchedulePriceChange { new_amount })
+            }
+            PlanUpdate::SetPayoutSplit { payout_split } => {
+                let recipients: Vec<Pubkey> =
+                    payout_split.iter().map(|s| s.recipient_ata).collect();
+
+                with_recipients(
+                    build(accounts, data::SetPayoutSplit { payout_split }),
+                    &recipients,
+                )
+            }
+        }
+    }
+}
+
+pub struct RetirePlan {
+    pub merchant: Pubkey,
+    pub subscription_plan: Pubkey,
+}
+
+impl RetirePlan {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::RetirePlan {
+                merchant: self.merchant,
+                subscription_plan: self.subscription_plan,
+            },
+            data::RetirePlan {},
+        )
+    }
+}
+
+pub struct RefundCharge {
+    pub merchant: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub charge_receipt: Pubkey, // `ChargeEvent::receipt`, see `pda::charge_receipt`
+    // the subscriber vault or `ChargeReceipt::subscriber_ata`
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+impl RefundCharge {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::RefundCharge {
+                merchant: self.merchant,
+                subscription_plan: self.subscription_plan,
+                charge_receipt: self.charge_receipt,
+                mint: self.mint,
+                merchant_ata: ata(&self.merchant, &self.mint, &self.token_program),
+                destination: self.destination,
+                token_program: self.token_program,
+            },
+            data::RefundCharge {
+                amount: self.amount,
+            },
+        )
+    }
+}
+
+pub struct TerminateSubscription {
+    pub merchant: Pubkey,
+    pub subscription: SubscriptionKeys,
+    pub reason: TerminationReason,
+}
+
+impl TerminateSubscription {
+    pub fn instruction(&self) -> Instruction {
+        let s = &self.subscription;
+
+        build(
+            accounts::TerminateSubscription {
+                merchant: self.merchant,
+                subscriber: s.subscriber,
+                user_subscription: s.user_subscription(),
+                billing_ledger: s.billing_ledger(),
+                subscription_plan: s.subscription_plan,
+                subscriber_ata: s.subscriber_ata,
+                global_state: pda::global_state(),
+                task_queue: s.task_queue,
+                queue_authority: pda::queue_authority(),
+                task_queue_authority: pda::task_queue_authority(&s.task_queue),
+                task: pda::task(&s.task_queue, s.next_task_id),
+                tuktuk_program: tuktuk_program::tuktuk::ID,
+                system_program: system_program::ID,
+            },
+            data::TerminateSubscription {
+                reason: self.reason,
+            },
+        )
+    }
+}
+
+// oracle of a metered plan
+pub struct ReportUsage {
+    pub oracle: Pubkey,
+    pub subscriber: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub units: u64,
+}
+
+impl ReportUsage {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::ReportUsage {
+                oracle: self.oracle,
+                subscription_plan: self.subscription_plan,
+                user_subscription: pda::user_subscription(
+                    &self.subscriber,
+                    &self.subscription_plan,
+                ),
+            },
+            data::ReportUsage { units: self.units },
+        )
+    }
+}
+
+// subscriber
+
+pub struct Subscribe {
+    pub subscriber: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub mint: Pubkey,
+    pub task_queue: Pubkey, // `GlobalState::task_queue`
+    pub token_program: Pubkey,
+    pub coupon: Option<Pubkey>,
+    pub args: SubscribeArgs, // `task_id` from `next_task_id`
+}
+
+impl Subscribe {
+    pub fn instruction(self) -> Instruction {
+        build(
+            accounts::Subscribe {
+                subscriber: self.subscriber,
+                user_subscription: pda::user_subscription(
+                    &self.subscriber,
+                    &self.subscription_plan,
+                ),
+                subscription_plan: self.subscription_plan,
+                coupon: self.coupon,
+                accepted_mints: pda::accepted_mints(),
+                mint: self.mint,
+                subscriber_ata: ata(&self.subscriber, &self.mint, &self.token_program),
+                subscriber_vault: pda::subscriber_vault(&self.subscriber, &self.mint),
+                fee_token_vault: pda::fee_token_vault(&self.mint),
+                global_state: pda::global_state(),
+                task_queue: self.task_queue,
+                queue_authority: pda::queue_authority(),
+                task_queue_authority: pda::task_queue_authority(&self.task_queue),
+                task: pda::task(&self.task_queue, self.args.task_id),
+                associated_token_program: associated_token::ID,
+                token_program: self.token_program,
+                tuktuk_program: tuktuk_program::tuktuk::ID,
+                system_program: system_program::ID,
+            },
+            data::Subscribe { args: self.args },
+        )
+    }
+}
+
+// accounts of an existing subscription, read from its `UserSubscription`
+pub struct SubscriptionKeys {
+    pub subscriber: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub mint: Pubkey,
+    pub subscriber_ata: Pubkey,
+    pub task_queue: Pubkey,
+    pub next_task_id: u16,
+    pub next_receipt: u64,
+    pub has_ledger: bool,
+    pub token_program: Pubkey,
+}
+
+impl SubscriptionKeys {
+    pub fn user_subscription(&self) -> Pubkey {
+        pda::user_subscription(&self.subscriber, &self.subscription_plan)
+    }
+
+    fn charge_receipt(&self) -> Pubkey {
+        pda::charge_receipt(&self.user_subscription(), self.next_receipt)
+    }
+
+    fn billing_ledger(&self) -> Option<Pubkey> {
+        self.has_ledger
+            .then(|| pda::billing_ledger(&self.user_subscription()))
+    }
+}
+
+pub struct CancelSubscription {
+    pub subscription: SubscriptionKeys,
+}
+
+impl CancelSubscription {
+    pub fn instruction(&self) -> Instruction {
+        let s = &self.subscription;
+
+        build(
+            accounts::CancelSubscription {
+                subscriber: s.subscriber,
+                user_subscription: s.user_subscription(),
+                billing_ledger: s.billing_ledger(),
+                subscription_plan: s.subscription_plan,
+                subscriber_ata: s.subscriber_ata,
+                global_state: pda::global_state(),
+                task_queue: s.task_queue,
+                queue_authority: pda::queue_authority(),
+                task_queue_authority: pda::task_queue_authority(&s.task_queue),
+                task: pda::task(&s.task_queue, s.next_task_id),
+                tuktuk_program: tuktuk_program::tuktuk::ID,
+                associated_token_program: associated_token::ID,
+                token_program: s.token_program,
+                system_program: system_program::ID,
+            },
+            data::CancelSubscription {},
+        )
+    }
+}
+
+pub struct CancelAtPeriodEnd {
+    pub subscription: SubscriptionKeys,
+}
+
+impl CancelAtPeriodEnd {
+    pub fn instruction(&self) -> Instruction {
+        let s = &self.subscription;
+
+        build(
+            accounts::CancelAtPeriodEnd {
+                subscriber: s.subscriber,
+                user_subscription: s.user_subscription(),
+                subscription_plan: s.subscription_plan,
+                subscriber_ata: s.subscriber_ata,
+                token_program: s.token_program,
+            },
+            data::CancelAtPeriodEnd {},
+        )
+    }
+}
+
+pub struct InitBillingLedger {
+    pub subscriber: Pubkey,
+    pub subscription_plan: Pubkey,
+}
+
+impl InitBillingLedger {
+    pub fn instruction(&self) -> Instruction {
+        let user_subscription = pda::user_subscription(&self.subscriber, &self.subscription_plan);
+
+        build(
+            accounts::InitBillingLedger {
+                subscriber: self.subscriber,
+                user_subscription,
+                billing_ledger: pda::billing_ledger(&user_subscription),
+                system_program: system_program::ID,
+            },
+            data::InitBillingLedger {},
+        )
+    }
+}
+
+pub struct PauseSubscription {
+    pub subscriber: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub resume_at: Option<i64>,
+}
+
+impl PauseSubscription {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::PauseSubscription {
+                subscriber: self.subscriber,
+                user_subscription: pda::user_subscription(
+                    &self.subscriber,
+                    &self.subscription_plan,
+                ),
+            },
+            data::PauseSubscription {
+                resume_at: self.resume_at,
+            },
+        )
+    }
+}
+
+pub struct ResumeSubscription {
+    pub subscription: SubscriptionKeys,
+    pub task_id: u16, // free id for the new charge task
+}
+
+impl ResumeSubscription {
+    pub fn instruction(&self) -> Instruction {
+        let s = &self.subscription;
+
+        build(
+            accounts::ResumeSubscription {
+                subscriber: s.subscriber,
+                user_subscription: s.user_subscription(),
+                subscription_plan: s.subscription_plan,
+                accepted_mints: pda::accepted_mints(),
+                mint: s.mint,
+                subscriber_vault: pda::subscriber_vault(&s.subscriber, &s.mint),
+                fee_token_vault: pda::fee_token_vault(&s.mint),
+                global_state: pda::global_state(),
+                task_queue: s.task_queue,
+                queue_authority: pda::queue_authority(),
+                task_queue_authority: pda::task_queue_authority(&s.task_queue),
+                task: pda::task(&s.task_queue, s.next_task_id),
+                new_task: pda::task(&s.task_queue, self.task_id),
+                associated_token_program: associated_token::ID,
+                token_program: s.token_program,
+                tuktuk_program: tuktuk_program::tuktuk::ID,
+                system_program: system_program::ID,
+            },
+            data::ResumeSubscription {
+                task_id: self.task_id,
+            },
+        )
+    }
+}
+
+pub struct FundCrankReserve {
+    pub subscription: SubscriptionKeys,
+    pub amount: u64,
+    pub task_id: u16, // only used when automation restarts, see `fund_crank_reserve`
+}
+
+impl FundCrankReserve {
+    pub fn instruction(&self) -> Instruction {
+        let s = &self.subscription;
+
+        build(
+            accounts::FundCrankReserve {
+                subscriber: s.subscriber,
+                user_subscription: s.user_subscription(),
+                subscription_plan: s.subscription_plan,
+                accepted_mints: pda::accepted_mints(),
+                mint: s.mint,
+                subscriber_vault: pda::subscriber_vault(&s.subscriber, &s.mint),
+                fee_token_vault: pda::fee_token_vault(&s.mint),
+                global_state: pda::global_state(),
+                task_queue: s.task_queue,
+                queue_authority: pda::queue_authority(),
+                task_queue_authority: pda::task_queue_authority(&s.task_queue),
+                task: pda::task(&s.task_queue, self.task_id),
+                associated_token_program: associated_token::ID,
+                token_program: s.token_program,
+                tuktuk_program: tuktuk_program::tuktuk::ID,
+                system_program: system_program::ID,
+            },
+            data::FundCrankReserve {
+                amount: self.amount,
+                task_id: self.task_id,
+            },
+        )
+    }
+}
+
+pub struct SetSpendingCap {
+    pub subscriber: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub spending_cap: Option<u64>,
+}
+
+impl SetSpendingCap {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::SetSpendingCap {
+                subscriber: self.subscriber,
+                user_subscription: pda::user_subscription(
+                    &self.subscriber,
+                    &self.subscription_plan,
+                ),
+                subscription_plan: self.subscription_plan,
+            },
+            data::SetSpendingCap {
+                spending_cap: self.spending_cap,
+            },
+        )
+    }
+}
+
+pub struct ReactivateSubscription {
+    pub subscriber: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub mint: Pubkey,
+    pub merchant_ata: Pubkey,           // `SubscriptionPlan::merchant_ata`
+    pub payout_recipients: Vec<Pubkey>, // `SubscriptionPlan::payout_split`, in order
+    pub task_queue: Pubkey,             // `GlobalState::task_queue`
+    pub token_program: Pubkey,
+    pub task_id: u16,
+    pub next_receipt: u64, // `UserSubscription::next_receipt`
+    pub has_ledger: bool,  // `UserSubscription::ledger_bump` is set
+}
+
+impl ReactivateSubscription {
+    pub fn instruction(&self) -> Instruction {
+        let user_subscription = pda::user_subscription(&self.subscriber, &self.subscription_plan);
+
+        let instruction = build(
+            accounts::ReactivateSubscription {
+                subscriber: self.subscriber,
+                user_subscription,
+                subscription_plan: self.subscription_plan,
+                accepted_mints: pda::accepted_mints(),
+                mint: self.mint,
+                subscriber_ata: ata(&self.subscriber, &self.mint, &self.token_program),
+                subscriber_vault: pda::subscriber_vault(&self.subscriber, &self.mint),
+                merchant_ata: self.merchant_ata,
+                fee_token_vault: pda::fee_token_vault(&self.mint),
+                charge_receipt: pda::charge_receipt(&user_subscription, self.next_receipt),
+                billing_ledger: self
+                    .has_ledger
+                    .then(|| pda::billing_ledger(&user_subscription)),
+                global_state: pda::global_state(),
+                task_queue: self.task_queue,
+                queue_authority: pda::queue_authority(),
+                task_queue_authority: pda::task_queue_authority(&self.task_queue),
+                task: pda::task(&self.task_queue, self.task_id),
+                associated_token_program: associated_token::ID,
+                token_program: self.token_program,
+                tuktuk_program: tuktuk_program::tuktuk::ID,
+                system_program: system_program::ID,
+            },
+            data::ReactivateSubscription {
+                task_id: self.task_id,
+            },
+        );
+
+        with_recipients(instruction, &self.payout_recipients)
+    }
+}
+
+pub struct ChangePlan {
+    pub subscription: SubscriptionKeys, // of the current plan
+    pub new_subscription_plan: Pubkey,
+    pub new_merchant_ata: Pubkey, // `SubscriptionPlan::merchant_ata` of the new plan
+    pub new_payout_recipients: Vec<Pubkey>, // `SubscriptionPlan::payout_split` of the new plan
+    // `SubscriptionPlan::charge_seq` of the new plan, numbers the receipt of a prorated charge.
+    // Another charge of the new plan landing first fails the change, fetch the plan again then
+    pub new_charge_seq: u64,
+    pub task_id: u16,
+}
+
+impl ChangePlan {
+    pub fn instruction(&self) -> Instruction {
+        let s = &self.subscription;
+        let new_user_subscription =
+            pda::user_subscription(&s.subscriber, &self.new_subscription_plan);
+
+        let instruction = build(
+            accounts::ChangePlan {
+                subscriber: s.subscriber,
+                user_subscription: s.user_subscription(),
+                billing_ledger: s.billing_ledger(),
+                old_subscription_plan: s.subscription_plan,
+                new_user_subscription,
+                new_subscription_plan: self.new_subscription_plan,
+                // the ledger is carried over to the new subscription
+                new_billing_ledger: s
+                    .has_ledger
+                    .then(|| pda::billing_ledger(&new_user_subscription)),
+                accepted_mints: pda::accepted_mints(),
+                mint: s.mint,
+                subscriber_ata: ata(&s.subscriber, &s.mint, &s.token_program),
+                subscriber_vault: pda::subscriber_vault(&s.subscriber, &s.mint),
+                merchant_ata: self.new_merchant_ata,
+                fee_token_vault: pda::fee_token_vault(&s.mint),
+                charge_receipt: pda::charge_receipt(&new_user_subscription, self.new_charge_seq),
+                global_state: pda::global_state(),
+                task_queue: s.task_queue,
+                queue_authority: pda::queue_authority(),
+                task_queue_authority: pda::task_queue_authority(&s.task_queue),
+                task: pda::task(&s.task_queue, s.next_task_id),
+                new_task: pda::task(&s.task_queue, self.task_id),
+                associated_token_program: associated_token::ID,
+                token_program: s.token_program,
+                tuktuk_program: tuktuk_program::tuktuk::ID,
+                system_program: system_program::ID,
+            },
+            data::ChangePlan {
+                task_id: self.task_id,
+            },
+        );
+
+        with_recipients(instruction, &self.new_payout_recipients)
+    }
+}
+
+pub struct CloseVault {
+    pub subscriber: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+}
+
+impl CloseVault {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::CloseVault {
+                subscriber: self.subscriber,
+                mint: self.mint,
+                subscriber_ata: ata(&self.subscriber, &self.mint, &self.token_program),
+                subscriber_vault: pda::subscriber_vault(&self.subscriber, &self.mint),
+                associated_token_program: associated_token::ID,
+                token_program: self.token_program,
+                system_program: system_program::ID,
+            },
+            data::CloseVault {},
+        )
+    }
+}
+
+// anyone
+
+pub struct VerifyEntitlement {
+    pub subscriber: Pubkey,
+    pub subscription_plan: Pubkey,
+}
+
+impl VerifyEntitlement {
+    pub fn instruction(&self) -> Instruction {
+        build(
+            accounts::VerifyEntitlement {
+                user_subscription: pda::user_subscription(
+                    &self.subscriber,
+                    &self.subscription_plan,
+                ),
+                subscription_plan: self.subscription_plan,
+            },
+            data::VerifyEntitlement {},
+        )
+    }
+}
+
+pub struct CloseFailedSubscription {
+    pub signer: Pubkey,
+    pub subscription: SubscriptionKeys,
+}
+
+impl CloseFailedSubscription {
+    pub fn instruction(&self) -> Instruction {
+        let s = &self.subscription;
+
+        build(
+            accounts::CloseFailedSubscription {
+                signer: self.signer,
+                subscriber: s.subscriber,
+                user_subscription: s.user_subscription(),
+                billing_ledger: s.billing_ledger(),
+                subscription_plan: s.subscription_plan,
+                subscriber_ata: s.subscriber_ata,
+            },
+            data::CloseFailedSubscription {},
+        )
+    }
+}
+
+// normally only run by tuktuk, which gets it from the queued task
+pub struct ChargeUserRecurring {
+    pub subscription: SubscriptionKeys,
+    pub merchant: Pubkey,
+    pub payout_recipients: Vec<Pubkey>,
+}
+
+impl ChargeUserRecurring {
+    pub fn instruction(&self) -> Instruction {
+        let s = &self.subscription;
+
+        let instruction = build(
+            accounts::ChargeUserRecurring {
+                subscriber: s.subscriber,
+                merchant: self.merchant,
+                user_subscription: s.user_subscription(),
+                subscription_plan: s.subscription_plan,
+                subscriber_vault: pda::subscriber_vault(&s.subscriber, &s.mint),
+                subscriber_ata: s.subscriber_ata,
+                merchant_ata: ata(&self.merchant, &s.mint, &s.token_program),
+                accepted_mints: pda::accepted_mints(),
+                mint: s.mint,
+                global_state: pda::global_state(),
+                fee_token_vault: pda::fee_token_vault(&s.mint),
+                task_queue: s.task_queue,
+                billing_ledger: s.billing_ledger(),
+                charge_receipt: s.charge_receipt(),
+                instructions: INSTRUCTIONS_SYSVAR_ID,
+                associated_token_program: associated_token::ID,
+                token_program: s.token_program,
+                system_program: system_program::ID,
+            },
+            data::ChargeUserRecurring {},
+        );
+
+        with_recipients(instruction, &self.payout_recipients)
+    }
+}
+
+pub struct BatchSubscriber {
+    pub subscriber: Pubkey,
+    pub subscriber_ata: Pubkey, // `UserSubscription::subscriber_ata`
+    pub next_receipt: u64,      // `UserSubscription::next_receipt`
+}
+
+pub struct ChargeBatch {
+    pub merchant: Pubkey,
+    pub subscription_plan: Pubkey,
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub payout_recipients: Vec<Pubkey>,
+    pub subscribers: Vec<BatchSubscriber>,
+}
+
+impl ChargeBatch {
+    pub fn instruction(&self) -> Instruction {
+        let mut instruction = with_recipients(
+            build(
+                accounts::ChargeBatch {
+                    merchant: self.merchant,
+                    subscription_plan: self.subscription_plan,
+                    merchant_ata: ata(&self.merchant, &self.mint, &self.token_program),
+                    accepted_mints: pda::accepted_mints(),
+                    mint: self.mint,
+                    global_state: pda::global_state(),
+                    fee_token_vault: pda::fee_token_vault(&self.mint),
+                    token_program: self.token_program,
+                    system_program: system_program::ID,
+                },
+                data::ChargeBatch {},
+            ),
+            &self.payout_recipients,
+        );
+
+        for subscriber in &self.subscribers {
+            let user_subscription =
+                pda::user_subscription(&subscriber.subscriber, &self.subscription_plan);
+
+            instruction.accounts.extend([
+                AccountMeta::new(user_subscription, false),
+                AccountMeta::new(
+                    pda::subscriber_vault(&subscriber.subscriber, &self.mint),
+                    false,
+                ),
+                AccountMeta::new(subscriber.subscriber_ata, false),
+                AccountMeta::new(
+                    pda::charge_receipt(&user_subscription, subscriber.next_receipt),
+                    false,
+                ),
+                // only read if the subscriber created a ledger
+                AccountMeta::new(pda::billing_ledger(&user_subscription), false),
+            ]);
+        }
+
+        instruction
+    }
+}
@@ -0,0 +1,44 @@
+//! Rust client for the capstone subscription program.
+//!
+//! * [`pda`] derives every program account and the tuktuk task accounts
+//! * [`instructions`] has a builder per instruction that fills in all derivable accounts
+//! * [`rpc`] fetches and decodes accounts and looks up the next free task id
+//!
+//! Subscribing to a plan:
+//!
+//! ```ignore
+//! let rpc = Rpc::new("http://localhost:8899");
+//! let plan = rpc.subscription_plan(&plan_address)?;
+//! let task_queue = rpc.global_state()?.task_queue;
+//!
+//! let ix = Subscribe {
+//!     subscriber,
+//!     subscription_plan: plan_address,
+//!     mint: plan.mint,
+//!     task_queue,
+//!     token_program: anchor_spl::token::ID,
+//!     coupon: None,
+//!     args: SubscribeArgs {
+//!         crank_reserve: 100_000,
+//!         task_id: rpc.next_task_id(&task_queue)?,
+//!         billing_mode: BillingMode::Vault,
+//!         spending_cap: None,
+//!     },
+//! }
+//! .instruction();
+//! ```
+
+pub mod instructions;
+pub mod pda;
+pub mod rpc;
+pub mod tasks;
+
+pub use capstone::{
+    instructions::{
+        CreateCouponArgs, CreateSubscriptionArgs, Entitlement, SubscribeArgs, UpdateConfigArgs,
+    },
+    states::*,
+    ID,
+};
+pub use instructions::*;
+pub use rpc::Rpc;
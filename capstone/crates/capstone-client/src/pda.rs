@@ -0,0 +1,92 @@
+use anchor_lang::{prelude::Pubkey, solana_program::hash::hash};
+use capstone::states::{
+    ACCEPTED_MINTS_SEED, COUPON_SEED, FEE_TOKEN_VAULT_SEED, GLOBAL_STATE_SEED, LEDGER_SEED,
+    PLAN_SEED, QUEUE_AUTHORITY_SEED, RECEIPT_SEED, SUBSCRIBER_VAULT_SEED, SUBSCRIPTION_SEED,
+    VAULT_SEED,
+};
+
+fn find(seeds: &[&[u8]]) -> Pubkey {
+    Pubkey::find_program_address(seeds, &capstone::ID).0
+}
+
+pub fn global_state() -> Pubkey {
+    find(&[GLOBAL_STATE_SEED])
+}
+
+pub fn accepted_mints() -> Pubkey {
+    find(&[ACCEPTED_MINTS_SEED])
+}
+
+// SOL vault collecting the plan creation fees
+pub fn fees_vault() -> Pubkey {
+    find(&[VAULT_SEED])
+}
+
+pub fn fee_token_vault(mint: &Pubkey) -> Pubkey {
+    find(&[FEE_TOKEN_VAULT_SEED, mint.as_ref()])
+}
+
+pub fn queue_authority() -> Pubkey {
+    find(&[QUEUE_AUTHORITY_SEED])
+}
+
+// plans are keyed by the hash of their name, so names longer than a seed still work
+pub fn subscription_plan(merchant: &Pubkey, name: &str) -> Pubkey {
+    find(&[PLAN_SEED, merchant.as_ref(), hash(name.as_bytes()).as_ref()])
+}
+
+pub fn user_subscription(subscriber: &Pubkey, subscription_plan: &Pubkey) -> Pubkey {
+    find(&[
+        SUBSCRIPTION_SEED,
+        subscriber.as_ref(),
+        subscription_plan.as_ref(),
+    ])
+}
+
+pub fn subscriber_vault(subscriber: &Pubkey, mint: &Pubkey) -> Pubkey {
+    find(&[SUBSCRIBER_VAULT_SEED, subscriber.as_ref(), mint.as_ref()])
+}
+
+pub fn billing_ledger(user_subscription: &Pubkey) -> Pubkey {
+    find(&[LEDGER_SEED, user_subscription.as_ref()])
+}
+
+pub fn coupon(subscription_plan: &Pubkey, code: &str) -> Pubkey {
+    find(&[
+        COUPON_SEED,
+        subscription_plan.as_ref(),
+        hash(code.as_bytes()).as_ref(),
+    ])
+}
+
+// receipts of a subscription are numbered from `UserSubscription::next_receipt`, the address
+// of a past charge is also in its `ChargeEvent`
+pub fn charge_receipt(user_subscription: &Pubkey, index: u64) -> Pubkey {
+    find(&[
+        RECEIPT_SEED,
+        user_subscription.as_ref(),
+        &index.to_le_bytes(),
+    ])
+}
+
+// tuktuk accounts, owned by the tuktuk program
+
+pub fn task_queue_authority(task_queue: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"task_queue_authority",
+            task_queue.as_ref(),
+            queue_authority().as_ref(),
+        ],
+        &tuktuk_program::tuktuk::ID,
+    )
+    .0
+}
+
+pub fn task(task_queue: &Pubkey, task_id: u16) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"task", task_queue.as_ref(), &task_id.to_le_bytes()],
+        &tuktuk_program::tuktuk::ID,
+    )
+    .0
+}
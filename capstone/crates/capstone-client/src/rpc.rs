@@ -0,0 +1,103 @@
+use anchor_lang::{prelude::Pubkey, AccountDeserialize};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use capstone::states::{BillingLedger, Coupon, GlobalState, SubscriptionPlan, UserSubscription};
+use serde_json::{json, Value};
+use tuktuk_program::TaskQueueV0;
+
+use crate::{pda, tasks::next_available_task_ids};
+
+// decodes an anchor account, checking its discriminator
+pub fn decode<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut &data[..]).map_err(|err| anyhow!("could not decode account: {err}"))
+}
+
+// minimal JSON-RPC client for reading accounts, sending transactions is left to the caller
+pub struct Rpc {
+    url: String,
+}
+
+impl Rpc {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+        }
+    }
+
+    // `None` if the account does not exist
+    pub fn account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>> {
+        let result = self.call(
+            "getAccountInfo",
+            json!([address.to_string(), { "encoding": "base64", "commitment": "confirmed" }]),
+        )?;
+
+        if result["value"].is_null() {
+            return Ok(None);
+        }
+
+        let data = result["value"]["data"][0]
+            .as_str()
+            .ok_or_else(|| anyhow!("unexpected getAccountInfo response"))?;
+
+        Ok(Some(STANDARD.decode(data)?))
+    }
+
+    pub fn fetch<T: AccountDeserialize>(&self, address: &Pubkey) -> Result<T> {
+        let data = self
+            .account_data(address)?
+            .with_context(|| format!("account {address} does not exist"))?;
+
+        decode(&data)
+    }
+
+    pub fn global_state(&self) -> Result<GlobalState> {
+        self.fetch(&pda::global_state())
+    }
+
+    pub fn subscription_plan(&self, address: &Pubkey) -> Result<SubscriptionPlan> {
+        self.fetch(address)
+    }
+
+    pub fn user_subscription(
+        &self,
+        subscriber: &Pubkey,
+        subscription_plan: &Pubkey,
+    ) -> Result<UserSubscription> {
+        self.fetch(&pda::user_subscription(subscriber, subscription_plan))
+    }
+
+    pub fn billing_ledger(&self, user_subscription: &Pubkey) -> Result<BillingLedger> {
+        self.fetch(&pda::billing_ledger(user_subscription))
+    }
+
+    pub fn coupon(&self, subscription_plan: &Pubkey, code: &str) -> Result<Coupon> {
+        self.fetch(&pda::coupon(subscription_plan, code))
+    }
+
+    // first free task id of the queue, pass it as `task_id` to the instructions that queue a task
+    pub fn next_task_id(&self, task_queue: &Pubkey) -> Result<u16> {
+        let task_queue: TaskQueueV0 = self.fetch(task_queue)?;
+
+        next_available_task_ids(&task_queue.task_bitmap, 1)
+            .first()
+            .copied()
+            .ok_or_else(|| anyhow!("task queue is full"))
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let response: Value = ureq::post(&self.url)
+            .send_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))?
+            .into_json()?;
+
+        if let Some(error) = response.get("error") {
+            return Err(anyhow!("{method} failed: {error}"));
+        }
+
+        Ok(response["result"].clone())
+    }
+}
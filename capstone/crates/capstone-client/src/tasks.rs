@@ -0,0 +1,13 @@
+// ids of the first `count` free tasks, a set bit in the queue's bitmap marks a task in use
+pub fn next_available_task_ids(task_bitmap: &[u8], count: usize) -> Vec<u16> {
+    task_bitmap
+        .iter()
+        .enumerate()
+        .flat_map(|(byte, bits)| {
+            (0..8)
+                .filter(move |bit| bits & (1 << bit) == 0)
+                .map(move |bit| (byte * 8 + bit) as u16)
+        })
+        .take(count)
+        .collect()
+}
@@ -0,0 +1,170 @@
+use anchor_lang::{prelude::Pubkey, AccountSerialize, Discriminator};
+use capstone_client::{
+    pda, rpc::decode, tasks::next_available_task_ids, BatchSubscriber, BillingMode, ChargeBatch,
+    PayoutShare, PlanUpdate, Status, Subscribe, SubscribeArgs, UpdatePlan, UserSubscription,
+};
+
+fn key(n: u8) -> Pubkey {
+    Pubkey::new_from_array([n; 32])
+}
+
+#[test]
+fn finds_free_task_ids_in_the_bitmap() {
+    // tasks 0-7 and 9 are taken
+    assert_eq!(next_available_task_ids(&[0xff, 0b10, 0], 3), [8, 10, 11]);
+    assert!(next_available_task_ids(&[0xff], 1).is_empty());
+}
+
+#[test]
+fn subscribe_derives_the_task_and_subscription_accounts() {
+    let (subscriber, plan, mint, task_queue) = (key(1), key(2), key(3), key(4));
+
+    let ix = Subscribe {
+        subscriber,
+        subscription_plan: plan,
+        mint,
+        task_queue,
+        token_program: anchor_spl::token::ID,
+        coupon: None,
+        args: SubscribeArgs {
+            crank_reserve: 100_000,
+            task_id: 7,
+            billing_mode: BillingMode::Vault,
+            spending_cap: None,
+        },
+    }
+    .instruction();
+
+    assert_eq!(ix.program_id, capstone_client::ID);
+    assert!(ix
+        .data
+        .starts_with(capstone::instruction::Subscribe::DISCRIMINATOR));
+
+    let keys: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+    assert_eq!(keys[0], subscriber);
+    assert_eq!(keys[1], pda::user_subscription(&subscriber, &plan));
+    // an omitted optional account is passed as the program id
+    assert_eq!(keys[3], capstone_client::ID);
+    assert!(keys.contains(&pda::task(&task_queue, 7)));
+    assert!(keys.contains(&pda::subscriber_vault(&subscriber, &mint)));
+    assert!(keys.contains(&pda::task_queue_authority(&task_queue)));
+}
+
+#[test]
+fn payout_recipients_follow_the_plan_accounts() {
+    let ix = UpdatePlan {
+        merchant: key(1),
+        subscription_plan: key(2),
+        update: PlanUpdate::SetPayoutSplit {
+            payout_split: vec![
+                PayoutShare {
+                    recipient_ata: key(5),
+                    bps: 6_000,
+                },
+                PayoutShare {
+                    recipient_ata: key(6),
+                    bps: 4_000,
+                },
+            ],
+        },
+    }
+    .instruction();
+
+    let tail: Vec<(Pubkey, bool)> = ix.accounts[2..]
+        .iter()
+        .map(|meta| (meta.pubkey, meta.is_writable))
+        .collect();
+    assert_eq!(tail, [(key(5), true), (key(6), true)]);
+}
+
+#[test]
+fn charge_batch_passes_five_accounts_per_subscriber() {
+    let (plan, mint) = (key(2), key(3));
+
+    let ix = ChargeBatch {
+        merchant: key(1),
+        subscription_plan: plan,
+        mint,
+        token_program: anchor_spl::token::ID,
+        payout_recipients: vec![key(9)],
+        subscribers: vec![
+            BatchSubscriber {
+                subscriber: key(10),
+                subscriber_ata: key(11),
+                next_receipt: 0,
+            },
+            BatchSubscriber {
+                subscriber: key(20),
+                subscriber_ata: key(21),
+                next_receipt: 5,
+            },
+        ],
+    }
+    .instruction();
+
+    // 9 instruction accounts, 1 recipient, 2 groups of 5
+    assert_eq!(ix.accounts.len(), 9 + 1 + 10);
+    assert_eq!(ix.accounts[9].pubkey, key(9));
+
+    let user_subscription = pda::user_subscription(&key(20), &plan);
+    assert_eq!(ix.accounts[15].pubkey, user_subscription);
+    assert_eq!(
+        ix.accounts[16].pubkey,
+        pda::subscriber_vault(&key(20), &mint)
+    );
+    assert_eq!(ix.accounts[17].pubkey, key(21));
+    assert_eq!(
+        ix.accounts[18].pubkey,
+        pda::charge_receipt(&user_subscription, 5)
+    );
+    assert_eq!(
+        ix.accounts[19].pubkey,
+        pda::billing_ledger(&user_subscription)
+    );
+    assert!(ix.accounts[10..].iter().all(|meta| meta.is_writable));
+}
+
+#[test]
+fn decodes_user_subscriptions() {
+    let subscription = UserSubscription {
+        subscriber: key(1),
+        subscriber_ata: key(2),
+        subscription: key(3),
+        billing_mode: BillingMode::Delegated {
+            approved_cycles: 12,
+        },
+        status: Status::PastDue,
+        failure_count: 1,
+        last_exec_ts: 1_700_000_000,
+        resume_at: None,
+        access_until: None,
+        retry_at: Some(1_700_086_400),
+        cycles_charged: 4,
+        last_charge_amount: 1_000_000,
+        credit: 0,
+        usage_units: 0,
+        spending_cap: Some(5_000_000),
+        discount: None,
+        discount_cycles: 0,
+        crank_reserve: 100_000,
+        depleted_status: None,
+        next_task_id: 3,
+        next_receipt: 4,
+        task_queue: key(4),
+        ledger_bump: None,
+        subscriber_vault_bump: 254,
+        bump: 255,
+    };
+
+    let mut data = Vec::new();
+    subscription.try_serialize(&mut data).unwrap();
+
+    let decoded: UserSubscription = decode(&data).unwrap();
+    assert!(decoded.status == Status::PastDue);
+    assert_eq!(decoded.spending_cap, Some(5_000_000));
+    assert_eq!(decoded.retry_at, Some(1_700_086_400));
+    assert_eq!(decoded.next_task_id, 3);
+
+    // a plan account does not decode as a subscription
+    assert!(decode::<UserSubscription>(&data[1..]).is_err());
+}
@@ -0,0 +1,84 @@
+//! Helpers for programs that gate features on a capstone subscription.
+//!
+//! Call [`verify_entitlement`] with the subscriber's `UserSubscription` and its plan, or invoke the
+//! instruction yourself and read the result with [`read_entitlement`].
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program::{get_return_data, invoke},
+    },
+};
+
+declare_id!("ZtzPHWinzmfmxDBeoEUy2JDSt3qGp3pv1BuAFc6nrop");
+
+// sha256("global:verify_entitlement")[..8]
+pub const VERIFY_ENTITLEMENT_DISCRIMINATOR: [u8; 8] = [133, 173, 131, 32, 66, 85, 138, 83];
+
+// same layout as `PricingTier` in the capstone program
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PricingTier {
+    Standard,
+    Introductory,
+    Trial,
+}
+
+// same layout as `Entitlement` in the capstone program
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
+pub struct Entitlement {
+    pub active: bool,
+    pub plan: Pubkey,
+    pub paid_through: i64,
+    pub tier: PricingTier,
+}
+
+#[error_code]
+pub enum EntitlementError {
+    #[msg("capstone did not return an entitlement")]
+    MissingReturnData,
+    #[msg("return data is not a capstone entitlement")]
+    InvalidReturnData,
+}
+
+pub fn verify_entitlement_instruction(
+    user_subscription: Pubkey,
+    subscription_plan: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: vec![
+            AccountMeta::new_readonly(user_subscription, false),
+            AccountMeta::new_readonly(subscription_plan, false),
+        ],
+        data: VERIFY_ENTITLEMENT_DISCRIMINATOR.to_vec(),
+    }
+}
+
+// invokes `verify_entitlement` and returns its result
+pub fn verify_entitlement<'info>(
+    capstone_program: AccountInfo<'info>,
+    user_subscription: AccountInfo<'info>,
+    subscription_plan: AccountInfo<'info>,
+) -> Result<Entitlement> {
+    require_keys_eq!(capstone_program.key(), ID, ErrorCode::InvalidProgramId);
+
+    let instruction =
+        verify_entitlement_instruction(user_subscription.key(), subscription_plan.key());
+
+    invoke(
+        &instruction,
+        &[user_subscription, subscription_plan, capstone_program],
+    )?;
+
+    read_entitlement()
+}
+
+// reads the result of the last `verify_entitlement` call in this instruction
+pub fn read_entitlement() -> Result<Entitlement> {
+    let (program_id, data) = get_return_data().ok_or(EntitlementError::MissingReturnData)?;
+
+    require_keys_eq!(program_id, ID, EntitlementError::InvalidReturnData);
+
+    Entitlement::try_from_slice(&data).map_err(|_| error!(EntitlementError::InvalidReturnData))
+}
@@ -0,0 +1,74 @@
+//! The helper crate copies the program's `Entitlement` layout and instruction discriminator
+//! instead of depending on the program, these tests keep the two in sync.
+
+use anchor_lang::{prelude::*, Discriminator, InstructionData};
+use capstone::states::PricingTier as ProgramTier;
+use capstone_entitlement::{
+    verify_entitlement_instruction, Entitlement, PricingTier, VERIFY_ENTITLEMENT_DISCRIMINATOR,
+};
+
+#[test]
+fn program_id_matches() {
+    assert_eq!(capstone_entitlement::ID, capstone::ID);
+}
+
+#[test]
+fn instruction_matches_the_program() {
+    let user_subscription = Pubkey::new_unique();
+    let subscription_plan = Pubkey::new_unique();
+    let instruction = verify_entitlement_instruction(user_subscription, subscription_plan);
+
+    assert_eq!(
+        VERIFY_ENTITLEMENT_DISCRIMINATOR,
+        capstone::instruction::VerifyEntitlement::DISCRIMINATOR
+    );
+    assert_eq!(
+        instruction.data,
+        capstone::instruction::VerifyEntitlement {}.data()
+    );
+    assert_eq!(
+        instruction.accounts,
+        capstone::accounts::VerifyEntitlement {
+            user_subscription,
+            subscription_plan,
+        }
+        .to_account_metas(None)
+    );
+}
+
+#[test]
+fn decodes_what_the_program_returns() {
+    let tiers = [
+        (ProgramTier::Standard, PricingTier::Standard),
+        (ProgramTier::Introductory, PricingTier::Introductory),
+        (ProgramTier::Trial, PricingTier::Trial),
+    ];
+
+    for (active, (program_tier, tier)) in [true, false].into_iter().zip(tiers.into_iter().cycle()) {
+        let plan = Pubkey::new_unique();
+        let returned = capstone::instructions::Entitlement {
+            active,
+            plan,
+            paid_through: 1_767_225_600,
+            tier: program_tier,
+        };
+
+        let decoded = Entitlement::try_from_slice(&returned.try_to_vec().unwrap()).unwrap();
+
+        assert_eq!(
+            decoded,
+            Entitlement {
+                active,
+                plan,
+                paid_through: 1_767_225_600,
+                tier,
+            }
+        );
+    }
+
+    // every tier round trips the other way as well
+    for (program_tier, tier) in tiers {
+        let decoded = ProgramTier::try_from_slice(&tier.try_to_vec().unwrap()).unwrap();
+        assert_eq!(decoded, program_tier);
+    }
+}
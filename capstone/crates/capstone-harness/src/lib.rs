@@ -0,0 +1,500 @@
+//! In-process test harness for the capstone program on LiteSVM.
+//!
+//! [`Harness::new`] loads `capstone.so` and `tuktuk_stub.so` from `target/deploy`, seeds the
+//! global state, a task queue and an accepted mint, and [`Harness::fire`] runs a queued tuktuk
+//! task the way a crank turner would. The clock only moves through [`Harness::warp_to`], so a
+//! whole subscription lifecycle runs in one test without devnet.
+//!
+//! ```ignore
+//! let mut harness = Harness::new();
+//! let merchant = harness.new_user();
+//! let plan = harness.create_plan(&merchant, args);
+//!
+//! let subscriber = harness.new_user();
+//! harness.set_token_balance(&subscriber.pubkey(), 50_000_000);
+//! harness.subscribe(&subscriber, &plan).unwrap();
+//!
+//! harness.warp_to(harness.now() + 30 * DAY);
+//! harness.fire_due();
+//! ```
+
+use std::path::PathBuf;
+
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program_option::COption,
+        program_pack::Pack,
+    },
+    system_program, AccountDeserialize, AccountSerialize, Discriminator, InstructionData, Space,
+    ToAccountMetas,
+};
+use anchor_spl::{associated_token::get_associated_token_address, token::spl_token};
+use capstone_client::{
+    pda, AcceptedMints, BillingMode, CreateSubscription, CreateSubscriptionArgs, GlobalState,
+    Subscribe, SubscribeArgs, SubscriptionKeys, UpdateAcceptedMints, UserSubscription,
+    ACCEPTED_MINTS_SEED, CRANK_REWARD, FEES, GLOBAL_STATE_SEED, QUEUE_AUTHORITY_SEED,
+};
+use litesvm::{types::TransactionResult, LiteSVM};
+use solana_sdk::{
+    account::Account, clock::Clock, compute_budget::ComputeBudgetInstruction,
+    native_token::LAMPORTS_PER_SOL, signature::Keypair, signer::Signer, transaction::Transaction,
+};
+use tuktuk_program::{types::RunTaskArgsV0, TaskQueueAuthorityV0};
+use tuktuk_stub::{is_writable, QueuedTask};
+
+pub use litesvm;
+pub use tuktuk_stub;
+
+pub const DAY: i64 = 86_400;
+pub const DECIMALS: u8 = 6;
+pub const START_TS: i64 = 1_767_225_600; // 2026-01-01
+                                         // crank rewards and charge receipts of every charge a test runs
+pub const CRANK_RESERVE: u64 = LAMPORTS_PER_SOL / 10;
+
+const MAX_TASK_ID: u16 = 64; // ids scanned for queued tasks, plenty for a test
+const FREE_TASKS_PER_RUN: u8 = 1; // a charge returns at most one task
+const COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+pub struct Harness {
+    pub svm: LiteSVM,
+    pub admin: Keypair,
+    pub crank_turner: Keypair,
+    pub task_queue: Pubkey,
+    pub mint: Pubkey,
+}
+
+impl Default for Harness {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Harness {
+    // panics while the programs are not built, see "Integration Tests" in the readme
+    pub fn new() -> Self {
+        let capstone_so = deploy_dir().join("capstone.so");
+        let tuktuk_so = deploy_dir().join("tuktuk_stub.so");
+
+        assert!(
+            capstone_so.exists() && tuktuk_so.exists(),
+            "{} or {} is missing, build them with `anchor build` and \
+             `cargo build-sbf --manifest-path crates/tuktuk-stub/Cargo.toml`",
+            capstone_so.display(),
+            tuktuk_so.display()
+        );
+
+        let mut svm = LiteSVM::new();
+        svm.add_program_from_file(capstone_client::ID, capstone_so)
+            .expect("load capstone.so");
+        svm.add_program_from_file(tuktuk_stub::ID, tuktuk_so)
+            .expect("load tuktuk_stub.so");
+
+        let mut harness = Self {
+            svm,
+            admin: Keypair::new(),
+            crank_turner: Keypair::new(),
+            task_queue: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+        };
+
+        harness.warp_to(START_TS);
+        harness.airdrop(&harness.admin.pubkey());
+        harness.airdrop(&harness.crank_turner.pubkey());
+        harness.seed_global_state();
+        harness.seed_task_queue();
+        harness.seed_mint();
+
+        let admin = harness.admin.insecure_clone();
+        harness
+            .send(
+                &[UpdateAcceptedMints {
+                    signer: admin.pubkey(),
+                    mint: harness.mint,
+                    token_program: spl_token::ID,
+                    add: true,
+                }
+                .instruction()],
+                &[&admin],
+            )
+            .expect("add accepted mint");
+
+        harness
+    }
+
+    // `initialize` needs an upgradeable deployment with program data, so the accounts it creates
+    // are written directly
+    fn seed_global_state(&mut self) {
+        let (global_state, bump) =
+            Pubkey::find_program_address(&[GLOBAL_STATE_SEED], &capstone_client::ID);
+        let (queue_authority, queue_authority_bump) =
+            Pubkey::find_program_address(&[QUEUE_AUTHORITY_SEED], &capstone_client::ID);
+        let (accepted_mints, accepted_mints_bump) =
+            Pubkey::find_program_address(&[ACCEPTED_MINTS_SEED], &capstone_client::ID);
+
+        self.set_anchor_account(
+            global_state,
+            capstone_client::ID,
+            GlobalState::DISCRIMINATOR.len() + GlobalState::INIT_SPACE,
+            &GlobalState {
+                admin: self.admin.pubkey(),
+                pending_admin: None,
+                task_queue: self.task_queue,
+                queue_authority,
+                fees: FEES,
+                protocol_fee_bps: 0,
+                crank_reward: CRANK_REWARD,
+                paused: false,
+                queue_authority_bump,
+                bump,
+            },
+        );
+        self.set_anchor_account(
+            accepted_mints,
+            capstone_client::ID,
+            AcceptedMints::DISCRIMINATOR.len() + AcceptedMints::INIT_SPACE,
+            &AcceptedMints {
+                mints: vec![],
+                bump: accepted_mints_bump,
+            },
+        );
+    }
+
+    // the stub keeps no queue state, the account only has to exist and receive crank rewards
+    fn seed_task_queue(&mut self) {
+        self.set_account(self.task_queue, tuktuk_stub::ID, vec![]);
+
+        let queue_authority = pda::queue_authority();
+        let (task_queue_authority, bump_seed) = Pubkey::find_program_address(
+            &[
+                b"task_queue_authority",
+                self.task_queue.as_ref(),
+                queue_authority.as_ref(),
+            ],
+            &tuktuk_stub::ID,
+        );
+
+        self.set_anchor_account(
+            task_queue_authority,
+            tuktuk_stub::ID,
+            0,
+            &TaskQueueAuthorityV0 {
+                task_queue: self.task_queue,
+                queue_authority,
+                bump_seed,
+            },
+        );
+    }
+
+    fn seed_mint(&mut self) {
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint::pack(
+            spl_token::state::Mint {
+                mint_authority: COption::None,
+                supply: 0,
+                decimals: DECIMALS,
+                is_initialized: true,
+                freeze_authority: COption::None,
+            },
+            &mut data,
+        )
+        .unwrap();
+
+        self.set_account(self.mint, spl_token::ID, data);
+    }
+
+    fn set_account(&mut self, address: Pubkey, owner: Pubkey, data: Vec<u8>) {
+        let account = Account {
+            lamports: self.svm.minimum_balance_for_rent_exemption(data.len()),
+            data,
+            owner,
+            executable: false,
+            rent_epoch: 0,
+        };
+
+        self.svm.set_account(address, account).unwrap();
+    }
+
+    // zero padded up to `space`, so fixed size accounts keep room for their `max_len` fields
+    fn set_anchor_account<T: AccountSerialize>(
+        &mut self,
+        address: Pubkey,
+        owner: Pubkey,
+        space: usize,
+        value: &T,
+    ) {
+        let mut data = Vec::with_capacity(space);
+        value.try_serialize(&mut data).unwrap();
+        data.resize(data.len().max(space), 0);
+
+        self.set_account(address, owner, data);
+    }
+
+    pub fn airdrop(&mut self, address: &Pubkey) {
+        self.svm.airdrop(address, 100 * LAMPORTS_PER_SOL).unwrap();
+    }
+
+    pub fn lamports(&self, address: &Pubkey) -> u64 {
+        self.svm
+            .get_account(address)
+            .map_or(0, |account| account.lamports)
+    }
+
+    pub fn new_user(&mut self) -> Keypair {
+        let user = Keypair::new();
+        self.airdrop(&user.pubkey());
+        user
+    }
+
+    // the first signer pays, every transaction gets a fresh blockhash so repeats are not dropped
+    pub fn send(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> TransactionResult {
+        self.svm.expire_blockhash();
+
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signers[0].pubkey()),
+            signers,
+            self.svm.latest_blockhash(),
+        );
+
+        self.svm.send_transaction(transaction)
+    }
+
+    pub fn fetch<T: AccountDeserialize>(&self, address: &Pubkey) -> Option<T> {
+        let account = self.svm.get_account(address)?;
+        T::try_deserialize(&mut account.data.as_slice()).ok()
+    }
+
+    // clock
+
+    pub fn now(&self) -> i64 {
+        self.svm.get_sysvar::<Clock>().unix_timestamp
+    }
+
+    pub fn warp_to(&mut self, unix_timestamp: i64) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.unix_timestamp = unix_timestamp;
+        clock.slot += 1;
+
+        self.svm.set_sysvar::<Clock>(&clock);
+    }
+
+    // tokens
+
+    // creates or overwrites the owner's associated token account of the harness mint
+    pub fn set_token_balance(&mut self, owner: &Pubkey, amount: u64) -> Pubkey {
+        let address = get_associated_token_address(owner, &self.mint);
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account::pack(
+            spl_token::state::Account {
+                mint: self.mint,
+                owner: *owner,
+                amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..Default::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+
+        self.set_account(address, spl_token::ID, data);
+        address
+    }
+
+    // overwrites the amount of an existing token account, e.g. to drain a subscriber vault
+    pub fn set_token_amount(&mut self, address: &Pubkey, amount: u64) {
+        let mut account = self.svm.get_account(address).expect("token account");
+        let mut token = spl_token::state::Account::unpack(&account.data).unwrap();
+        token.amount = amount;
+        spl_token::state::Account::pack(token, &mut account.data).unwrap();
+
+        self.svm.set_account(*address, account).unwrap();
+    }
+
+    pub fn token_balance(&self, address: &Pubkey) -> u64 {
+        self.svm
+            .get_account(address)
+            .and_then(|account| spl_token::state::Account::unpack(&account.data).ok())
+            .map_or(0, |token| token.amount)
+    }
+
+    pub fn token_delegate(&self, address: &Pubkey) -> Option<Pubkey> {
+        self.svm
+            .get_account(address)
+            .and_then(|account| spl_token::state::Account::unpack(&account.data).ok())
+            .and_then(|token| token.delegate.into())
+    }
+
+    // capstone
+
+    pub fn create_plan(&mut self, merchant: &Keypair, args: CreateSubscriptionArgs) -> Pubkey {
+        let plan = pda::subscription_plan(&merchant.pubkey(), &args.name);
+        let instruction = CreateSubscription {
+            merchant: merchant.pubkey(),
+            mint: self.mint,
+            token_program: spl_token::ID,
+            args,
+        }
+        .instruction();
+
+        self.send(&[instruction], &[merchant]).expect("create plan");
+        plan
+    }
+
+    // vault billing without a coupon or spending cap, queues the first charge at the next free id
+    pub fn subscribe(
+        &mut self,
+        subscriber: &Keypair,
+        subscription_plan: &Pubkey,
+    ) -> TransactionResult {
+        self.subscribe_with(subscriber, subscription_plan, BillingMode::Vault)
+    }
+
+    pub fn subscribe_with(
+        &mut self,
+        subscriber: &Keypair,
+        subscription_plan: &Pubkey,
+        billing_mode: BillingMode,
+    ) -> TransactionResult {
+        let task_id = self.free_task_ids(1)[0];
+        let instruction = Subscribe {
+            subscriber: subscriber.pubkey(),
+            subscription_plan: *subscription_plan,
+            mint: self.mint,
+            task_queue: self.task_queue,
+            token_program: spl_token::ID,
+            coupon: None,
+            args: SubscribeArgs {
+                crank_reserve: CRANK_RESERVE,
+                task_id,
+                billing_mode,
+                spending_cap: None,
+            },
+        }
+        .instruction();
+
+        self.send(&[instruction], &[subscriber])
+    }
+
+    pub fn user_subscription(
+        &self,
+        subscriber: &Pubkey,
+        subscription_plan: &Pubkey,
+    ) -> Option<UserSubscription> {
+        self.fetch(&pda::user_subscription(subscriber, subscription_plan))
+    }
+
+    // current keys of a subscription, `next_task_id` moves with every charge
+    pub fn subscription_keys(
+        &self,
+        subscriber: &Pubkey,
+        subscription_plan: &Pubkey,
+    ) -> Option<SubscriptionKeys> {
+        let subscription = self.user_subscription(subscriber, subscription_plan)?;
+
+        Some(SubscriptionKeys {
+            subscriber: *subscriber,
+            subscription_plan: *subscription_plan,
+            mint: self.mint,
+            subscriber_ata: subscription.subscriber_ata,
+            task_queue: subscription.task_queue,
+            next_task_id: subscription.next_task_id,
+            next_receipt: subscription.next_receipt,
+            has_ledger: subscription.ledger_bump.is_some(),
+            token_program: spl_token::ID,
+        })
+    }
+
+    // tuktuk
+
+    pub fn task(&self, task_id: u16) -> Option<QueuedTask> {
+        self.fetch(&pda::task(&self.task_queue, task_id))
+    }
+
+    pub fn tasks(&self) -> Vec<(u16, QueuedTask)> {
+        (0..MAX_TASK_ID)
+            .filter_map(|id| self.task(id).map(|task| (id, task)))
+            .collect()
+    }
+
+    pub fn free_task_ids(&self, count: usize) -> Vec<u16> {
+        (0..MAX_TASK_ID)
+            .filter(|id| {
+                self.svm
+                    .get_account(&pda::task(&self.task_queue, *id))
+                    .is_none()
+            })
+            .take(count)
+            .collect()
+    }
+
+    // runs a queued task as a crank turner would, with its instructions' accounts and free task ids
+    pub fn fire(&mut self, task_id: u16) -> TransactionResult {
+        let task_address = pda::task(&self.task_queue, task_id);
+        let task = self.task(task_id).expect("task is queued");
+        let transaction = task.transaction().expect("compiled transaction");
+        let free_task_ids =
+            self.free_task_ids(task.args.free_tasks.min(FREE_TASKS_PER_RUN) as usize);
+        let crank_turner = self.crank_turner.insecure_clone();
+
+        let mut accounts = tuktuk_stub::accounts::RunTaskV0 {
+            crank_turner: crank_turner.pubkey(),
+            task_queue: self.task_queue,
+            task: task_address,
+            system_program: system_program::ID,
+        }
+        .to_account_metas(None);
+        accounts.extend(
+            free_task_ids
+                .iter()
+                .map(|id| AccountMeta::new(pda::task(&self.task_queue, *id), false)),
+        );
+        accounts.extend(transaction.accounts.iter().enumerate().map(|(index, key)| {
+            if is_writable(transaction, index) {
+                AccountMeta::new(*key, false)
+            } else {
+                AccountMeta::new_readonly(*key, false)
+            }
+        }));
+
+        let run_task = Instruction {
+            program_id: tuktuk_stub::ID,
+            accounts,
+            data: tuktuk_stub::instruction::RunTaskV0 {
+                args: RunTaskArgsV0 { free_task_ids },
+            }
+            .data(),
+        };
+
+        self.send(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(COMPUTE_UNIT_LIMIT),
+                run_task,
+            ],
+            &[&crank_turner],
+        )
+    }
+
+    // fires every task whose trigger has passed, in task id order
+    pub fn fire_due(&mut self) -> Vec<(u16, TransactionResult)> {
+        let now = self.now();
+        let due: Vec<u16> = self
+            .tasks()
+            .into_iter()
+            .filter(|(_, task)| task.is_due(now))
+            .map(|(id, _)| id)
+            .collect();
+
+        due.into_iter().map(|id| (id, self.fire(id))).collect()
+    }
+}
+
+// `anchor build` and `cargo build-sbf` both write to the workspace target directory
+pub fn deploy_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../target/deploy")
+}
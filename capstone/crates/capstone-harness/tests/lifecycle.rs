@@ -0,0 +1,354 @@
+use anchor_spl::associated_token::get_associated_token_address;
+use capstone_client::{
+    pda, BatchSubscriber, BillingInterval, BillingMode, CancelSubscription, ChargeBatch,
+    CreateSubscriptionArgs, PayoutShare, PlanUpdate, RetryPolicy, Status, SubscriptionPlan,
+    UpdatePlan,
+};
+use capstone_harness::{litesvm::types::TransactionResult, Harness, DAY, START_TS};
+use solana_sdk::signer::Signer;
+
+const AMOUNT: u64 = 10_000_000;
+const INTERVAL: i64 = 30 * DAY;
+
+fn plan_args() -> CreateSubscriptionArgs {
+    CreateSubscriptionArgs {
+        name: "monthly".to_string(),
+        amount: AMOUNT,
+        interval: BillingInterval::Seconds { seconds: INTERVAL },
+        max_failure_count: 2,
+        trial_period: 0,
+        intro_amount: 0,
+        intro_cycles: 0,
+        retry_policy: RetryPolicy::Fixed { delay: DAY },
+        grace_period: 0,
+        proration_enabled: false,
+        metering: None,
+        batch_billing: false,
+    }
+}
+
+fn assert_ok(result: &TransactionResult) {
+    assert!(result.is_ok(), "transaction failed: {result:?}");
+}
+
+// fires every due task and returns how many ran
+fn fire_due(harness: &mut Harness) -> usize {
+    let fired = harness.fire_due();
+    fired.iter().for_each(|(_, result)| assert_ok(result));
+    fired.len()
+}
+
+#[test]
+#[ignore = "needs the built programs, see the readme"]
+fn subscribe_charge_fail_cancel() {
+    let mut harness = Harness::new();
+
+    let merchant = harness.new_user();
+    let plan = harness.create_plan(&merchant, plan_args());
+    let merchant_ata = get_associated_token_address(&merchant.pubkey(), &harness.mint);
+
+    // enough for the first cycle only, subscribe moves it into the vault
+    let subscriber = harness.new_user();
+    let subscriber_ata = harness.set_token_balance(&subscriber.pubkey(), AMOUNT + AMOUNT / 2);
+    let vault = pda::subscriber_vault(&subscriber.pubkey(), &harness.mint);
+
+    assert_ok(&harness.subscribe(&subscriber, &plan));
+    assert_eq!(harness.token_balance(&vault), AMOUNT);
+    assert_eq!(harness.token_balance(&subscriber_ata), AMOUNT / 2);
+
+    let tasks = harness.tasks();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].1.trigger_ts(), None);
+
+    // first cycle is charged right away and the next one is queued a period later
+    assert_eq!(fire_due(&mut harness), 1);
+    assert_eq!(harness.token_balance(&vault), 0);
+    assert_eq!(harness.token_balance(&merchant_ata), AMOUNT);
+
+    let subscription = harness
+        .user_subscription(&subscriber.pubkey(), &plan)
+        .unwrap();
+    assert!(subscription.status == Status::Active);
+    assert_eq!(subscription.cycles_charged, 1);
+    assert_eq!(subscription.last_exec_ts, START_TS + INTERVAL);
+
+    let tasks = harness.tasks();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].0, subscription.next_task_id);
+    assert_eq!(tasks[0].1.trigger_ts(), Some(START_TS + INTERVAL));
+
+    // the stub holds tasks until their trigger
+    assert!(harness.fire(subscription.next_task_id).is_err());
+    assert_eq!(fire_due(&mut harness), 0);
+
+    // the vault is empty at the next due date, two retries a day apart are allowed
+    harness.warp_to(START_TS + INTERVAL);
+
+    for failure_count in 1..=2 {
+        assert_eq!(fire_due(&mut harness), 1);
+
+        let subscription = harness
+            .user_subscription(&subscriber.pubkey(), &plan)
+            .unwrap();
+        assert!(subscription.status == Status::PastDue);
+        assert_eq!(subscription.failure_count, failure_count);
+        assert_eq!(subscription.last_exec_ts, START_TS + INTERVAL);
+        assert_eq!(harness.tasks().len(), 1);
+
+        harness.warp_to(harness.now() + DAY);
+    }
+
+    // out of retries, the task chain ends
+    assert_eq!(fire_due(&mut harness), 1);
+    assert!(harness.tasks().is_empty());
+    assert_eq!(harness.token_balance(&merchant_ata), AMOUNT);
+
+    let subscription = harness
+        .user_subscription(&subscriber.pubkey(), &plan)
+        .unwrap();
+    assert!(subscription.status == Status::Failed);
+
+    // cancelling a failed subscription has no task to dequeue, it only closes the account
+    let keys = harness
+        .subscription_keys(&subscriber.pubkey(), &plan)
+        .unwrap();
+    let cancel = CancelSubscription { subscription: keys }.instruction();
+    assert_ok(&harness.send(&[cancel], &[&subscriber]));
+
+    assert!(harness
+        .user_subscription(&subscriber.pubkey(), &plan)
+        .is_none());
+    assert_eq!(
+        harness
+            .fetch::<SubscriptionPlan>(&plan)
+            .unwrap()
+            .subscriber_count,
+        0
+    );
+}
+
+#[test]
+#[ignore = "needs the built programs, see the readme"]
+fn cancel_dequeues_the_next_charge() {
+    let mut harness = Harness::new();
+
+    let merchant = harness.new_user();
+    let plan = harness.create_plan(&merchant, plan_args());
+
+    let subscriber = harness.new_user();
+    harness.set_token_balance(&subscriber.pubkey(), 3 * AMOUNT);
+
+    assert_ok(&harness.subscribe(&subscriber, &plan));
+    assert_eq!(fire_due(&mut harness), 1);
+    assert_eq!(harness.tasks().len(), 1);
+
+    let keys = harness
+        .subscription_keys(&subscriber.pubkey(), &plan)
+        .unwrap();
+    let cancel = CancelSubscription { subscription: keys }.instruction();
+    assert_ok(&harness.send(&[cancel], &[&subscriber]));
+
+    assert!(harness.tasks().is_empty());
+    assert!(harness
+        .user_subscription(&subscriber.pubkey(), &plan)
+        .is_none());
+
+    // nothing is left to charge the subscriber later
+    harness.warp_to(START_TS + INTERVAL);
+    assert_eq!(fire_due(&mut harness), 0);
+}
+
+#[test]
+#[ignore = "needs the built programs, see the readme"]
+fn calendar_months_charge_on_the_anchor_day() {
+    let mut harness = Harness::new();
+
+    let merchant = harness.new_user();
+    let args = CreateSubscriptionArgs {
+        interval: BillingInterval::CalendarMonth { anchor_day: 31 },
+        ..plan_args()
+    };
+    let plan = harness.create_plan(&merchant, args);
+
+    let subscriber = harness.new_user();
+    harness.set_token_balance(&subscriber.pubkey(), AMOUNT);
+    assert_ok(&harness.subscribe(&subscriber, &plan));
+
+    // prefund every cycle of the test
+    let vault = pda::subscriber_vault(&subscriber.pubkey(), &harness.mint);
+    harness.set_token_amount(&vault, 4 * AMOUNT);
+
+    // charged on subscribing, then on the 31st or the last day of shorter months
+    let due_dates = [
+        START_TS + 30 * DAY,  // 2026-01-31
+        START_TS + 58 * DAY,  // 2026-02-28
+        START_TS + 89 * DAY,  // 2026-03-31
+        START_TS + 119 * DAY, // 2026-04-30
+    ];
+
+    assert_eq!(fire_due(&mut harness), 1);
+
+    for (cycle, due) in due_dates.into_iter().enumerate() {
+        let subscription = harness
+            .user_subscription(&subscriber.pubkey(), &plan)
+            .unwrap();
+        assert_eq!(subscription.cycles_charged, cycle as u32 + 1);
+        assert_eq!(subscription.last_exec_ts, due);
+        assert_eq!(harness.tasks()[0].1.trigger_ts(), Some(due));
+
+        if cycle + 1 < due_dates.len() {
+            harness.warp_to(due);
+            assert_eq!(fire_due(&mut harness), 1);
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs the built programs, see the readme"]
+fn a_second_delegated_subscription_does_not_take_over_the_allowance() {
+    let mut harness = Harness::new();
+
+    let merchant = harness.new_user();
+    let plan = harness.create_plan(&merchant, plan_args());
+    let other_plan = harness.create_plan(
+        &merchant,
+        CreateSubscriptionArgs {
+            name: "other".to_string(),
+            ..plan_args()
+        },
+    );
+
+    let subscriber = harness.new_user();
+    let subscriber_ata = harness.set_token_balance(&subscriber.pubkey(), 6 * AMOUNT);
+    let delegated = BillingMode::Delegated { approved_cycles: 3 };
+
+    assert_ok(&harness.subscribe_with(&subscriber, &plan, delegated));
+    assert_eq!(harness.token_delegate(&subscriber_ata), Some(plan));
+
+    // the ata has a single delegate, the allowance of the first plan stays
+    assert!(harness
+        .subscribe_with(&subscriber, &other_plan, delegated)
+        .is_err());
+    assert_eq!(harness.token_delegate(&subscriber_ata), Some(plan));
+
+    // a vault subscription needs no allowance
+    assert_ok(&harness.subscribe(&subscriber, &other_plan));
+}
+
+#[test]
+#[ignore = "needs the built programs, see the readme"]
+fn charges_continue_after_the_payout_split_changes() {
+    let mut harness = Harness::new();
+
+    let merchant = harness.new_user();
+    let plan = harness.create_plan(&merchant, plan_args());
+
+    let subscriber = harness.new_user();
+    harness.set_token_balance(&subscriber.pubkey(), AMOUNT);
+    assert_ok(&harness.subscribe(&subscriber, &plan));
+    assert_eq!(fire_due(&mut harness), 1);
+
+    // the queued task still carries the recipients of the old, empty split
+    let partner = harness.new_user();
+    let partner_ata = harness.set_token_balance(&partner.pubkey(), 0);
+    let update = UpdatePlan {
+        merchant: merchant.pubkey(),
+        subscription_plan: plan,
+        update: PlanUpdate::SetPayoutSplit {
+            payout_split: vec![PayoutShare {
+                recipient_ata: partner_ata,
+                bps: 10_000,
+            }],
+        },
+    }
+    .instruction();
+    assert_ok(&harness.send(&[update], &[&merchant]));
+
+    let vault = pda::subscriber_vault(&subscriber.pubkey(), &harness.mint);
+    harness.set_token_amount(&vault, AMOUNT);
+    harness.warp_to(START_TS + INTERVAL);
+
+    // the stale task only requeues itself for the same due date, the new one charges
+    assert_eq!(fire_due(&mut harness), 1);
+    assert_eq!(harness.token_balance(&vault), AMOUNT);
+    assert_eq!(fire_due(&mut harness), 1);
+    assert_eq!(harness.token_balance(&vault), 0);
+    assert!(harness.token_balance(&partner_ata) > 0);
+
+    let subscription = harness
+        .user_subscription(&subscriber.pubkey(), &plan)
+        .unwrap();
+    assert_eq!(subscription.cycles_charged, 2);
+    assert_eq!(subscription.last_exec_ts, START_TS + 2 * INTERVAL);
+    assert_eq!(harness.tasks().len(), 1);
+}
+
+#[test]
+#[ignore = "needs the built programs, see the readme"]
+fn batch_billed_plans_queue_no_task_and_retry_in_the_batch() {
+    let mut harness = Harness::new();
+
+    let merchant = harness.new_user();
+    let plan = harness.create_plan(
+        &merchant,
+        CreateSubscriptionArgs {
+            batch_billing: true,
+            ..plan_args()
+        },
+    );
+    let merchant_ata = get_associated_token_address(&merchant.pubkey(), &harness.mint);
+
+    let subscriber = harness.new_user();
+    let subscriber_ata = harness.set_token_balance(&subscriber.pubkey(), AMOUNT);
+    let vault = pda::subscriber_vault(&subscriber.pubkey(), &harness.mint);
+    assert_ok(&harness.subscribe(&subscriber, &plan));
+    assert!(harness.tasks().is_empty());
+
+    let charge_batch = |harness: &mut Harness| {
+        let next_receipt = harness
+            .user_subscription(&subscriber.pubkey(), &plan)
+            .unwrap()
+            .next_receipt;
+        let instruction = ChargeBatch {
+            merchant: merchant.pubkey(),
+            subscription_plan: plan,
+            mint: harness.mint,
+            token_program: anchor_spl::token::ID,
+            payout_recipients: vec![],
+            subscribers: vec![BatchSubscriber {
+                subscriber: subscriber.pubkey(),
+                subscriber_ata,
+                next_receipt,
+            }],
+        }
+        .instruction();
+
+        assert_ok(&harness.send(&[instruction], &[&merchant]));
+        harness
+            .user_subscription(&subscriber.pubkey(), &plan)
+            .unwrap()
+    };
+
+    let subscription = charge_batch(&mut harness);
+    assert_eq!(subscription.cycles_charged, 1);
+    assert_eq!(subscription.last_exec_ts, START_TS + INTERVAL);
+    assert_eq!(harness.token_balance(&merchant_ata), AMOUNT);
+
+    // the empty vault fails the next cycle, the retry waits for `retry_at`
+    harness.warp_to(START_TS + INTERVAL);
+    let subscription = charge_batch(&mut harness);
+    assert!(subscription.status == Status::PastDue);
+    assert_eq!(subscription.retry_at, Some(START_TS + INTERVAL + DAY));
+
+    harness.set_token_amount(&vault, AMOUNT);
+    assert_eq!(charge_batch(&mut harness).failure_count, 1);
+    assert_eq!(harness.token_balance(&vault), AMOUNT);
+
+    harness.warp_to(START_TS + INTERVAL + DAY);
+    let subscription = charge_batch(&mut harness);
+    assert!(subscription.status == Status::Active);
+    assert_eq!(subscription.cycles_charged, 2);
+    assert_eq!(subscription.retry_at, None);
+    assert_eq!(subscription.last_exec_ts, START_TS + 2 * INTERVAL);
+    assert!(harness.tasks().is_empty());
+}
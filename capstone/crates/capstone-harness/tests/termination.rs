@@ -0,0 +1,203 @@
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::Instruction};
+use capstone_client::{
+    pda, BillingInterval, ChargeReceipt, CloseFailedSubscription, CreateSubscriptionArgs,
+    RefundCharge, RetryPolicy, Status, SubscriptionPlan, TerminateSubscription, TerminationReason,
+};
+use capstone_harness::{litesvm::types::TransactionResult, Harness, DAY, START_TS};
+use solana_sdk::signer::Signer;
+
+const AMOUNT: u64 = 10_000_000;
+const INTERVAL: i64 = 30 * DAY;
+
+// the first failed charge ends the subscription
+fn plan_args() -> CreateSubscriptionArgs {
+    CreateSubscriptionArgs {
+        name: "no retries".to_string(),
+        amount: AMOUNT,
+        interval: BillingInterval::Seconds { seconds: INTERVAL },
+        max_failure_count: 0,
+        trial_period: 0,
+        intro_amount: 0,
+        intro_cycles: 0,
+        retry_policy: RetryPolicy::Fixed { delay: DAY },
+        grace_period: 0,
+        proration_enabled: false,
+        metering: None,
+        batch_billing: false,
+    }
+}
+
+fn assert_ok(result: &TransactionResult) {
+    assert!(result.is_ok(), "transaction failed: {result:?}");
+}
+
+fn fire_due(harness: &mut Harness) -> usize {
+    let fired = harness.fire_due();
+    fired.iter().for_each(|(_, result)| assert_ok(result));
+    fired.len()
+}
+
+fn terminate(
+    harness: &Harness,
+    merchant: &Pubkey,
+    subscriber: &Pubkey,
+    plan: &Pubkey,
+) -> Instruction {
+    TerminateSubscription {
+        merchant: *merchant,
+        subscription: harness.subscription_keys(subscriber, plan).unwrap(),
+        reason: TerminationReason::Abuse,
+    }
+    .instruction()
+}
+
+#[test]
+#[ignore = "needs the built programs, see the readme"]
+fn merchant_terminates_an_active_subscription() {
+    let mut harness = Harness::new();
+
+    let merchant = harness.new_user();
+    let plan = harness.create_plan(&merchant, plan_args());
+
+    let subscriber = harness.new_user();
+    harness.set_token_balance(&subscriber.pubkey(), 3 * AMOUNT);
+    assert_ok(&harness.subscribe(&subscriber, &plan));
+    assert_eq!(fire_due(&mut harness), 1);
+
+    // only the plan's merchant can end it
+    let stranger = harness.new_user();
+    let instruction = terminate(&harness, &stranger.pubkey(), &subscriber.pubkey(), &plan);
+    assert!(harness.send(&[instruction], &[&stranger]).is_err());
+
+    let user_subscription = pda::user_subscription(&subscriber.pubkey(), &plan);
+    let refund = harness.lamports(&user_subscription);
+    let balance = harness.lamports(&subscriber.pubkey());
+
+    let instruction = terminate(&harness, &merchant.pubkey(), &subscriber.pubkey(), &plan);
+    assert_ok(&harness.send(&[instruction], &[&merchant]));
+
+    // the queued charge is gone and the rent with the crank reserve is back with the subscriber
+    assert!(harness.tasks().is_empty());
+    assert!(harness
+        .user_subscription(&subscriber.pubkey(), &plan)
+        .is_none());
+    assert_eq!(harness.lamports(&subscriber.pubkey()), balance + refund);
+    assert_eq!(
+        harness
+            .fetch::<SubscriptionPlan>(&plan)
+            .unwrap()
+            .subscriber_count,
+        0
+    );
+}
+
+#[test]
+#[ignore = "needs the built programs, see the readme"]
+fn anyone_closes_a_failed_subscription_once_it_can_no_longer_be_reactivated() {
+    let mut harness = Harness::new();
+
+    let merchant = harness.new_user();
+    let plan = harness.create_plan(&merchant, plan_args());
+
+    // one cycle only, the charge at the next due date fails
+    let subscriber = harness.new_user();
+    harness.set_token_balance(&subscriber.pubkey(), AMOUNT);
+    assert_ok(&harness.subscribe(&subscriber, &plan));
+    assert_eq!(fire_due(&mut harness), 1);
+
+    harness.warp_to(START_TS + INTERVAL);
+    assert_eq!(fire_due(&mut harness), 1);
+
+    let subscription = harness
+        .user_subscription(&subscriber.pubkey(), &plan)
+        .unwrap();
+    assert!(subscription.status == Status::Failed);
+    assert!(harness.tasks().is_empty());
+
+    let crank = harness.new_user();
+    let close = CloseFailedSubscription {
+        signer: crank.pubkey(),
+        subscription: harness
+            .subscription_keys(&subscriber.pubkey(), &plan)
+            .unwrap(),
+    }
+    .instruction();
+
+    // the subscriber can still reactivate during the cycle after the unpaid one
+    assert!(harness
+        .send(std::slice::from_ref(&close), &[&crank])
+        .is_err());
+
+    harness.warp_to(START_TS + 2 * INTERVAL);
+
+    let user_subscription = pda::user_subscription(&subscriber.pubkey(), &plan);
+    let refund = harness.lamports(&user_subscription);
+    let balance = harness.lamports(&subscriber.pubkey());
+
+    assert_ok(&harness.send(&[close], &[&crank]));
+
+    assert!(harness
+        .user_subscription(&subscriber.pubkey(), &plan)
+        .is_none());
+    assert_eq!(harness.lamports(&subscriber.pubkey()), balance + refund);
+}
+
+#[test]
+#[ignore = "needs the built programs, see the readme"]
+fn terminated_subscriber_is_refunded_up_to_the_charge() {
+    let mut harness = Harness::new();
+
+    let merchant = harness.new_user();
+    let plan = harness.create_plan(&merchant, plan_args());
+
+    let subscriber = harness.new_user();
+    let subscriber_ata = harness.set_token_balance(&subscriber.pubkey(), AMOUNT);
+    assert_ok(&harness.subscribe(&subscriber, &plan));
+    assert_eq!(fire_due(&mut harness), 1);
+
+    // the first charge of the plan writes the first receipt of the subscription
+    let user_subscription = pda::user_subscription(&subscriber.pubkey(), &plan);
+    let receipt = pda::charge_receipt(&user_subscription, 0);
+    let charged = harness.fetch::<ChargeReceipt>(&receipt).unwrap();
+    assert_eq!(charged.amount, AMOUNT);
+    assert_eq!(charged.subscriber, subscriber.pubkey());
+    assert_eq!(
+        harness
+            .user_subscription(&subscriber.pubkey(), &plan)
+            .unwrap()
+            .next_receipt,
+        1
+    );
+
+    let instruction = terminate(&harness, &merchant.pubkey(), &subscriber.pubkey(), &plan);
+    assert_ok(&harness.send(&[instruction], &[&merchant]));
+
+    let refund = |destination, amount| {
+        RefundCharge {
+            merchant: merchant.pubkey(),
+            subscription_plan: plan,
+            mint: harness.mint,
+            token_program: anchor_spl::token::ID,
+            charge_receipt: receipt,
+            destination,
+            amount,
+        }
+        .instruction()
+    };
+    let vault = pda::subscriber_vault(&subscriber.pubkey(), &harness.mint);
+    let first = refund(subscriber_ata, AMOUNT / 2);
+    let too_much = refund(vault, AMOUNT / 2 + 1);
+    let rest = refund(vault, AMOUNT / 2);
+
+    // the subscription is gone, the receipt is enough to refund in parts
+    assert_ok(&harness.send(&[first], &[&merchant]));
+    assert!(harness.send(&[too_much], &[&merchant]).is_err());
+    assert_ok(&harness.send(&[rest], &[&merchant]));
+
+    assert_eq!(harness.token_balance(&subscriber_ata), AMOUNT / 2);
+    assert_eq!(harness.token_balance(&vault), AMOUNT / 2);
+    assert_eq!(
+        harness.fetch::<ChargeReceipt>(&receipt).unwrap().refunded,
+        AMOUNT
+    );
+}
@@ -0,0 +1,67 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct Config {
+    pub rpc_url: String,
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_secs: u64,
+    // newest relayed transaction is kept here, so a restart does not skip events; without it the
+    // relay starts from the latest page of program history
+    pub cursor_file: Option<String>,
+    // deliveries that gave up are appended here as JSON lines instead of holding back the cursor
+    #[serde(default = "default_dead_letter_file")]
+    pub dead_letter_file: String,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub endpoints: Vec<Endpoint>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64, // doubled after every failed attempt
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay_ms: 500,
+        }
+    }
+}
+
+// a URL registered by a merchant for the events of some of its plans
+#[derive(Deserialize, Debug, Clone)]
+pub struct Endpoint {
+    pub url: String,
+    pub secret: String, // shared with the merchant, signs every delivery
+    pub plans: Vec<String>,
+}
+
+impl Endpoint {
+    pub fn wants(&self, plan: &str) -> bool {
+        self.plans.iter().any(|p| p == plan)
+    }
+}
+
+fn default_poll_interval() -> u64 {
+    5
+}
+
+fn default_dead_letter_file() -> String {
+    "webhooks.dead_letters.jsonl".to_string()
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let config = fs::read_to_string(path)
+            .with_context(|| format!("could not read config {}", path.display()))?;
+
+        toml::from_str(&config).context("invalid config")
+    }
+}
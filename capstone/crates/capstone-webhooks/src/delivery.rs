@@ -0,0 +1,184 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::Sha256;
+
+use crate::{
+    config::{Endpoint, RetryConfig},
+    events::LoggedEvent,
+    source::RecordedTransaction,
+};
+
+pub const SIGNATURE_HEADER: &str = "Capstone-Signature";
+pub const IDEMPOTENCY_HEADER: &str = "Idempotency-Key";
+
+// signs `{timestamp}.{body}` so a captured delivery cannot be replayed later with a new timestamp,
+// merchants recompute it with their secret and compare
+pub fn sign(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
+
+pub fn signature_header(secret: &str, timestamp: u64, body: &str) -> String {
+    format!("t={timestamp},v1={}", sign(secret, timestamp, body))
+}
+
+// sends one request, any error or non-2xx status counts as a failed attempt
+pub trait Transport {
+    fn post(&self, url: &str, headers: &[(&str, String)], body: &str) -> Result<()>;
+}
+
+pub struct HttpTransport {
+    agent: ureq::Agent,
+}
+
+impl HttpTransport {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+        }
+    }
+}
+
+impl Transport for HttpTransport {
+    fn post(&self, url: &str, headers: &[(&str, String)], body: &str) -> Result<()> {
+        let mut request = self.agent.post(url).set("Content-Type", "application/json");
+
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+
+        request.send_string(body)?;
+        Ok(())
+    }
+}
+
+// a delivery that gave up after `max_attempts`, kept for a manual replay so the relay can move on
+#[derive(Serialize, Debug)]
+pub struct DeadLetter {
+    pub url: String,
+    pub id: String, // idempotency key, a replay must send it again
+    pub payload: Value,
+    pub error: String,
+}
+
+// appends one JSON line per dead letter
+pub fn record_dead_letters(path: &Path, dead_letters: &[DeadLetter]) -> Result<()> {
+    if dead_letters.is_empty() {
+        return Ok(());
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("could not open dead letters {}", path.display()))?;
+
+    for dead_letter in dead_letters {
+        writeln!(file, "{}", serde_json::to_string(dead_letter)?)?;
+    }
+
+    Ok(())
+}
+
+pub struct Deliverer<T: Transport> {
+    transport: T,
+    retry: RetryConfig,
+}
+
+impl<T: Transport> Deliverer<T> {
+    pub fn new(transport: T, retry: RetryConfig) -> Self {
+        Self { transport, retry }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    // posts the event to the endpoint, retrying with exponential backoff; every attempt carries
+    // the same idempotency key
+    pub fn deliver(&self, endpoint: &Endpoint, event: &LoggedEvent, timestamp: u64) -> Result<()> {
+        let body = event.payload().to_string();
+        let headers = [
+            (IDEMPOTENCY_HEADER, event.idempotency_key()),
+            (
+                SIGNATURE_HEADER,
+                signature_header(&endpoint.secret, timestamp, &body),
+            ),
+        ];
+
+        let mut delay = Duration::from_millis(self.retry.base_delay_ms);
+        let mut attempt = 1;
+
+        loop {
+            match self.transport.post(&endpoint.url, &headers, &body) {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt >= self.retry.max_attempts => {
+                    return Err(anyhow!(
+                        "giving up on {} for {} after {attempt} attempts: {err}",
+                        event.idempotency_key(),
+                        endpoint.url
+                    ));
+                }
+                Err(_) => {
+                    thread::sleep(delay);
+                    delay *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    // delivers every event to the endpoints of its plan and returns the deliveries that gave up.
+    // They are not retried on the next poll, so an unreachable endpoint neither holds back the
+    // cursor nor makes the other endpoints see events twice
+    pub fn relay(
+        &self,
+        endpoints: &[Endpoint],
+        transactions: &[RecordedTransaction],
+    ) -> Vec<DeadLetter> {
+        let mut dead_letters = Vec::new();
+
+        for transaction in transactions {
+            for event in transaction.events() {
+                let plan = event.event.plan().to_string();
+                let timestamp = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default();
+
+                for endpoint in endpoints.iter().filter(|e| e.wants(&plan)) {
+                    match self.deliver(endpoint, &event, timestamp) {
+                        Ok(()) => {
+                            println!("delivered {} to {}", event.idempotency_key(), endpoint.url)
+                        }
+                        Err(err) => {
+                            eprintln!("{err:#}");
+
+                            dead_letters.push(DeadLetter {
+                                url: endpoint.url.clone(),
+                                id: event.idempotency_key(),
+                                payload: event.payload(),
+                                error: format!("{err:#}"),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        dead_letters
+    }
+}
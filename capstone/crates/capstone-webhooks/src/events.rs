@@ -0,0 +1,184 @@
+use anchor_lang::{prelude::*, Discriminator};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+
+// prefix of the log line `emit!` writes, followed by the base64 encoded event
+const PROGRAM_DATA: &str = "Program data: ";
+
+// decoded with the program's own types, so the relay cannot drift from what it emits. Only the
+// events merchants are notified about are forwarded
+pub use capstone::{
+    events::{
+        CancelSubscriptionEvent, ChargeEvent, SubscribeEvent, SubscriptionFailedEvent,
+        SubscriptionTerminatedEvent,
+    },
+    states::{PricingTier, Status, TerminationReason},
+};
+
+pub enum CapstoneEvent {
+    Subscribe(SubscribeEvent),
+    Charge(ChargeEvent),
+    CancelSubscription(CancelSubscriptionEvent),
+    SubscriptionFailed(SubscriptionFailedEvent),
+    SubscriptionTerminated(SubscriptionTerminatedEvent),
+}
+
+impl CapstoneEvent {
+    // decodes one emitted event, `None` for events the relay does not forward
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let (discriminator, mut body) = data.split_at_checked(8)?;
+
+        let event = match discriminator {
+            d if d == SubscribeEvent::DISCRIMINATOR => {
+                Self::Subscribe(SubscribeEvent::deserialize(&mut body).ok()?)
+            }
+            d if d == ChargeEvent::DISCRIMINATOR => {
+                Self::Charge(ChargeEvent::deserialize(&mut body).ok()?)
+            }
+            d if d == CancelSubscriptionEvent::DISCRIMINATOR => {
+                Self::CancelSubscription(CancelSubscriptionEvent::deserialize(&mut body).ok()?)
+            }
+            d if d == SubscriptionFailedEvent::DISCRIMINATOR => {
+                Self::SubscriptionFailed(SubscriptionFailedEvent::deserialize(&mut body).ok()?)
+            }
+            d if d == SubscriptionTerminatedEvent::DISCRIMINATOR => Self::SubscriptionTerminated(
+                SubscriptionTerminatedEvent::deserialize(&mut body).ok()?,
+            ),
+            _ => return None,
+        };
+
+        Some(event)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Subscribe(_) => "subscribe",
+            Self::Charge(_) => "charge",
+            Self::CancelSubscription(_) => "cancel_subscription",
+            Self::SubscriptionFailed(_) => "subscription_failed",
+            Self::SubscriptionTerminated(_) => "subscription_terminated",
+        }
+    }
+
+    // plan the event belongs to, webhooks are routed by it
+    pub fn plan(&self) -> Pubkey {
+        match self {
+            Self::Subscribe(e) => e.subscription,
+            Self::Charge(e) => e.subscription,
+            Self::CancelSubscription(e) => e.subscription,
+            Self::SubscriptionFailed(e) => e.subscription,
+            Self::SubscriptionTerminated(e) => e.subscription,
+        }
+    }
+
+    pub fn data(&self) -> Value {
+        match self {
+            Self::Subscribe(e) => json!({
+                "subscriber": e.subscriber.to_string(),
+                "plan": e.subscription.to_string(),
+                "status": format!("{:?}", e.status),
+            }),
+            // amounts are strings, u64 does not fit a javascript number
+            Self::Charge(e) => json!({
+                "subscriber": e.subscriber.to_string(),
+                "plan": e.subscription.to_string(),
+                "amount": e.amount.to_string(),
+                "fee": e.fee.to_string(),
+                "tier": format!("{:?}", e.tier),
+                "charge_seq": e.charge_seq.to_string(),
+                "usage_units": e.usage_units.to_string(),
+                // refunds are made against the receipt, null if nothing was transferred
+                "receipt": e.receipt.map(|receipt| receipt.to_string()),
+            }),
+            Self::CancelSubscription(e) => json!({
+                "subscriber": e.subscriber.to_string(),
+                "plan": e.subscription.to_string(),
+            }),
+            Self::SubscriptionFailed(e) => json!({
+                "subscriber": e.subscriber.to_string(),
+                "plan": e.subscription.to_string(),
+            }),
+            Self::SubscriptionTerminated(e) => json!({
+                "subscriber": e.subscriber.to_string(),
+                "plan": e.subscription.to_string(),
+                "terminated_by": e.terminated_by.to_string(),
+                "reason": format!("{:?}", e.reason),
+                "remaining_allowance": e.remaining_allowance.to_string(),
+            }),
+        }
+    }
+}
+
+// an event together with the transaction that emitted it
+pub struct LoggedEvent {
+    pub signature: String,
+    pub slot: u64,
+    pub index: usize, // position among the capstone events of the transaction
+    pub event: CapstoneEvent,
+}
+
+impl LoggedEvent {
+    // stable across retries and restarts, merchants use it to drop duplicate deliveries
+    pub fn idempotency_key(&self) -> String {
+        format!("{}:{}", self.signature, self.index)
+    }
+
+    pub fn payload(&self) -> Value {
+        json!({
+            "id": self.idempotency_key(),
+            "type": self.event.name(),
+            "signature": self.signature,
+            "slot": self.slot,
+            "data": self.event.data(),
+        })
+    }
+}
+
+// events emitted by the capstone program in a transaction's logs, data logged by other programs
+// (including programs capstone calls into) is ignored
+pub fn decode_logs(signature: &str, slot: u64, logs: &[String]) -> Vec<LoggedEvent> {
+    let program_id = capstone::ID.to_string();
+    let mut invocations: Vec<&str> = Vec::new();
+    let mut index = 0;
+    let mut events = Vec::new();
+
+    for line in logs {
+        if let Some(data) = line.strip_prefix(PROGRAM_DATA) {
+            if invocations.last() != Some(&program_id.as_str()) {
+                continue;
+            }
+
+            let Ok(data) = STANDARD.decode(data) else {
+                continue;
+            };
+
+            if let Some(event) = CapstoneEvent::decode(&data) {
+                events.push(LoggedEvent {
+                    signature: signature.to_string(),
+                    slot,
+                    index,
+                    event,
+                });
+            }
+
+            index += 1;
+            continue;
+        }
+
+        let mut words = line.split_whitespace();
+
+        if let (Some("Program"), Some(id), Some(status)) =
+            (words.next(), words.next(), words.next())
+        {
+            match status {
+                "invoke" => invocations.push(id),
+                "success" | "failed:" => {
+                    invocations.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    events
+}
@@ -0,0 +1,10 @@
+//! Off-chain relay that turns capstone program events into merchant webhooks.
+//!
+//! [`source`] reads program logs, either by polling an RPC endpoint or from a recorded fixture,
+//! [`events`] decodes the anchor events in them and [`delivery`] posts each event as a signed
+//! JSON body to the endpoints that registered its plan, see [`config`].
+
+pub mod config;
+pub mod delivery;
+pub mod events;
+pub mod source;
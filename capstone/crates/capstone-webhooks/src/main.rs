@@ -0,0 +1,69 @@
+//! Usage: `capstone-webhooks <config.toml> [--fixture <logs.json>]`
+//!
+//! Polls the RPC endpoint of the config for capstone transactions and delivers their events, with
+//! `--fixture` the recorded transactions are delivered once instead.
+
+use std::{env, fs, path::Path, thread, time::Duration};
+
+use anyhow::{bail, Result};
+use capstone_webhooks::{
+    config::Config,
+    delivery::{record_dead_letters, Deliverer, HttpTransport},
+    source::{read_fixture, RpcSource},
+};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (config_path, fixture) = match args.as_slice() {
+        [config] => (config, None),
+        [config, flag, fixture] if flag == "--fixture" => (config, Some(fixture)),
+        _ => bail!("usage: capstone-webhooks <config.toml> [--fixture <logs.json>]"),
+    };
+
+    let config = Config::load(Path::new(config_path))?;
+    let dead_letter_file = Path::new(&config.dead_letter_file);
+    let deliverer = Deliverer::new(
+        HttpTransport::new(Duration::from_secs(10)),
+        config.retry.clone(),
+    );
+
+    if let Some(fixture) = fixture {
+        let transactions = read_fixture(Path::new(fixture))?;
+        let dead_letters = deliverer.relay(&config.endpoints, &transactions);
+        record_dead_letters(dead_letter_file, &dead_letters)?;
+
+        if !dead_letters.is_empty() {
+            bail!(
+                "{} deliveries gave up, see {}",
+                dead_letters.len(),
+                dead_letter_file.display()
+            );
+        }
+        return Ok(());
+    }
+
+    let cursor = match &config.cursor_file {
+        Some(path) => fs::read_to_string(path)
+            .ok()
+            .map(|cursor| cursor.trim().to_string()),
+        None => None,
+    };
+    let mut source = RpcSource::new(&config.rpc_url, &capstone::ID.to_string(), cursor);
+
+    loop {
+        match source.poll() {
+            Ok(transactions) => {
+                let dead_letters = deliverer.relay(&config.endpoints, &transactions);
+                record_dead_letters(dead_letter_file, &dead_letters)?;
+
+                if let (Some(path), Some(cursor)) = (&config.cursor_file, &source.cursor) {
+                    fs::write(path, cursor)?;
+                }
+            }
+            Err(err) => eprintln!("poll failed: {err:#}"),
+        }
+
+        thread::sleep(Duration::from_secs(config.poll_interval_secs));
+    }
+}
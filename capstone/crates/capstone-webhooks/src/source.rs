@@ -0,0 +1,149 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::events::{decode_logs, LoggedEvent};
+
+// most signatures `getSignaturesForAddress` returns per call
+const PAGE_LIMIT: usize = 1000;
+
+// logs of one capstone transaction, also the format of recorded fixtures
+#[derive(Deserialize, Debug, Clone)]
+pub struct RecordedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub logs: Vec<String>,
+}
+
+impl RecordedTransaction {
+    pub fn events(&self) -> Vec<LoggedEvent> {
+        decode_logs(&self.signature, self.slot, &self.logs)
+    }
+}
+
+// a JSON array of `RecordedTransaction`, oldest first
+pub fn read_fixture(path: &Path) -> Result<Vec<RecordedTransaction>> {
+    let fixture = fs::read_to_string(path)
+        .with_context(|| format!("could not read fixture {}", path.display()))?;
+
+    serde_json::from_str(&fixture).context("invalid fixture")
+}
+
+// tails the capstone program by polling an RPC endpoint, only successful transactions are returned
+pub struct RpcSource {
+    url: String,
+    program_id: String,
+    // newest transaction already returned, the next poll starts after it
+    pub cursor: Option<String>,
+}
+
+impl RpcSource {
+    pub fn new(url: &str, program_id: &str, cursor: Option<String>) -> Self {
+        Self {
+            url: url.to_string(),
+            program_id: program_id.to_string(),
+            cursor,
+        }
+    }
+
+    // transactions since the cursor, oldest first. Without a cursor only the latest page is read,
+    // history older than `PAGE_LIMIT` transactions is never delivered then; to replay from a known
+    // point write its signature to the cursor file before the first start
+    pub fn poll(&mut self) -> Result<Vec<RecordedTransaction>> {
+        let mut signatures = Vec::new();
+        let mut before: Option<String> = None;
+        let mut newest: Option<String> = None;
+
+        // pages run newest to oldest, keep paging back until the cursor is reached
+        loop {
+            let mut options = json!({ "limit": PAGE_LIMIT, "commitment": "confirmed" });
+
+            if let Some(until) = &self.cursor {
+                options["until"] = json!(until);
+            }
+            if let Some(before) = &before {
+                options["before"] = json!(before);
+            }
+
+            let page = self.call("getSignaturesForAddress", json!([self.program_id, options]))?;
+            let page = page
+                .as_array()
+                .ok_or_else(|| anyhow!("unexpected getSignaturesForAddress response"))?;
+
+            for entry in page {
+                let signature = entry["signature"]
+                    .as_str()
+                    .ok_or_else(|| anyhow!("signature missing"))?;
+
+                if entry["err"].is_null() {
+                    signatures.push((signature.to_string(), entry["slot"].as_u64().unwrap_or(0)));
+                }
+                newest.get_or_insert_with(|| signature.to_string());
+                before = Some(signature.to_string());
+            }
+
+            if page.len() < PAGE_LIMIT || self.cursor.is_none() {
+                break;
+            }
+        }
+
+        let transactions = signatures
+            .into_iter()
+            .rev()
+            .map(|(signature, slot)| {
+                let logs = self.logs(&signature)?;
+
+                Ok(RecordedTransaction {
+                    signature,
+                    slot,
+                    logs,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // only moved once every log was fetched, failed transactions move it too
+        if newest.is_some() {
+            self.cursor = newest;
+        }
+
+        Ok(transactions)
+    }
+
+    fn logs(&self, signature: &str) -> Result<Vec<String>> {
+        let transaction = self.call(
+            "getTransaction",
+            json!([
+                signature,
+                { "commitment": "confirmed", "maxSupportedTransactionVersion": 0 }
+            ]),
+        )?;
+
+        let logs = transaction["meta"]["logMessages"]
+            .as_array()
+            .ok_or_else(|| anyhow!("no logs for {signature}"))?;
+
+        Ok(logs
+            .iter()
+            .filter_map(|line| line.as_str().map(str::to_string))
+            .collect())
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let response: Value = ureq::post(&self.url)
+            .send_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))?
+            .into_json()?;
+
+        if let Some(error) = response.get("error") {
+            return Err(anyhow!("{method} failed: {error}"));
+        }
+
+        Ok(response["result"].clone())
+    }
+}
@@ -0,0 +1,231 @@
+use std::{cell::RefCell, env, fs, path::Path, process};
+
+use anyhow::{anyhow, Result};
+use capstone_webhooks::{
+    config::{Endpoint, RetryConfig},
+    delivery::{
+        record_dead_letters, sign, Deliverer, Transport, IDEMPOTENCY_HEADER, SIGNATURE_HEADER,
+    },
+    events::{
+        CapstoneEvent, LoggedEvent, PricingTier, Status, SubscriptionTerminatedEvent,
+        TerminationReason,
+    },
+    source::{read_fixture, RecordedTransaction},
+};
+
+const SUBSCRIBER: &str = "37WyEGj3NhfMV7z888iQMrCDC6ekRZgjdLfx8BuX851a";
+const PLAN: &str = "7mRiVnAxAecvHHMZqsFzRU27Yw6TNUqvgKRUFEDsJyfa";
+const OTHER_PLAN: &str = "63JApLZERN9obYWtvwBu27Fa3PfhnoHdNExKTETdh41C";
+const RECEIPT: &str = "cGfHiC6Kgg3FpFZvgwGcswsCRtp4aBP2fzuXRQPizuN";
+
+fn fixture() -> Vec<RecordedTransaction> {
+    read_fixture(Path::new("tests/fixtures/logs.json")).unwrap()
+}
+
+fn fixture_events() -> Vec<LoggedEvent> {
+    fixture()
+        .iter()
+        .flat_map(|transaction| transaction.events())
+        .collect()
+}
+
+#[test]
+fn decodes_capstone_events_from_recorded_logs() {
+    let events = fixture_events();
+
+    let keys: Vec<String> = events.iter().map(|e| e.idempotency_key()).collect();
+    // data logged by tuktuk is skipped, the retry event is not forwarded but keeps its index
+    assert_eq!(
+        keys,
+        [
+            "sig-subscribe:0",
+            "sig-charge:0",
+            "sig-retry-then-fail:1",
+            "sig-cancel:0"
+        ]
+    );
+
+    let CapstoneEvent::Subscribe(subscribe) = &events[0].event else {
+        panic!("expected a subscribe event");
+    };
+    assert_eq!(subscribe.subscriber.to_string(), SUBSCRIBER);
+    assert_eq!(subscribe.status, Status::Active);
+
+    let CapstoneEvent::Charge(charge) = &events[1].event else {
+        panic!("expected a charge event");
+    };
+    assert_eq!(charge.amount, 1_000_000);
+    assert_eq!(charge.fee, 10_000);
+    assert_eq!(charge.tier, PricingTier::Standard);
+    assert_eq!(charge.charge_seq, 7);
+    assert_eq!(charge.receipt, Some(RECEIPT.parse().unwrap()));
+
+    assert!(matches!(
+        events[2].event,
+        CapstoneEvent::SubscriptionFailed(_)
+    ));
+    assert_eq!(events[2].event.plan().to_string(), OTHER_PLAN);
+    assert!(matches!(
+        events[3].event,
+        CapstoneEvent::CancelSubscription(_)
+    ));
+}
+
+#[test]
+fn payload_carries_the_idempotency_key_and_string_amounts() {
+    let payload = fixture_events()[1].payload();
+
+    assert_eq!(payload["id"], "sig-charge:0");
+    assert_eq!(payload["type"], "charge");
+    assert_eq!(payload["slot"], 200);
+    assert_eq!(payload["data"]["plan"], PLAN);
+    assert_eq!(payload["data"]["amount"], "1000000");
+    assert_eq!(payload["data"]["receipt"], RECEIPT);
+}
+
+#[test]
+fn forwards_terminations_with_their_reason() {
+    let terminated = SubscriptionTerminatedEvent {
+        subscriber: SUBSCRIBER.parse().unwrap(),
+        subscription: PLAN.parse().unwrap(),
+        terminated_by: OTHER_PLAN.parse().unwrap(),
+        reason: TerminationReason::PaymentFailed,
+        remaining_allowance: 2_000_000,
+    };
+
+    let event = CapstoneEvent::decode(&anchor_lang::Event::data(&terminated)).unwrap();
+    let CapstoneEvent::SubscriptionTerminated(decoded) = &event else {
+        panic!("expected a termination");
+    };
+    assert_eq!(decoded.terminated_by, terminated.terminated_by);
+    assert_eq!(decoded.reason, terminated.reason);
+    assert_eq!(event.name(), "subscription_terminated");
+    assert_eq!(event.plan().to_string(), PLAN);
+    assert_eq!(event.data()["reason"], "PaymentFailed");
+    assert_eq!(event.data()["remaining_allowance"], "2000000");
+}
+
+#[test]
+fn signs_timestamp_and_body() {
+    assert_eq!(
+        sign("whsec", 1_700_000_000, r#"{"id":"sig:0"}"#),
+        "3941d695031007cbd1ed71fa2ca7184d493bb33d4908de313f245e4036fa4805"
+    );
+}
+
+// fails the first `failures` requests and records every attempt
+struct FlakyTransport {
+    failures: usize,
+    attempts: RefCell<Vec<Vec<(String, String)>>>,
+}
+
+impl Transport for FlakyTransport {
+    fn post(&self, _url: &str, headers: &[(&str, String)], _body: &str) -> Result<()> {
+        let mut attempts = self.attempts.borrow_mut();
+        attempts.push(
+            headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+        );
+
+        if attempts.len() <= self.failures {
+            return Err(anyhow!("503"));
+        }
+        Ok(())
+    }
+}
+
+fn endpoint() -> Endpoint {
+    Endpoint {
+        url: "http://merchant.test/webhook".to_string(),
+        secret: "whsec".to_string(),
+        plans: vec![PLAN.to_string()],
+    }
+}
+
+fn deliverer(failures: usize, max_attempts: u32) -> Deliverer<FlakyTransport> {
+    Deliverer::new(
+        FlakyTransport {
+            failures,
+            attempts: RefCell::new(Vec::new()),
+        },
+        RetryConfig {
+            max_attempts,
+            base_delay_ms: 0,
+        },
+    )
+}
+
+#[test]
+fn retries_with_the_same_idempotency_key() {
+    let event = &fixture_events()[1];
+    let deliverer = deliverer(2, 3);
+
+    deliverer
+        .deliver(&endpoint(), event, 1_700_000_000)
+        .unwrap();
+
+    let attempts = deliverer.transport().attempts.borrow();
+    assert_eq!(attempts.len(), 3);
+
+    for headers in attempts.iter() {
+        assert!(headers.contains(&(IDEMPOTENCY_HEADER.to_string(), "sig-charge:0".to_string())));
+
+        let signature = &headers
+            .iter()
+            .find(|(name, _)| name == SIGNATURE_HEADER)
+            .unwrap()
+            .1;
+        let body = event.payload().to_string();
+        assert_eq!(
+            signature,
+            &format!("t=1700000000,v1={}", sign("whsec", 1_700_000_000, &body))
+        );
+    }
+}
+
+#[test]
+fn gives_up_after_max_attempts() {
+    let deliverer = deliverer(5, 2);
+
+    assert!(deliverer
+        .deliver(&endpoint(), &fixture_events()[1], 1_700_000_000)
+        .is_err());
+    assert_eq!(deliverer.transport().attempts.borrow().len(), 2);
+}
+
+#[test]
+fn routes_events_by_plan() {
+    let events = fixture_events();
+
+    assert!(endpoint().wants(&events[0].event.plan().to_string()));
+    assert!(!endpoint().wants(&events[2].event.plan().to_string()));
+}
+
+#[test]
+fn relay_dead_letters_deliveries_that_gave_up() {
+    let transactions = fixture();
+
+    assert!(deliverer(1, 2)
+        .relay(&[endpoint()], &transactions)
+        .is_empty());
+
+    // delivery of the first event gives up, the later events of the plan still go out once
+    let deliverer = deliverer(1, 1);
+    let dead_letters = deliverer.relay(&[endpoint()], &transactions);
+    assert_eq!(deliverer.transport().attempts.borrow().len(), 3);
+
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0].url, endpoint().url);
+    assert_eq!(dead_letters[0].id, "sig-subscribe:0");
+    assert_eq!(dead_letters[0].payload["type"], "subscribe");
+
+    let path = env::temp_dir().join(format!("dead_letters_{}.jsonl", process::id()));
+    record_dead_letters(&path, &dead_letters).unwrap();
+    record_dead_letters(&path, &dead_letters).unwrap();
+
+    let lines = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(lines.lines().count(), 2);
+}
@@ -0,0 +1,303 @@
+//! Local stand-in for the tuktuk program, deployed at tuktuk's address by `capstone-harness`.
+//!
+//! It keeps the instruction names and account order of the CPIs capstone makes, so the real
+//! `tuktuk-program` client talks to it unchanged, but skips everything capstone does not rely on:
+//! there is no task bitmap, no crank reward payout and no remote transaction source. Queued tasks
+//! are stored as [`QueuedTask`] at tuktuk's task PDA and `run_task_v0` fires one once its trigger
+//! has passed, queueing whatever tasks the fired instructions return.
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::Instruction,
+        program::{get_return_data, invoke},
+    },
+    system_program::{create_account, CreateAccount},
+};
+use tuktuk_program::types::{
+    CompiledInstructionV0, CompiledTransactionV0, QueueTaskArgsV0, RunTaskArgsV0, RunTaskReturnV0,
+    TaskReturnV0, TransactionSourceV0, TriggerV0,
+};
+
+declare_id!("tuktukUrfhXT6ZT77QTU8RQtvgL967uRuVagWF57zVA");
+
+pub const TASK_SEED: &[u8] = b"task";
+
+#[program]
+pub mod tuktuk_stub {
+    use super::*;
+
+    pub fn queue_task_v0(ctx: Context<QueueTaskV0>, args: QueueTaskArgsV0) -> Result<()> {
+        ctx.accounts.task.set_inner(QueuedTask {
+            task_queue: ctx.accounts.task_queue.key(),
+            queued_at: Clock::get()?.unix_timestamp,
+            args,
+        });
+
+        Ok(())
+    }
+
+    // the task account is closed by its constraint
+    pub fn dequeue_task_v0(_ctx: Context<DequeueTaskV0>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn run_task_v0<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RunTaskV0<'info>>,
+        args: RunTaskArgsV0,
+    ) -> Result<()> {
+        ctx.accounts.run_task_v0(args, ctx.remaining_accounts)
+    }
+}
+
+#[account]
+pub struct QueuedTask {
+    pub task_queue: Pubkey,
+    pub queued_at: i64,
+    pub args: QueueTaskArgsV0,
+}
+
+impl QueuedTask {
+    pub fn space(args: &QueueTaskArgsV0) -> usize {
+        let args_len = borsh::to_vec(args).map_or(0, |data| data.len());
+
+        Self::DISCRIMINATOR.len() + 32 + 8 + args_len
+    }
+
+    // `None` for tasks that run right away
+    pub fn trigger_ts(&self) -> Option<i64> {
+        match self.args.trigger {
+            TriggerV0::Now => None,
+            TriggerV0::Timestamp(ts) => Some(ts),
+        }
+    }
+
+    pub fn is_due(&self, now: i64) -> bool {
+        self.trigger_ts().is_none_or(|ts| now >= ts)
+    }
+
+    pub fn transaction(&self) -> Option<&CompiledTransactionV0> {
+        match &self.args.transaction {
+            TransactionSourceV0::CompiledV0(transaction) => Some(transaction),
+            _ => None,
+        }
+    }
+}
+
+// accounts are ordered writable signers, readonly signers, writable, readonly
+pub fn is_writable(transaction: &CompiledTransactionV0, index: usize) -> bool {
+    let signers = (transaction.num_rw_signers + transaction.num_ro_signers) as usize;
+
+    index < transaction.num_rw_signers as usize
+        || (index >= signers && index < signers + transaction.num_rw as usize)
+}
+
+pub fn decompile_instruction(
+    transaction: &CompiledTransactionV0,
+    instruction: &CompiledInstructionV0,
+) -> Instruction {
+    Instruction {
+        program_id: transaction.accounts[instruction.program_id_index as usize],
+        accounts: instruction
+            .accounts
+            .iter()
+            .map(|&index| AccountMeta {
+                pubkey: transaction.accounts[index as usize],
+                is_signer: false,
+                is_writable: is_writable(transaction, index as usize),
+            })
+            .collect(),
+        data: instruction.data.clone(),
+    }
+}
+
+#[derive(Accounts)]
+#[instruction(args: QueueTaskArgsV0)]
+pub struct QueueTaskV0<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub queue_authority: Signer<'info>,
+    /// CHECK: tuktuk checks the authority is registered on the queue, the stub trusts the signer
+    pub task_queue_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: only used as seed of the task accounts
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = QueuedTask::space(&args),
+        seeds = [TASK_SEED, task_queue.key().as_ref(), args.id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub task: Account<'info, QueuedTask>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DequeueTaskV0<'info> {
+    pub queue_authority: Signer<'info>,
+    #[account(mut)]
+    /// CHECK: receives the rent of the task
+    pub rent_refund: UncheckedAccount<'info>,
+    /// CHECK: see `QueueTaskV0`
+    pub task_queue_authority: UncheckedAccount<'info>,
+    #[account(mut)]
+    /// CHECK: via the task
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = rent_refund,
+        has_one = task_queue @ StubError::InvalidTaskQueue
+    )]
+    pub task: Account<'info, QueuedTask>,
+}
+
+// remaining accounts: one task PDA per free task id, then the accounts of the compiled transaction
+#[derive(Accounts)]
+pub struct RunTaskV0<'info> {
+    #[account(mut)]
+    pub crank_turner: Signer<'info>, // pays the rent of returned tasks
+    #[account(mut)]
+    /// CHECK: via the task
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        close = crank_turner,
+        has_one = task_queue @ StubError::InvalidTaskQueue
+    )]
+    pub task: Account<'info, QueuedTask>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RunTaskV0<'info> {
+    pub fn run_task_v0(
+        &mut self,
+        args: RunTaskArgsV0,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(self.task.is_due(now), StubError::TaskNotTriggered);
+
+        let transaction = self
+            .task
+            .transaction()
+            .ok_or(StubError::UnsupportedTransaction)?;
+
+        // signer seeds of tuktuk's custom signers are not supported
+        require!(
+            transaction.num_rw_signers == 0 && transaction.num_ro_signers == 0,
+            StubError::UnsupportedTransaction
+        );
+        require!(
+            remaining_accounts.len() == args.free_task_ids.len() + transaction.accounts.len(),
+            StubError::InvalidAccounts
+        );
+
+        let (free_tasks, accounts) = remaining_accounts.split_at(args.free_task_ids.len());
+
+        require!(
+            accounts
+                .iter()
+                .zip(&transaction.accounts)
+                .all(|(info, key)| info.key == key),
+            StubError::InvalidAccounts
+        );
+
+        let mut returned = Vec::new();
+
+        for compiled in &transaction.instructions {
+            let instruction = decompile_instruction(transaction, compiled);
+
+            invoke(&instruction, accounts)?;
+
+            if let Some((program_id, data)) = get_return_data() {
+                if program_id == instruction.program_id {
+                    let run_task_return = RunTaskReturnV0::try_from_slice(&data)
+                        .map_err(|_| StubError::InvalidReturnData)?;
+
+                    returned.extend(run_task_return.tasks);
+                }
+            }
+        }
+
+        require!(
+            returned.len() <= free_tasks.len(),
+            StubError::NotEnoughFreeTasks
+        );
+
+        for ((task, id), info) in returned
+            .into_iter()
+            .zip(&args.free_task_ids)
+            .zip(free_tasks)
+        {
+            self.queue_returned_task(task, *id, info, now)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn queue_returned_task(
+        &self,
+        task: TaskReturnV0,
+        id: u16,
+        info: &AccountInfo<'info>,
+        now: i64,
+    ) -> Result<()> {
+        let task_queue = self.task_queue.key();
+        let id_bytes = id.to_le_bytes();
+        let (address, bump) =
+            Pubkey::find_program_address(&[TASK_SEED, task_queue.as_ref(), &id_bytes], &crate::ID);
+
+        require_keys_eq!(info.key(), address, StubError::InvalidAccounts);
+
+        let queued = QueuedTask {
+            task_queue,
+            queued_at: now,
+            args: QueueTaskArgsV0 {
+                id,
+                trigger: task.trigger,
+                transaction: task.transaction,
+                crank_reward: task.crank_reward,
+                free_tasks: task.free_tasks,
+                description: task.description,
+            },
+        };
+        let space = QueuedTask::space(&queued.args);
+
+        let signer_seeds: &[&[&[u8]]] = &[&[TASK_SEED, task_queue.as_ref(), &id_bytes, &[bump]]];
+
+        create_account(
+            CpiContext::new_with_signer(
+                self.system_program.to_account_info(),
+                CreateAccount {
+                    from: self.crank_turner.to_account_info(),
+                    to: info.clone(),
+                },
+                signer_seeds,
+            ),
+            Rent::get()?.minimum_balance(space),
+            space as u64,
+            &crate::ID,
+        )?;
+
+        let mut data = info.try_borrow_mut_data()?;
+        queued.try_serialize(&mut &mut data[..])
+    }
+}
+
+#[error_code]
+pub enum StubError {
+    #[msg("Task belongs to another task queue")]
+    InvalidTaskQueue,
+    #[msg("Task trigger has not passed yet")]
+    TaskNotTriggered,
+    #[msg("Only compiled transactions without signers are supported")]
+    UnsupportedTransaction,
+    #[msg("Remaining accounts do not match the free tasks and the transaction")]
+    InvalidAccounts,
+    #[msg("Returned data is not a RunTaskReturnV0")]
+    InvalidReturnData,
+    #[msg("More tasks returned than free task ids passed")]
+    NotEnoughFreeTasks,
+}
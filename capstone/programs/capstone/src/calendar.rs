@@ -0,0 +1,132 @@
+// pure UTC date math behind calendar billing intervals, no clock or account access so it can be
+// tested off-chain. Dates are proleptic gregorian, days count from 1970-01-01
+
+pub const SECONDS_PER_DAY: i64 = 86_400;
+
+pub fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+pub fn days_in_month(year: i64, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// days since 1970-01-01 of a date, negative before it
+pub fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    // years start in march so the leap day is the last day of the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * ((month as i64 + 9) % 12) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+// (year, month, day) of a day count from `days_from_civil`
+pub fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + (month <= 2) as i64;
+
+    (year, month as u8, day as u8)
+}
+
+// (year, month, day) of a timestamp
+pub fn date_of(ts: i64) -> (i64, u8, u8) {
+    civil_from_days(ts.div_euclid(SECONDS_PER_DAY))
+}
+
+// 00:00 UTC of `anchor_day` in a month, clamped to its last day
+pub fn anchor_in_month(year: i64, month: u8, anchor_day: u8) -> Option<i64> {
+    let day = anchor_day.clamp(1, days_in_month(year, month));
+
+    days_from_civil(year, month, day).checked_mul(SECONDS_PER_DAY)
+}
+
+// month `months` after (or before) `year`/`month`
+fn shift_month(year: i64, month: u8, months: i64) -> Option<(i64, u8)> {
+    let index = year
+        .checked_mul(12)?
+        .checked_add(month as i64 - 1)?
+        .checked_add(months)?;
+
+    Some((index.div_euclid(12), index.rem_euclid(12) as u8 + 1))
+}
+
+// first monthly anchor strictly after `ts`
+pub fn next_monthly(ts: i64, anchor_day: u8) -> Option<i64> {
+    let (year, month, _) = date_of(ts);
+    let anchor = anchor_in_month(year, month, anchor_day)?;
+
+    if anchor > ts {
+        return Some(anchor);
+    }
+
+    let (year, month) = shift_month(year, month, 1)?;
+    anchor_in_month(year, month, anchor_day)
+}
+
+// last monthly anchor strictly before `ts`
+pub fn previous_monthly(ts: i64, anchor_day: u8) -> Option<i64> {
+    let (year, month, _) = date_of(ts);
+    let anchor = anchor_in_month(year, month, anchor_day)?;
+
+    if anchor < ts {
+        return Some(anchor);
+    }
+
+    let (year, month) = shift_month(year, month, -1)?;
+    anchor_in_month(year, month, anchor_day)
+}
+
+// first yearly anchor strictly after `ts`, a 29 february anchor falls on the 28th in common years
+pub fn next_yearly(ts: i64, anchor_month: u8, anchor_day: u8) -> Option<i64> {
+    let (year, _, _) = date_of(ts);
+    let anchor = anchor_in_month(year, anchor_month, anchor_day)?;
+
+    if anchor > ts {
+        return Some(anchor);
+    }
+
+    anchor_in_month(year.checked_add(1)?, anchor_month, anchor_day)
+}
+
+// last yearly anchor strictly before `ts`
+pub fn previous_yearly(ts: i64, anchor_month: u8, anchor_day: u8) -> Option<i64> {
+    let (year, _, _) = date_of(ts);
+    let anchor = anchor_in_month(year, anchor_month, anchor_day)?;
+
+    if anchor < ts {
+        return Some(anchor);
+    }
+
+    anchor_in_month(year.checked_sub(1)?, anchor_month, anchor_day)
+}
+
+// same day and time `months` calendar months later, clamped to the last day of shorter months
+pub fn add_months(ts: i64, months: u32) -> Option<i64> {
+    let (year, month, day) = date_of(ts);
+    let (year, month) = shift_month(year, month, months as i64)?;
+    let day = day.min(days_in_month(year, month));
+
+    days_from_civil(year, month, day)
+        .checked_mul(SECONDS_PER_DAY)?
+        .checked_add(ts.rem_euclid(SECONDS_PER_DAY))
+}
@@ -19,4 +19,66 @@ pub enum SubscriptionError {
     ArithmeticError,
     #[msg("invalid signer")]
     InvalidSigner,
+    #[msg("mint is not accepted")]
+    MintNotAccepted,
+    #[msg("mint is already accepted")]
+    MintAlreadyAccepted,
+    #[msg("accepted mints list is full")]
+    AcceptedMintsFull,
+    #[msg("plan is already active")]
+    PlanAlreadyActive,
+    #[msg("plan still has subscribers")]
+    PlanHasSubscribers,
+    #[msg("subscription does not belong to this plan")]
+    InvalidSubscription,
+    #[msg("invalid retry policy")]
+    InvalidRetryPolicy,
+    #[msg("subscription has not failed")]
+    SubscriptionNotFailed,
+    #[msg("subscription is not active")]
+    SubscriptionNotActive,
+    #[msg("subscription is not paused")]
+    SubscriptionNotPaused,
+    #[msg("plans must share merchant and mint")]
+    PlanMismatch,
+    #[msg("fee must not exceed 10000 bps")]
+    InvalidFee,
+    #[msg("protocol is paused")]
+    ProtocolPaused,
+    #[msg("task queue does not match")]
+    InvalidTaskQueue,
+    #[msg("crank reserve is too low")]
+    InsufficientCrankReserve,
+    #[msg("next task id could not be read")]
+    InvalidTaskId,
+    #[msg("charge receipt does not match")]
+    InvalidReceipt,
+    #[msg("plan is not metered")]
+    NotMeteredPlan,
+    #[msg("spending cap must cover the base fee and reported usage")]
+    InvalidSpendingCap,
+    #[msg("usage would exceed the spending cap")]
+    SpendingCapExceeded,
+    #[msg("subscription has unbilled usage")]
+    UnbilledUsage,
+    #[msg("invalid coupon")]
+    InvalidCoupon,
+    #[msg("coupon is expired or fully redeemed")]
+    CouponNotRedeemable,
+    #[msg("invalid payout split")]
+    InvalidPayoutSplit,
+    #[msg("billing ledger of the subscription is missing")]
+    MissingBillingLedger,
+    #[msg("failed subscription can still be reactivated")]
+    ReactivationWindowOpen,
+    #[msg("refund exceeds what is left of the charge")]
+    RefundExceedsCharge,
+    #[msg("refunds go to the subscriber vault or the charged token account")]
+    InvalidRefundDestination,
+    #[msg("subscriber token account is already delegated to someone else")]
+    AtaAlreadyDelegated,
+    #[msg("only batch billed plans are charged with charge_batch")]
+    NotBatchBilled,
+    #[msg("crank reward is below the minimum")]
+    CrankRewardTooLow,
 }
@@ -1,6 +1,8 @@
 use anchor_lang::prelude::*;
 
-use crate::states::Status;
+use crate::states::{
+    BatchChargeOutcome, Discount, PayoutShare, PricingTier, Status, TerminationReason,
+};
 
 #[event]
 pub struct SubscribeEvent {
@@ -14,6 +16,20 @@ pub struct ChargeEvent {
     pub subscriber: Pubkey,
     pub subscription: Pubkey,
     pub amount: u64,
+    pub fee: u64,
+    pub tier: PricingTier,
+    pub charge_seq: u64,
+    pub usage_units: u64,        // metered units billed with this charge
+    pub receipt: Option<Pubkey>, // refunds are made against it, none if nothing was transferred
+}
+
+#[event]
+pub struct BatchChargeEvent {
+    pub subscriber: Pubkey, // default if the subscription account could not be read
+    pub subscription: Pubkey,
+    pub user_subscription: Pubkey,
+    pub outcome: BatchChargeOutcome,
+    pub amount: u64, // charged, or due if the funds were insufficient
 }
 
 #[event]
@@ -22,8 +38,196 @@ pub struct CancelSubscriptionEvent {
     pub subscription: Pubkey,
 }
 
+#[event]
+pub struct SubscriptionTerminatedEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub terminated_by: Pubkey, // the merchant, or whoever cranked `close_failed_subscription`
+    pub reason: TerminationReason,
+    // allowance the plan still holds over the subscriber ata, only the subscriber can revoke it
+    pub remaining_allowance: u64,
+}
+
 #[event]
 pub struct SubscriptionFailedEvent {
     pub subscriber: Pubkey,
     pub subscription: Pubkey,
 }
+
+#[event]
+pub struct SubscriptionRetryScheduledEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub failure_count: u8,
+    pub next_attempt_ts: i64,
+}
+
+#[event]
+pub struct PlanPausedEvent {
+    pub merchant: Pubkey,
+    pub subscription: Pubkey,
+}
+
+#[event]
+pub struct PlanResumedEvent {
+    pub merchant: Pubkey,
+    pub subscription: Pubkey,
+}
+
+#[event]
+pub struct PlanRetiredEvent {
+    pub merchant: Pubkey,
+    pub subscription: Pubkey,
+}
+
+#[event]
+pub struct PriceChangeScheduledEvent {
+    pub merchant: Pubkey,
+    pub subscription: Pubkey,
+    pub old_amount: u64,
+    pub new_amount: u64,
+    pub effective_ts: i64,
+}
+
+#[event]
+pub struct SubscriptionReactivatedEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub amount: u64,
+    pub next_exec_ts: i64,
+}
+
+#[event]
+pub struct SubscriptionPausedEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub resume_at: Option<i64>,
+}
+
+#[event]
+pub struct SubscriptionResumedEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub next_exec_ts: i64,
+}
+
+#[event]
+pub struct PlanChangedEvent {
+    pub subscriber: Pubkey,
+    pub old_subscription: Pubkey,
+    pub new_subscription: Pubkey,
+    pub credit: u64,
+    pub charged: u64,
+    pub next_exec_ts: i64,
+}
+
+#[event]
+pub struct ConfigUpdatedEvent {
+    pub fees: u64,
+    pub protocol_fee_bps: u16,
+    pub crank_reward: u64,
+    pub paused: bool,
+}
+
+#[event]
+pub struct AdminProposedEvent {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct AdminChangedEvent {
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct TaskQueueUpdatedEvent {
+    pub old_task_queue: Pubkey,
+    pub new_task_queue: Pubkey,
+}
+
+#[event]
+pub struct FeesWithdrawnEvent {
+    pub admin: Pubkey,
+    pub mint: Pubkey,
+    pub lamports: u64,
+    pub amount: u64,
+}
+
+#[event]
+pub struct CrankReserveFundedEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub amount: u64,
+    pub crank_reserve: u64,
+}
+
+#[event]
+pub struct CrankReserveDepletedEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+}
+
+#[event]
+pub struct SubscriptionCancelScheduledEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub access_until: i64,
+}
+
+#[event]
+pub struct UsageReportedEvent {
+    pub oracle: Pubkey,
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub cycle: u32,
+    pub units: u64,
+    pub usage_units: u64, // total reported since the last charge
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SpendingCapUpdatedEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub spending_cap: Option<u64>,
+}
+
+#[event]
+pub struct PayoutSplitUpdatedEvent {
+    pub merchant: Pubkey,
+    pub subscription: Pubkey,
+    pub payout_split: Vec<PayoutShare>,
+}
+
+#[event]
+pub struct CouponCreatedEvent {
+    pub merchant: Pubkey,
+    pub subscription: Pubkey,
+    pub coupon: Pubkey,
+    pub discount: Discount,
+    pub duration_cycles: u16,
+    pub max_redemptions: u32,
+    pub expires_at: i64,
+}
+
+#[event]
+pub struct CouponRedeemedEvent {
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub coupon: Pubkey,
+    pub redemptions: u32,
+}
+
+#[event]
+pub struct RefundEvent {
+    pub merchant: Pubkey,
+    pub subscriber: Pubkey,
+    pub subscription: Pubkey,
+    pub receipt: Pubkey,
+    pub charge_seq: u64,
+    pub amount: u64,
+    pub refunded: u64, // of the charge so far, including this refund
+    pub to_vault: bool,
+}
@@ -0,0 +1,67 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{TokenAccount, TokenInterface};
+
+use crate::{
+    error::SubscriptionError,
+    events::SubscriptionCancelScheduledEvent,
+    states::{BillingMode, Status, SubscriptionPlan, UserSubscription, SUBSCRIPTION_SEED},
+    utils::revoke_plan,
+};
+
+#[derive(Accounts)]
+pub struct CancelAtPeriodEnd<'info> {
+    pub subscriber: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), user_subscription.subscription.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = matches!(user_subscription.status, Status::Active | Status::Paused) @ SubscriptionError::SubscriptionNotActive
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    #[account(
+        address = user_subscription.subscription @ SubscriptionError::InvalidSubscription
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    #[account(
+        mut,
+        address = user_subscription.subscriber_ata
+    )]
+    pub subscriber_ata: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> CancelAtPeriodEnd<'info> {
+    // the queued task stays, when it fires it closes the subscription instead of charging, or is
+    // queued again for `access_until` if it fires earlier (the resume check of a pause). Batch
+    // billed plans have no task, `charge_batch` skips the subscription until the subscriber closes it
+    pub fn cancel_at_period_end(&mut self) -> Result<()> {
+        // `last_exec_ts` is the due date of the next cycle, everything before it is paid for
+        let access_until = self.user_subscription.last_exec_ts;
+
+        self.user_subscription.status = Status::Canceled;
+        self.user_subscription.access_until = Some(access_until);
+        self.user_subscription.resume_at = None;
+
+        // nothing more will be pulled, the plan no longer needs the allowance, except for metered
+        // plans where the last task still bills the usage of the paid period
+        if self.user_subscription.billing_mode != BillingMode::Vault
+            && (self.subscription_plan.metering.is_none() || self.subscription_plan.batch_billing)
+        {
+            revoke_plan(
+                self.token_program.to_account_info(),
+                &self.subscriber_ata,
+                self.subscriber.to_account_info(),
+                &self.user_subscription.subscription,
+            )?;
+        }
+
+        emit!(SubscriptionCancelScheduledEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.user_subscription.subscription.key(),
+            access_until
+        });
+
+        Ok(())
+    }
+}
@@ -1,19 +1,21 @@
 use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token_interface::TokenInterface};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{TokenAccount, TokenInterface},
+};
 use tuktuk_program::{
-    tuktuk::{
-        cpi::{accounts::DequeueTaskV0, dequeue_task_v0},
-        program::Tuktuk,
-    },
+    tuktuk::{cpi::accounts::DequeueTaskV0, program::Tuktuk},
     TaskQueueAuthorityV0,
 };
 
 use crate::{
+    error::SubscriptionError,
     events::CancelSubscriptionEvent,
     states::{
-        GlobalState, Status, UserSubscription, GLOBAL_STATE_SEED, QUEUE_AUTHORITY_SEED,
-        SUBSCRIPTION_SEED,
+        BillingLedger, BillingMode, GlobalState, Status, SubscriptionPlan, UserSubscription,
+        GLOBAL_STATE_SEED, LEDGER_SEED, QUEUE_AUTHORITY_SEED, SUBSCRIPTION_SEED,
     },
+    utils::{dequeue_charge_task, revoke_plan},
 };
 
 #[derive(Accounts)]
@@ -28,6 +30,24 @@ pub struct CancelSubscription<'info> {
         bump = user_subscription.bump
     )]
     pub user_subscription: Account<'info, UserSubscription>,
+    // history of the closed subscription goes with it
+    #[account(
+        mut,
+        close = subscriber,
+        seeds = [LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = billing_ledger.bump
+    )]
+    pub billing_ledger: Option<Account<'info, BillingLedger>>,
+    #[account(
+        mut,
+        address = user_subscription.subscription @ SubscriptionError::InvalidSubscription
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    #[account(
+        mut,
+        address = user_subscription.subscriber_ata
+    )]
+    pub subscriber_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         seeds = [GLOBAL_STATE_SEED],
@@ -36,8 +56,11 @@ pub struct CancelSubscription<'info> {
     pub global_state: Account<'info, GlobalState>,
 
     // TUKTUK
-    #[account(mut)]
-    /// CHECK: via signer, only can call this instruction
+    #[account(
+        mut,
+        address = user_subscription.task_queue @ SubscriptionError::InvalidTaskQueue
+    )]
+    /// CHECK: via address
     pub task_queue: UncheckedAccount<'info>,
     #[account(
         mut,
@@ -54,12 +77,11 @@ pub struct CancelSubscription<'info> {
     pub task_queue_authority: Account<'info, TaskQueueAuthorityV0>,
     #[account(
         mut,
-        // seeds = [b"task".as_ref(), task_queue.key().as_ref(), user_subscription.next_task_id.to_le_bytes().as_ref()],
-        // seeds::program = tuktuk_program::tuktuk::ID,
-        // constraint = !task.data_is_empty(),
-        // bump, // ??
+        seeds = [b"task", task_queue.key().as_ref(), user_subscription.next_task_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = tuktuk_program::tuktuk::ID,
     )]
-    /// CHECK: via seeds
+    /// CHECK: via seeds, queued task of the subscription
     pub task: AccountInfo<'info>,
 
     // PROGRAMS
@@ -71,12 +93,37 @@ pub struct CancelSubscription<'info> {
 
 impl<'info> CancelSubscription<'info> {
     pub fn cancel_subscription(&mut self) -> Result<()> {
-        if self.user_subscription.status == Status::Active {
+        // an existing ledger has to be passed, it would be left behind as an orphan otherwise
+        require!(
+            self.billing_ledger.is_some() == self.user_subscription.ledger_bump.is_some(),
+            SubscriptionError::MissingBillingLedger
+        );
+
+        // failed subscriptions have no task left in the queue
+        if self
+            .user_subscription
+            .has_queued_task(&self.subscription_plan)
+        {
             self.dequeue_task()?;
         };
 
+        if self.user_subscription.billing_mode != BillingMode::Vault {
+            revoke_plan(
+                self.token_program.to_account_info(),
+                &self.subscriber_ata,
+                self.subscriber.to_account_info(),
+                &self.subscription_plan.key(),
+            )?;
+        }
+
         self.user_subscription.status = Status::Canceled;
 
+        self.subscription_plan.subscriber_count = self
+            .subscription_plan
+            .subscriber_count
+            .checked_sub(1)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
         emit!(CancelSubscriptionEvent {
             subscriber: self.subscriber.key(),
             subscription: self.user_subscription.subscription.key()
@@ -86,12 +133,7 @@ impl<'info> CancelSubscription<'info> {
     }
 
     pub fn dequeue_task(&mut self) -> Result<()> {
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            QUEUE_AUTHORITY_SEED,
-            &[self.global_state.queue_authority_bump],
-        ]];
-
-        let ctx = CpiContext::new_with_signer(
+        dequeue_charge_task(
             self.tuktuk_program.to_account_info(),
             DequeueTaskV0 {
                 queue_authority: self.queue_authority.to_account_info(),
@@ -100,9 +142,7 @@ impl<'info> CancelSubscription<'info> {
                 task_queue: self.task_queue.to_account_info(),
                 task: self.task.to_account_info(),
             },
-            signer_seeds,
-        );
-
-        dequeue_task_v0(ctx)
+            self.global_state.queue_authority_bump,
+        )
     }
 }
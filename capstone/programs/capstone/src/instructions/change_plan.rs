@@ -0,0 +1,599 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::sysvar::instructions::ID as INSTRUCTIONS_SYSVAR_ID,
+    system_program::{self, Transfer},
+};
+
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        transfer_checked, ApproveChecked, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+use tuktuk_program::{
+    tuktuk::{
+        cpi::accounts::{DequeueTaskV0, QueueTaskV0},
+        program::Tuktuk,
+    },
+    TaskQueueAuthorityV0, TriggerV0,
+};
+
+use crate::{
+    error::SubscriptionError,
+    events::{ChargeEvent, PlanChangedEvent},
+    states::{
+        AcceptedMints, BillingLedger, BillingMode, ChargeOutcome, GlobalState, LedgerEntry, Status,
+        SubscriptionPlan, UserSubscription, ACCEPTED_MINTS_SEED, FEE_TOKEN_VAULT_SEED,
+        GLOBAL_STATE_SEED, LEDGER_SEED, QUEUE_AUTHORITY_SEED, SUBSCRIBER_VAULT_SEED,
+        SUBSCRIPTION_SEED,
+    },
+    utils::{
+        approve_plan, charge_instruction, create_charge_receipt, dequeue_charge_task, pay_merchant,
+        payout_accounts, queue_charge_task, receipt_rent,
+    },
+};
+
+#[derive(Accounts)]
+#[instruction(task_id: u16)]
+pub struct ChangePlan<'info> {
+    #[account(mut)]
+    pub subscriber: Signer<'info>,
+    #[account(
+        mut,
+        close = subscriber,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), old_subscription_plan.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.status == Status::Active @ SubscriptionError::SubscriptionNotActive,
+        // usage is priced by the old plan, it has to be billed before switching
+        constraint = user_subscription.usage_units == 0 @ SubscriptionError::UnbilledUsage
+    )]
+    pub user_subscription: Box<Account<'info, UserSubscription>>,
+    // closed with the old subscription, its history is copied to `new_billing_ledger` first
+    #[account(
+        mut,
+        close = subscriber,
+        seeds = [LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = billing_ledger.bump
+    )]
+    pub billing_ledger: Option<Box<Account<'info, BillingLedger>>>,
+    #[account(mut)]
+    pub old_subscription_plan: Box<Account<'info, SubscriptionPlan>>,
+    #[account(
+        init,
+        payer = subscriber,
+        space = UserSubscription::DISCRIMINATOR.len() + UserSubscription::INIT_SPACE,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), new_subscription_plan.key().as_ref()],
+        bump
+    )]
+    pub new_user_subscription: Box<Account<'info, UserSubscription>>,
+    #[account(
+        mut,
+        constraint = new_subscription_plan.active @ SubscriptionError::InactivePlan,
+        constraint = new_subscription_plan.merchant == old_subscription_plan.merchant @ SubscriptionError::PlanMismatch,
+        constraint = new_subscription_plan.mint == old_subscription_plan.mint @ SubscriptionError::PlanMismatch
+    )]
+    pub new_subscription_plan: Box<Account<'info, SubscriptionPlan>>,
+    // given exactly when the old subscription kept a ledger
+    #[account(
+        init,
+        payer = subscriber,
+        space = BillingLedger::DISCRIMINATOR.len() + BillingLedger::INIT_SPACE,
+        seeds = [LEDGER_SEED, new_user_subscription.key().as_ref()],
+        bump
+    )]
+    pub new_billing_ledger: Option<Box<Account<'info, BillingLedger>>>,
+    #[account(
+        seeds = [ACCEPTED_MINTS_SEED],
+        bump = accepted_mints.bump
+    )]
+    pub accepted_mints: Box<Account<'info, AcceptedMints>>,
+    #[account(
+        address = new_subscription_plan.mint @ SubscriptionError::MintMismatch,
+        mint::token_program = token_program,
+        constraint = accepted_mints.contains(&mint.key()) @ SubscriptionError::MintNotAccepted
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = subscriber,
+        associated_token::token_program = token_program
+    )]
+    pub subscriber_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [SUBSCRIBER_VAULT_SEED, subscriber.key.as_ref(), mint.key().as_ref()],
+        token::mint = mint,
+        token::authority = subscriber_vault,
+        token::token_program = token_program,
+        bump = user_subscription.subscriber_vault_bump
+    )]
+    pub subscriber_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        address = new_subscription_plan.merchant_ata
+    )]
+    pub merchant_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [FEE_TOKEN_VAULT_SEED, mint.key().as_ref()],
+        token::mint = mint,
+        token::authority = fee_token_vault,
+        token::token_program = token_program,
+        bump
+    )]
+    pub fee_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    /// CHECK: receipt of a prorated charge, numbered from the new plan's charge sequence and
+    /// checked when written
+    pub charge_receipt: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump
+    )]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    // TUKTUK ACCOUNTS
+    #[account(
+        mut,
+        address = user_subscription.task_queue @ SubscriptionError::InvalidTaskQueue
+    )]
+    /// CHECK: via address
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [QUEUE_AUTHORITY_SEED],
+        bump = global_state.queue_authority_bump
+    )]
+    /// CHECK: via seeds
+    pub queue_authority: UncheckedAccount<'info>,
+    #[account(
+      seeds = [b"task_queue_authority", task_queue.key().as_ref(), queue_authority.key().as_ref()],
+      bump = task_queue_authority.bump_seed,
+      seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    pub task_queue_authority: Box<Account<'info, TaskQueueAuthorityV0>>,
+    #[account(
+        mut,
+        seeds = [b"task", task_queue.key().as_ref(), user_subscription.next_task_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    /// CHECK: via seeds, queued task of the old subscription, closed in CPI
+    pub task: AccountInfo<'info>,
+    #[account(
+        mut,
+        seeds = [b"task", task_queue.key().as_ref(), task_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    /// CHECK: via seeds, initialized in CPI
+    pub new_task: AccountInfo<'info>,
+
+    // PROGRAMS
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub tuktuk_program: Program<'info, Tuktuk>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ChangePlan<'info> {
+    pub fn change_plan(
+        &mut self,
+        task_id: u16,
+        bumps: &ChangePlanBumps,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        require_keys_neq!(
+            self.old_subscription_plan.key(),
+            self.new_subscription_plan.key(),
+            SubscriptionError::PlanMismatch
+        );
+        let payees = payout_accounts(&self.new_subscription_plan.payout_split, remaining_accounts)
+            .ok_or(SubscriptionError::InvalidPayoutSplit)?;
+        require!(
+            self.billing_ledger.is_some() == self.user_subscription.ledger_bump.is_some()
+                && self.new_billing_ledger.is_some() == self.billing_ledger.is_some(),
+            SubscriptionError::MissingBillingLedger
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let old = &self.user_subscription;
+
+        // the spending cap carries over and has to fit the new plan
+        let (new_amount, _) = self
+            .new_subscription_plan
+            .charge_amount(old.cycles_charged, now);
+        match old.spending_cap {
+            Some(cap) => require!(cap >= new_amount, SubscriptionError::InvalidSpendingCap),
+            None => require!(
+                self.new_subscription_plan.metering.is_none(),
+                SubscriptionError::InvalidSpendingCap
+            ),
+        }
+
+        // nothing was paid yet while still in a trial, so there is nothing to credit
+        let prorate = self.old_subscription_plan.proration_enabled
+            && self.new_subscription_plan.proration_enabled
+            && old.cycles_charged > 0;
+
+        // charge sequence, fee and tier of the prorated charge, its receipt is written by the new
+        // subscription
+        let mut charge = None;
+
+        let (credit, charged, last_exec_ts, cycles_charged, last_charge_amount) = if prorate {
+            let credit = self.unused_credit(now)?;
+            let total_credit = old
+                .credit
+                .checked_add(credit)
+                .ok_or(SubscriptionError::ArithmeticError)?;
+
+            // new plan starts a fresh cycle right away, paid with the credit first
+            let (amount, tier) = self
+                .new_subscription_plan
+                .charge_amount(old.cycles_charged, now);
+            let due = amount.saturating_sub(total_credit);
+
+            if due > 0 {
+                self.top_up_vault(due)?;
+                let fee = self.transfer_tokens(due, &payees)?;
+                let charge_seq = self
+                    .new_subscription_plan
+                    .next_charge_seq()
+                    .ok_or(SubscriptionError::ArithmeticError)?;
+
+                charge = Some((charge_seq, fee, tier));
+            }
+
+            let last_exec_ts = self
+                .new_subscription_plan
+                .interval
+                .next_charge_ts(now)
+                .ok_or(SubscriptionError::ArithmeticError)?;
+            let cycles_charged = self
+                .user_subscription
+                .cycles_charged
+                .checked_add(1)
+                .ok_or(SubscriptionError::ArithmeticError)?;
+
+            (
+                total_credit.saturating_sub(amount),
+                due,
+                last_exec_ts,
+                cycles_charged,
+                amount,
+            )
+        } else {
+            // without proration the new price simply applies from the current due date
+            (
+                old.credit,
+                0,
+                old.last_exec_ts,
+                old.cycles_charged,
+                old.last_charge_amount,
+            )
+        };
+
+        self.new_user_subscription.set_inner(UserSubscription {
+            subscriber: self.subscriber.key(),
+            subscriber_ata: self.subscriber_ata.key(),
+            subscription: self.new_subscription_plan.key(),
+            billing_mode: self.user_subscription.billing_mode,
+            status: Status::Active,
+            failure_count: 0,
+            last_exec_ts,
+            resume_at: None,
+            access_until: None,
+            retry_at: None,
+            cycles_charged,
+            last_charge_amount,
+            credit,
+            usage_units: 0,
+            spending_cap: self.user_subscription.spending_cap,
+            // coupons belong to a plan, the discount does not carry over
+            discount: None,
+            discount_cycles: 0,
+            crank_reserve: self.user_subscription.crank_reserve,
+            depleted_status: None,
+            // the receipt of the prorated charge is the first one of the new subscription
+            next_receipt: charge.map_or(self.new_subscription_plan.charge_seq, |(seq, _, _)| seq),
+            next_task_id: task_id,
+            task_queue: self.task_queue.key(),
+            ledger_bump: bumps.new_billing_ledger,
+            subscriber_vault_bump: self.user_subscription.subscriber_vault_bump,
+            bump: bumps.new_user_subscription,
+        });
+
+        // the old account is closed to the subscriber, move the crank reserve over first
+        let crank_reserve = self.user_subscription.crank_reserve;
+        self.user_subscription.sub_lamports(crank_reserve)?;
+        self.new_user_subscription.add_lamports(crank_reserve)?;
+
+        self.carry_over_ledger();
+
+        self.old_subscription_plan.subscriber_count = self
+            .old_subscription_plan
+            .subscriber_count
+            .checked_sub(1)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        self.new_subscription_plan.subscriber_count = self
+            .new_subscription_plan
+            .subscriber_count
+            .checked_add(1)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        if let Some((charge_seq, fee, tier)) = charge {
+            let receipt = self.write_receipt(charge_seq, charged)?;
+            self.record_in_ledger(
+                self.user_subscription.cycles_charged,
+                now,
+                charged,
+                charge_seq,
+            );
+
+            emit!(ChargeEvent {
+                subscriber: self.subscriber.key(),
+                subscription: self.new_subscription_plan.key(),
+                amount: charged,
+                fee,
+                tier,
+                charge_seq,
+                usage_units: 0,
+                receipt: Some(receipt)
+            });
+        }
+
+        // a delegated allowance is moved over to the new plan
+        self.approve()?;
+        self.replace_task(task_id, last_exec_ts)?;
+
+        emit!(PlanChangedEvent {
+            subscriber: self.subscriber.key(),
+            old_subscription: self.old_subscription_plan.key(),
+            new_subscription: self.new_subscription_plan.key(),
+            credit,
+            charged,
+            next_exec_ts: last_exec_ts
+        });
+
+        Ok(())
+    }
+
+    // the history of the old subscription continues on the new one
+    pub fn carry_over_ledger(&mut self) {
+        let (Some(old), Some(new)) = (&self.billing_ledger, self.new_billing_ledger.as_mut())
+        else {
+            return;
+        };
+
+        new.set_inner(BillingLedger {
+            user_subscription: self.new_user_subscription.key(),
+            head: old.head,
+            entries: old.entries.clone(),
+            bump: self.new_user_subscription.ledger_bump.unwrap_or_default(),
+        });
+    }
+
+    pub fn record_in_ledger(&mut self, cycle: u32, timestamp: i64, amount: u64, charge_seq: u64) {
+        if let Some(billing_ledger) = self.new_billing_ledger.as_mut() {
+            billing_ledger.record(LedgerEntry {
+                cycle,
+                timestamp,
+                amount,
+                mint: self.mint.key(),
+                outcome: ChargeOutcome::Paid,
+                charge_seq: Some(charge_seq),
+            });
+        }
+    }
+
+    // the subscriber pays the rent of the receipt
+    pub fn write_receipt(&mut self, charge_seq: u64, amount: u64) -> Result<Pubkey> {
+        let ctx = CpiContext::new(
+            self.system_program.to_account_info(),
+            Transfer {
+                from: self.subscriber.to_account_info(),
+                to: self.charge_receipt.to_account_info(),
+            },
+        );
+
+        system_program::transfer(ctx, receipt_rent()?)?;
+
+        create_charge_receipt(
+            &self.charge_receipt.to_account_info(),
+            &self.system_program.to_account_info(),
+            &mut self.new_user_subscription,
+            charge_seq,
+            amount,
+        )
+    }
+
+    // value of the part of the current cycle that is paid for but not used yet
+    pub fn unused_credit(&self, now: i64) -> Result<u64> {
+        let last_exec_ts = self.user_subscription.last_exec_ts;
+        // calendar cycles differ in length, the paid one started at the previous due date
+        let cycle_length = self
+            .old_subscription_plan
+            .interval
+            .previous_charge_ts(last_exec_ts)
+            .map(|cycle_start_ts| last_exec_ts.saturating_sub(cycle_start_ts))
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        let remaining = last_exec_ts.saturating_sub(now).min(cycle_length).max(0);
+
+        let credit = (self.user_subscription.last_charge_amount as u128)
+            .checked_mul(remaining as u128)
+            .and_then(|x| x.checked_div(cycle_length as u128))
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        u64::try_from(credit).map_err(|_| SubscriptionError::ArithmeticError.into())
+    }
+
+    pub fn top_up_vault(&mut self, amount: u64) -> Result<()> {
+        // delegated subscriptions are paid straight from the subscriber ata
+        if self.user_subscription.billing_mode != BillingMode::Vault {
+            return Ok(());
+        }
+
+        let missing = amount.saturating_sub(self.subscriber_vault.amount);
+
+        if missing == 0 {
+            return Ok(());
+        }
+
+        let ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: self.subscriber_ata.to_account_info(),
+                to: self.subscriber_vault.to_account_info(),
+                mint: self.mint.to_account_info(),
+                authority: self.subscriber.to_account_info(),
+            },
+        );
+
+        transfer_checked(ctx, missing, self.mint.decimals)
+    }
+
+    // renews the allowance of a delegated subscription for the plan it is billed on from now
+    pub fn approve(&mut self) -> Result<()> {
+        let BillingMode::Delegated { approved_cycles } = self.user_subscription.billing_mode else {
+            return Ok(());
+        };
+
+        let allowance = self
+            .new_subscription_plan
+            .allowance(approved_cycles, self.user_subscription.spending_cap)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        approve_plan(
+            self.token_program.to_account_info(),
+            ApproveChecked {
+                to: self.subscriber_ata.to_account_info(),
+                mint: self.mint.to_account_info(),
+                delegate: self.new_subscription_plan.to_account_info(),
+                authority: self.subscriber.to_account_info(),
+            },
+            &self.subscriber_ata,
+            Some(self.old_subscription_plan.key()),
+            allowance,
+            self.mint.decimals,
+        )
+    }
+
+    // returns the protocol fee taken from `amount`
+    pub fn transfer_tokens(
+        &mut self,
+        amount: u64,
+        payees: &[(AccountInfo<'info>, u16)],
+    ) -> Result<u64> {
+        let fee = self
+            .global_state
+            .protocol_fee(amount)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        let mint_key = self.mint.key();
+        let vault_seeds: &[&[&[u8]]] = &[&[
+            SUBSCRIBER_VAULT_SEED,
+            self.subscriber.key.as_ref(),
+            mint_key.as_ref(),
+            &[self.user_subscription.subscriber_vault_bump],
+        ]];
+
+        // the subscriber signs this instruction, so delegated subscriptions need no approval here
+        let (from, authority, signer_seeds) = match self.user_subscription.billing_mode {
+            BillingMode::Vault => (
+                self.subscriber_vault.to_account_info(),
+                self.subscriber_vault.to_account_info(),
+                vault_seeds,
+            ),
+            BillingMode::Delegated { .. } => (
+                self.subscriber_ata.to_account_info(),
+                self.subscriber.to_account_info(),
+                &[] as &[&[&[u8]]],
+            ),
+        };
+
+        pay_merchant(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from,
+                to: self.merchant_ata.to_account_info(),
+                mint: self.mint.to_account_info(),
+                authority,
+            },
+            self.fee_token_vault.to_account_info(),
+            payees,
+            signer_seeds,
+            amount,
+            fee,
+            self.mint.decimals,
+        )?;
+
+        Ok(fee)
+    }
+
+    // the queued task points at the old subscription account, swap it for one on the new plan.
+    // Batch billed plans have no task on their side of the switch
+    pub fn replace_task(&mut self, task_id: u16, timestamp: i64) -> Result<()> {
+        if !self.old_subscription_plan.batch_billing {
+            dequeue_charge_task(
+                self.tuktuk_program.to_account_info(),
+                DequeueTaskV0 {
+                    queue_authority: self.queue_authority.to_account_info(),
+                    rent_refund: self.task_queue.to_account_info(),
+                    task_queue_authority: self.task_queue_authority.to_account_info(),
+                    task_queue: self.task_queue.to_account_info(),
+                    task: self.task.to_account_info(),
+                },
+                self.global_state.queue_authority_bump,
+            )?;
+        }
+
+        if self.new_subscription_plan.batch_billing {
+            return Ok(());
+        }
+
+        queue_charge_task(
+            self.tuktuk_program.to_account_info(),
+            QueueTaskV0 {
+                payer: self.subscriber.to_account_info(),
+                queue_authority: self.queue_authority.to_account_info(),
+                task_queue: self.task_queue.to_account_info(),
+                task_queue_authority: self.task_queue_authority.to_account_info(),
+                task: self.new_task.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            self.global_state.queue_authority_bump,
+            task_id,
+            TriggerV0::Timestamp(timestamp),
+            self.global_state.crank_reward,
+            charge_instruction(
+                crate::accounts::ChargeUserRecurring {
+                    subscriber: self.subscriber.key(),
+                    merchant: self.new_subscription_plan.merchant.key(),
+                    user_subscription: self.new_user_subscription.key(),
+                    subscription_plan: self.new_subscription_plan.key(),
+                    merchant_ata: self.merchant_ata.key(),
+                    accepted_mints: self.accepted_mints.key(),
+                    global_state: self.global_state.key(),
+                    fee_token_vault: self.fee_token_vault.key(),
+                    task_queue: self.task_queue.key(),
+                    billing_ledger: self
+                        .new_user_subscription
+                        .billing_ledger(&self.new_user_subscription.key()),
+                    charge_receipt: self
+                        .new_user_subscription
+                        .next_receipt_address(&self.new_user_subscription.key()),
+                    mint: self.mint.key(),
+                    subscriber_vault: self.subscriber_vault.key(),
+                    subscriber_ata: self.subscriber_ata.key(),
+                    instructions: INSTRUCTIONS_SYSVAR_ID.key(),
+                    associated_token_program: self.associated_token_program.key(),
+                    token_program: self.token_program.key(),
+                    system_program: self.system_program.key(),
+                },
+                &self.new_subscription_plan.payout_split,
+            ),
+        )
+    }
+}
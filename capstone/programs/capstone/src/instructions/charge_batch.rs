@@ -0,0 +1,435 @@
+use anchor_lang::{prelude::*, solana_program::hash::hash};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{
+    error::SubscriptionError,
+    events::{
+        BatchChargeEvent, ChargeEvent, SubscriptionFailedEvent, SubscriptionResumedEvent,
+        SubscriptionRetryScheduledEvent,
+    },
+    states::{
+        AcceptedMints, BatchChargeOutcome, BillingLedger, BillingMode, ChargeOutcome, GlobalState,
+        LedgerEntry, Status, SubscriptionPlan, UserSubscription, ACCEPTED_MINTS_SEED,
+        FEE_TOKEN_VAULT_SEED, GLOBAL_STATE_SEED, PLAN_SEED, SUBSCRIBER_VAULT_SEED,
+    },
+    utils::{
+        chargeable_balance, create_charge_receipt, pay_merchant, pay_receipt_rent, payout_accounts,
+        receipt_rent,
+    },
+};
+
+// accounts passed per subscription after the payout recipients: user subscription, subscriber
+// vault, subscriber ata, the receipt its next charge writes and its billing ledger, all writable.
+// The ledger address is passed even if the subscriber never created one
+pub const BATCH_GROUP_LEN: usize = 5;
+
+#[derive(Accounts)]
+pub struct ChargeBatch<'info> {
+    /// CHECK: only used as merchant ata authority
+    pub merchant: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        has_one = merchant,
+        constraint = subscription_plan.batch_billing @ SubscriptionError::NotBatchBilled
+    )]
+    pub subscription_plan: Box<Account<'info, SubscriptionPlan>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = merchant,
+        associated_token::token_program = token_program
+    )]
+    pub merchant_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        seeds = [ACCEPTED_MINTS_SEED],
+        bump = accepted_mints.bump
+    )]
+    pub accepted_mints: Box<Account<'info, AcceptedMints>>,
+    #[account(
+        address = subscription_plan.mint @ SubscriptionError::MintMismatch,
+        mint::token_program = token_program,
+        constraint = accepted_mints.contains(&mint.key()) @ SubscriptionError::MintNotAccepted
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump
+    )]
+    pub global_state: Box<Account<'info, GlobalState>>,
+    #[account(
+        mut,
+        seeds = [FEE_TOKEN_VAULT_SEED, mint.key().as_ref()],
+        token::mint = mint,
+        token::authority = fee_token_vault,
+        token::token_program = token_program,
+        bump
+    )]
+    pub fee_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ChargeBatch<'info> {
+    // charges every due subscription of the plan passed as remaining accounts. Batch billed plans
+    // queue no task per subscriber, so this crank also skips the cycles of a paused plan, ends
+    // pauses and schedules retries the way `charge_user_recurring` does for a single subscription
+    pub fn charge_batch(&mut self, remaining_accounts: &'info [AccountInfo<'info>]) -> Result<()> {
+        let split_len = self.subscription_plan.payout_split.len();
+        let payees = payout_accounts(&self.subscription_plan.payout_split, remaining_accounts)
+            .ok_or(SubscriptionError::InvalidPayoutSplit)?;
+
+        let groups = remaining_accounts[split_len..].chunks_exact(BATCH_GROUP_LEN);
+        require!(
+            groups.remainder().is_empty(),
+            SubscriptionError::InvalidSubscription
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+
+        for group in groups {
+            let (outcome, subscriber, amount) = self.charge_one(group, &payees, now)?;
+
+            emit!(BatchChargeEvent {
+                subscriber,
+                subscription: self.subscription_plan.key(),
+                user_subscription: group[0].key(),
+                outcome,
+                amount
+            });
+        }
+
+        Ok(())
+    }
+
+    // errors only on accounts that do not match the subscription, which reverts the batch
+    fn charge_one(
+        &mut self,
+        group: &'info [AccountInfo<'info>],
+        payees: &[(AccountInfo<'info>, u16)],
+        now: i64,
+    ) -> Result<(BatchChargeOutcome, Pubkey, u64)> {
+        let [subscription_info, vault_info, ata_info, receipt_info, ledger_info] = group else {
+            return err!(SubscriptionError::InvalidSubscription);
+        };
+
+        // closed in the meantime or not writable, nothing to charge
+        let Ok(mut user_subscription) = Account::<UserSubscription>::try_from(subscription_info)
+        else {
+            return Ok((BatchChargeOutcome::Skipped, Pubkey::default(), 0));
+        };
+        let subscriber = user_subscription.subscriber;
+
+        if !subscription_info.is_writable {
+            return Ok((BatchChargeOutcome::Skipped, subscriber, 0));
+        }
+
+        require_keys_eq!(
+            user_subscription.subscription,
+            self.subscription_plan.key(),
+            SubscriptionError::InvalidSubscription
+        );
+
+        let mint_key = self.mint.key();
+        let vault_bump = [user_subscription.subscriber_vault_bump];
+        let vault_seeds: &[&[u8]] = &[
+            SUBSCRIBER_VAULT_SEED,
+            subscriber.as_ref(),
+            mint_key.as_ref(),
+            &vault_bump,
+        ];
+        let vault_key = Pubkey::create_program_address(vault_seeds, &crate::ID)
+            .map_err(|_| error!(SubscriptionError::InvalidSubscription))?;
+
+        require_keys_eq!(
+            vault_info.key(),
+            vault_key,
+            SubscriptionError::InvalidSubscription
+        );
+        require_keys_eq!(
+            ata_info.key(),
+            user_subscription.subscriber_ata,
+            SubscriptionError::InvalidSubscription
+        );
+        let billing_ledger = user_subscription.billing_ledger(subscription_info.key);
+        if let Some(ledger_key) = billing_ledger {
+            require_keys_eq!(
+                ledger_info.key(),
+                ledger_key,
+                SubscriptionError::MissingBillingLedger
+            );
+        }
+
+        // failed subscriptions wait for a reactivation, canceled ones keep access until
+        // `access_until` and are closed by the subscriber
+        match user_subscription.status {
+            Status::Active => {}
+            Status::PastDue if user_subscription.retry_at.is_some_and(|ts| now < ts) => {
+                return Ok((BatchChargeOutcome::NotDue, subscriber, 0));
+            }
+            Status::PastDue => {}
+            Status::Paused => match user_subscription.resume_at {
+                Some(resume_at) if now >= resume_at => {
+                    user_subscription.resume(now);
+                    user_subscription.exit(&crate::ID)?;
+
+                    emit!(SubscriptionResumedEvent {
+                        subscriber,
+                        subscription: self.subscription_plan.key(),
+                        next_exec_ts: user_subscription.last_exec_ts
+                    });
+                }
+                _ => return Ok((BatchChargeOutcome::Skipped, subscriber, 0)),
+            },
+            _ => return Ok((BatchChargeOutcome::Skipped, subscriber, 0)),
+        }
+
+        if now < user_subscription.last_exec_ts {
+            return Ok((BatchChargeOutcome::NotDue, subscriber, 0));
+        }
+
+        // merchant paused the plan, the due cycle is skipped without charging
+        if !self.subscription_plan.active {
+            user_subscription.last_exec_ts = self
+                .subscription_plan
+                .interval
+                .next_charge_ts(user_subscription.last_exec_ts)
+                .ok_or(SubscriptionError::ArithmeticError)?;
+            user_subscription.exit(&crate::ID)?;
+
+            return Ok((BatchChargeOutcome::Skipped, subscriber, 0));
+        }
+
+        // e.g. an ata closed in the meantime, nothing to charge from
+        let (Ok(subscriber_vault), Ok(subscriber_ata)) = (
+            InterfaceAccount::<TokenAccount>::try_from(vault_info),
+            InterfaceAccount::<TokenAccount>::try_from(ata_info),
+        ) else {
+            return Ok((BatchChargeOutcome::Skipped, subscriber, 0));
+        };
+
+        if billing_ledger.is_some() && !ledger_info.is_writable {
+            return Ok((BatchChargeOutcome::Skipped, subscriber, 0));
+        }
+
+        let quote = user_subscription
+            .quote(&self.subscription_plan, user_subscription.last_exec_ts)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        let due = quote.due;
+
+        let available = chargeable_balance(
+            user_subscription.billing_mode,
+            &subscriber_vault,
+            &subscriber_ata,
+            &self.subscription_plan.key(),
+        );
+
+        if available < due {
+            let cycle = user_subscription.cycles_charged;
+
+            self.record_failure(&mut user_subscription, now)?;
+            user_subscription.exit(&crate::ID)?;
+
+            if billing_ledger.is_some() {
+                self.record_in_ledger(
+                    ledger_info,
+                    LedgerEntry {
+                        cycle,
+                        timestamp: now,
+                        amount: due,
+                        mint: mint_key,
+                        outcome: ChargeOutcome::Failed,
+                        charge_seq: None,
+                    },
+                )?;
+            }
+
+            return Ok((BatchChargeOutcome::InsufficientFunds, subscriber, due));
+        }
+
+        // the receipt is paid out of the crank reserve, charged again once it is topped up
+        let rent = receipt_rent()?;
+
+        if due > 0 && user_subscription.crank_reserve < rent {
+            return Ok((BatchChargeOutcome::Skipped, subscriber, 0));
+        }
+
+        let fee = if due > 0 {
+            self.transfer_tokens(
+                user_subscription.billing_mode,
+                vault_info,
+                ata_info,
+                vault_seeds,
+                payees,
+                due,
+            )?
+        } else {
+            0
+        };
+
+        let cycle = user_subscription.cycles_charged;
+        let next_exec_ts = self
+            .subscription_plan
+            .interval
+            .next_charge_ts(user_subscription.last_exec_ts)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        user_subscription
+            .record_charge(&quote, next_exec_ts)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        let charge_seq = self
+            .subscription_plan
+            .next_charge_seq()
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        let receipt = if due > 0 {
+            pay_receipt_rent(&mut user_subscription, receipt_info, rent)?;
+
+            Some(create_charge_receipt(
+                receipt_info,
+                &self.system_program.to_account_info(),
+                &mut user_subscription,
+                charge_seq,
+                due,
+            )?)
+        } else {
+            None
+        };
+
+        // accounts loaded from remaining accounts are not written back by anchor
+        user_subscription.exit(&crate::ID)?;
+
+        if billing_ledger.is_some() {
+            self.record_in_ledger(
+                ledger_info,
+                LedgerEntry {
+                    cycle,
+                    timestamp: now,
+                    amount: due,
+                    mint: mint_key,
+                    outcome: ChargeOutcome::Paid,
+                    charge_seq: Some(charge_seq),
+                },
+            )?;
+        }
+
+        emit!(ChargeEvent {
+            subscriber,
+            subscription: self.subscription_plan.key(),
+            amount: due,
+            fee,
+            tier: quote.tier,
+            charge_seq,
+            usage_units: quote.usage_units,
+            receipt
+        });
+
+        Ok((BatchChargeOutcome::Charged, subscriber, due))
+    }
+
+    // `last_exec_ts` stays at the due date of the unpaid cycle, the retry is kept in `retry_at`
+    // as there is no task to time it
+    #[inline(never)]
+    fn record_failure(&self, user_subscription: &mut UserSubscription, now: i64) -> Result<()> {
+        user_subscription.failure_count = user_subscription
+            .failure_count
+            .checked_add(1)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        let next_attempt_ts = self.subscription_plan.retry_ts(
+            user_subscription.last_exec_ts,
+            now,
+            user_subscription.failure_count,
+        );
+        user_subscription.retry_at = next_attempt_ts;
+
+        match next_attempt_ts {
+            Some(next_attempt_ts) => {
+                user_subscription.status = Status::PastDue;
+
+                emit!(SubscriptionRetryScheduledEvent {
+                    subscriber: user_subscription.subscriber,
+                    subscription: self.subscription_plan.key(),
+                    failure_count: user_subscription.failure_count,
+                    next_attempt_ts
+                });
+            }
+            None => {
+                user_subscription.status = Status::Failed;
+
+                emit!(SubscriptionFailedEvent {
+                    subscriber: user_subscription.subscriber,
+                    subscription: self.subscription_plan.key()
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // kept out of the loop's stack frame, the ledger is too large to hold next to the subscription
+    #[inline(never)]
+    fn record_in_ledger(
+        &self,
+        ledger_info: &'info AccountInfo<'info>,
+        entry: LedgerEntry,
+    ) -> Result<()> {
+        let mut billing_ledger = Account::<BillingLedger>::try_from(ledger_info)?;
+        billing_ledger.record(entry);
+
+        billing_ledger.exit(&crate::ID)
+    }
+
+    // returns the protocol fee taken from `amount`
+    fn transfer_tokens(
+        &self,
+        billing_mode: BillingMode,
+        subscriber_vault: &AccountInfo<'info>,
+        subscriber_ata: &AccountInfo<'info>,
+        vault_seeds: &[&[u8]],
+        payees: &[(AccountInfo<'info>, u16)],
+        amount: u64,
+    ) -> Result<u64> {
+        let fee = self
+            .global_state
+            .protocol_fee(amount)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        let merchant = self.subscription_plan.merchant;
+        let name_hash = hash(self.subscription_plan.name.as_bytes());
+        let plan_bump = [self.subscription_plan.bump];
+        let plan_seeds: &[&[u8]] = &[PLAN_SEED, merchant.as_ref(), name_hash.as_ref(), &plan_bump];
+
+        // the vault signs for itself, in delegated mode the plan signs as the approved delegate
+        let (from, authority, signer_seeds) = match billing_mode {
+            BillingMode::Vault => (
+                subscriber_vault.clone(),
+                subscriber_vault.clone(),
+                vault_seeds,
+            ),
+            BillingMode::Delegated { .. } => (
+                subscriber_ata.clone(),
+                self.subscription_plan.to_account_info(),
+                plan_seeds,
+            ),
+        };
+
+        pay_merchant(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from,
+                to: self.merchant_ata.to_account_info(),
+                mint: self.mint.to_account_info(),
+                authority,
+            },
+            self.fee_token_vault.to_account_info(),
+            payees,
+            &[signer_seeds],
+            amount,
+            fee,
+            self.mint.decimals,
+        )?;
+
+        Ok(fee)
+    }
+}
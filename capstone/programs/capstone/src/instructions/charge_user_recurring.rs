@@ -1,32 +1,42 @@
 use anchor_lang::{
     prelude::*,
     solana_program::{
-        instruction::Instruction,
+        hash::hash,
         sysvar::instructions::{
             load_current_index_checked, load_instruction_at_checked, ID as INSTRUCTIONS_SYSVAR_ID,
         },
     },
-    InstructionData,
 };
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+    token_interface::{Mint, TokenAccount, TokenInterface, TransferChecked},
 };
 use tuktuk_program::{
-    compile_transaction, RunTaskReturnV0, TaskReturnV0, TransactionSourceV0, TriggerV0,
+    compile_transaction, tuktuk::client::args::RunTaskV0, RunTaskReturnV0, TaskReturnV0,
+    TransactionSourceV0, TriggerV0,
 };
 
 use crate::{
     error::SubscriptionError,
-    events::{ChargeEvent, SubscriptionFailedEvent},
+    events::{
+        CancelSubscriptionEvent, ChargeEvent, CrankReserveDepletedEvent, SubscriptionFailedEvent,
+        SubscriptionResumedEvent, SubscriptionRetryScheduledEvent,
+    },
     states::{
-        Status, SubscriptionPlan, UserSubscription, SUBSCRIBER_VAULT_SEED, SUBSCRIPTION_SEED,
+        AcceptedMints, BillingLedger, BillingMode, ChargeOutcome, GlobalState, LedgerEntry, Status,
+        SubscriptionPlan, UserSubscription, ACCEPTED_MINTS_SEED, FEE_TOKEN_VAULT_SEED,
+        GLOBAL_STATE_SEED, LEDGER_SEED, PLAN_SEED, SUBSCRIBER_VAULT_SEED, SUBSCRIPTION_SEED,
+    },
+    utils::{
+        charge_instruction, chargeable_balance, create_charge_receipt, pay_merchant,
+        pay_receipt_rent, payout_accounts, receipt_rent,
     },
 };
 
 #[derive(Accounts)]
 pub struct ChargeUserRecurring<'info> {
-    /// CHECK: called via tuktuk
+    #[account(mut)]
+    /// CHECK: called via tuktuk, receives the rent once the subscription is closed
     pub subscriber: UncheckedAccount<'info>,
     /// CHECK: called via tuktuk
     pub merchant: UncheckedAccount<'info>,
@@ -35,32 +45,71 @@ pub struct ChargeUserRecurring<'info> {
         seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), subscription_plan.key().as_ref()],
         bump = user_subscription.bump
     )]
-    pub user_subscription: Account<'info, UserSubscription>,
-    #[account(
-        constraint = subscription_plan.active @ SubscriptionError::InactivePlan
-    )]
-    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    pub user_subscription: Box<Account<'info, UserSubscription>>,
+    #[account(mut)]
+    pub subscription_plan: Box<Account<'info, SubscriptionPlan>>,
     #[account(
         mut,
         token::mint = mint,
         token::authority = subscriber_vault,
         token::token_program = token_program,
-        seeds = [SUBSCRIBER_VAULT_SEED, subscriber.key.as_ref()],
+        seeds = [SUBSCRIBER_VAULT_SEED, subscriber.key.as_ref(), mint.key().as_ref()],
         bump = user_subscription.subscriber_vault_bump
     )]
-    pub subscriber_vault: InterfaceAccount<'info, TokenAccount>,
+    pub subscriber_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    // charged directly in delegated billing mode
+    #[account(
+        mut,
+        address = user_subscription.subscriber_ata
+    )]
+    pub subscriber_ata: Box<InterfaceAccount<'info, TokenAccount>>,
     #[account(
         mut,
         associated_token::mint = mint,
         associated_token::authority = merchant,
         associated_token::token_program = token_program
     )]
-    pub merchant_ata: InterfaceAccount<'info, TokenAccount>,
+    pub merchant_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        seeds = [ACCEPTED_MINTS_SEED],
+        bump = accepted_mints.bump
+    )]
+    pub accepted_mints: Box<Account<'info, AcceptedMints>>,
     #[account(
         address = subscription_plan.mint @ SubscriptionError::MintMismatch,
-        mint::token_program = token_program
+        mint::token_program = token_program,
+        constraint = accepted_mints.contains(&mint.key()) @ SubscriptionError::MintNotAccepted
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump
+    )]
+    pub global_state: Box<Account<'info, GlobalState>>,
+    #[account(
+        mut,
+        seeds = [FEE_TOKEN_VAULT_SEED, mint.key().as_ref()],
+        token::mint = mint,
+        token::authority = fee_token_vault,
+        token::token_program = token_program,
+        bump
+    )]
+    pub fee_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        address = user_subscription.task_queue @ SubscriptionError::InvalidTaskQueue
     )]
-    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: via address, receives the crank reward of the next task
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = billing_ledger.bump
+    )]
+    pub billing_ledger: Option<Box<Account<'info, BillingLedger>>>,
+    #[account(mut)]
+    /// CHECK: written by a charge that moves tokens, checked against the subscription then
+    pub charge_receipt: UncheckedAccount<'info>,
 
     // programs
     #[account(address = INSTRUCTIONS_SYSVAR_ID)]
@@ -73,112 +122,473 @@ pub struct ChargeUserRecurring<'info> {
 }
 
 impl<'info> ChargeUserRecurring<'info> {
-    pub fn charge_user_recurring(&mut self) -> Result<RunTaskReturnV0> {
-        self.set_next_task_id()?; // is this right place?
+    pub fn charge_user_recurring(
+        &mut self,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<RunTaskReturnV0> {
+        self.set_next_task_id()?;
+
+        // the split changed after this task was compiled, nobody it does not list is paid. The
+        // task is queued again for the same due date with the current recipients instead
+        let Some(payees) =
+            payout_accounts(&self.subscription_plan.payout_split, remaining_accounts)
+        else {
+            msg!("payout split changed, requeueing with the current recipients");
+
+            return self.schedule_next_task(self.user_subscription.last_exec_ts);
+        };
+
+        // canceled at period end, the paid period is over so clean up instead of charging
+        if self.user_subscription.status == Status::Canceled {
+            // a task queued before the cancellation, e.g. the resume check of a pause, can fire
+            // before the paid period ends, wait for it instead
+            if let Some(access_until) = self.user_subscription.access_until {
+                if Clock::get()?.unix_timestamp < access_until {
+                    return self.schedule_next_task(access_until);
+                }
+            }
+
+            return self.close_subscription(&payees);
+        }
+
+        // subscriber paused, reschedule without charging until the pause ends
+        if self.user_subscription.status == Status::Paused {
+            let now = Clock::get()?.unix_timestamp;
+
+            match self.user_subscription.resume_at {
+                Some(resume_at) if now >= resume_at => {
+                    self.user_subscription.resume(now);
+
+                    emit!(SubscriptionResumedEvent {
+                        subscriber: self.subscriber.key(),
+                        subscription: self.subscription_plan.key(),
+                        next_exec_ts: self.user_subscription.last_exec_ts
+                    });
+                }
+                resume_at => {
+                    msg!("subscription is paused, skipping current cycle");
+
+                    let next_check_ts = match resume_at {
+                        Some(ts) => ts,
+                        None => self
+                            .subscription_plan
+                            .interval
+                            .next_charge_ts(now)
+                            .ok_or(SubscriptionError::ArithmeticError)?,
+                    };
+
+                    return self.schedule_next_task(next_check_ts);
+                }
+            }
+        }
+
+        // merchant paused the plan, skip this cycle without charging and keep the task chain alive
+        if !self.subscription_plan.active {
+            msg!("plan is paused, skipping current cycle");
+
+            let next_exec_ts = self
+                .subscription_plan
+                .interval
+                .next_charge_ts(self.user_subscription.last_exec_ts)
+                .ok_or(SubscriptionError::ArithmeticError)?;
+
+            self.user_subscription.last_exec_ts = next_exec_ts;
+
+            return self.schedule_next_task(next_exec_ts);
+        }
+
+        // fired before the cycle is due, wait for the due date
+        let now = Clock::get()?.unix_timestamp;
+
+        if now < self.user_subscription.last_exec_ts {
+            msg!("cycle is already paid, rescheduling");
+
+            return self.schedule_next_task(self.user_subscription.last_exec_ts);
+        }
+
+        // credit left over from a prorated plan change is used up before the vault
+        let quote = self
+            .user_subscription
+            .quote(&self.subscription_plan, self.user_subscription.last_exec_ts)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        let due = quote.due;
+        let cycle = self.user_subscription.cycles_charged;
 
         // improvements: check cpi failure
-        if self.subscriber_vault.amount < self.subscription_plan.amount {
-            msg!("not enough amount of tokens in vault");
+        if self.available_balance() < due {
+            msg!("not enough amount of tokens in vault or allowance");
 
             match self.user_subscription.failure_count.checked_add(1) {
                 Some(x) => self.user_subscription.failure_count = x,
                 None => return err!(SubscriptionError::ArithmeticError),
             };
 
-            if self.user_subscription.failure_count > self.subscription_plan.max_failure_count {
-                msg!("max failure count reached, changing status to failed");
+            // `last_exec_ts` stays at the due date of the unpaid cycle so the billing anchor does
+            // not drift, retries are timed from now (or the due date for offset policies)
+            let due_ts = self.user_subscription.last_exec_ts;
 
-                self.user_subscription.status = Status::Failed;
+            self.record_in_ledger(cycle, now, due, ChargeOutcome::Failed, None);
 
-                emit!(SubscriptionFailedEvent {
-                    subscriber: self.subscriber.key(),
-                    subscription: self.subscription_plan.key()
-                });
+            let next_attempt_ts = match self.subscription_plan.retry_ts(
+                due_ts,
+                now,
+                self.user_subscription.failure_count,
+            ) {
+                Some(ts) => ts,
+                None => {
+                    msg!("no retries left, changing status to failed");
 
-                // return no task so that recursion ends
-                return Ok(RunTaskReturnV0 {
-                    tasks: vec![],
-                    accounts: vec![],
-                });
-            }
+                    self.user_subscription.status = Status::Failed;
+
+                    emit!(SubscriptionFailedEvent {
+                        subscriber: self.subscriber.key(),
+                        subscription: self.subscription_plan.key()
+                    });
+
+                    // return no task so that recursion ends
+                    return Ok(RunTaskReturnV0 {
+                        tasks: vec![],
+                        accounts: vec![],
+                    });
+                }
+            };
 
-            // improvement: dont hard-code
-            let one_day_later = self.user_subscription.last_exec_ts.checked_add(60).unwrap();
+            self.user_subscription.status = Status::PastDue;
+
+            emit!(SubscriptionRetryScheduledEvent {
+                subscriber: self.subscriber.key(),
+                subscription: self.subscription_plan.key(),
+                failure_count: self.user_subscription.failure_count,
+                next_attempt_ts
+            });
 
-            self.schedule_next_task(one_day_later)
+            self.schedule_next_task(next_attempt_ts)
         } else {
-            self.transfer_tokens()?;
+            // the receipt is paid out of the crank reserve, the charge waits for a top up without it
+            if due > 0 && self.user_subscription.crank_reserve < receipt_rent()? {
+                msg!("crank reserve cannot pay the charge receipt");
 
-            self.user_subscription.failure_count = 0;
+                return self.stop_automation();
+            }
+
+            let fee = if due > 0 {
+                self.transfer_tokens(due, &payees)?
+            } else {
+                0
+            };
 
             let next_exec_ts = self
-                .user_subscription
-                .last_exec_ts
-                .checked_add(self.subscription_plan.interval)
-                .unwrap();
+                .subscription_plan
+                .interval
+                .next_charge_ts(self.user_subscription.last_exec_ts)
+                .ok_or(SubscriptionError::ArithmeticError)?;
 
-            self.user_subscription.last_exec_ts = next_exec_ts;
+            self.user_subscription
+                .record_charge(&quote, next_exec_ts)
+                .ok_or(SubscriptionError::ArithmeticError)?;
+
+            let charge_seq = self
+                .subscription_plan
+                .next_charge_seq()
+                .ok_or(SubscriptionError::ArithmeticError)?;
+            let receipt = if due > 0 {
+                Some(self.write_receipt(charge_seq, due)?)
+            } else {
+                None
+            };
 
             emit!(ChargeEvent {
                 subscriber: self.subscriber.key(),
                 subscription: self.subscription_plan.key(),
-                amount: self.subscription_plan.amount
+                amount: due,
+                fee,
+                tier: quote.tier,
+                charge_seq,
+                usage_units: quote.usage_units,
+                receipt
             });
 
+            self.record_in_ledger(cycle, now, due, ChargeOutcome::Paid, Some(charge_seq));
+
             self.schedule_next_task(next_exec_ts)
         }
     }
 
-    pub fn transfer_tokens(&mut self) -> Result<()> {
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            SUBSCRIBER_VAULT_SEED,
-            self.subscriber.key.as_ref(),
-            &[self.user_subscription.subscriber_vault_bump],
-        ]];
-
-        let ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
-            TransferChecked {
-                from: self.subscriber_vault.to_account_info(),
-                to: self.merchant_ata.to_account_info(),
-                mint: self.mint.to_account_info(),
-                authority: self.subscriber_vault.to_account_info(),
-            },
-            signer_seeds,
-        );
+    // the ledger is optional, without it the history only lives in the emitted events
+    pub fn record_in_ledger(
+        &mut self,
+        cycle: u32,
+        timestamp: i64,
+        amount: u64,
+        outcome: ChargeOutcome,
+        charge_seq: Option<u64>,
+    ) {
+        if let Some(billing_ledger) = self.billing_ledger.as_mut() {
+            billing_ledger.record(LedgerEntry {
+                cycle,
+                timestamp,
+                amount,
+                mint: self.mint.key(),
+                outcome,
+                charge_seq,
+            });
+        }
+    }
+
+    // closes the subscription to the subscriber, this refunds the rent and the crank reserve
+    pub fn close_subscription(
+        &mut self,
+        payees: &[(AccountInfo<'info>, u16)],
+    ) -> Result<RunTaskReturnV0> {
+        msg!("subscription was canceled, closing it");
+
+        self.charge_final_usage(payees)?;
+
+        self.subscription_plan.subscriber_count = self
+            .subscription_plan
+            .subscriber_count
+            .checked_sub(1)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        self.user_subscription
+            .close(self.subscriber.to_account_info())?;
+
+        if let Some(billing_ledger) = &self.billing_ledger {
+            billing_ledger.close(self.subscriber.to_account_info())?;
+        }
+
+        emit!(CancelSubscriptionEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key()
+        });
+
+        // return no task so that recursion ends
+        Ok(RunTaskReturnV0 {
+            tasks: vec![],
+            accounts: vec![],
+        })
+    }
+
+    // bills usage reported before a cancel at period end, best effort as nothing is retried after
+    pub fn charge_final_usage(&mut self, payees: &[(AccountInfo<'info>, u16)]) -> Result<()> {
+        let usage_units = self.user_subscription.usage_units;
+
+        if usage_units == 0 {
+            return Ok(());
+        }
+
+        let amount = self
+            .subscription_plan
+            .metered_amount(0, usage_units, self.user_subscription.spending_cap)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        let due = amount.saturating_sub(self.user_subscription.credit);
+
+        if self.available_balance() < due {
+            msg!("not enough tokens to bill the final usage");
+            return Ok(());
+        }
+
+        let fee = if due > 0 {
+            self.transfer_tokens(due, payees)?
+        } else {
+            0
+        };
+
+        // the account is closed right after, its rent covers the receipt if the reserve does not
+        let charge_seq = self
+            .subscription_plan
+            .next_charge_seq()
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        let receipt = if due > 0 {
+            Some(self.write_receipt(charge_seq, due)?)
+        } else {
+            None
+        };
+
+        emit!(ChargeEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key(),
+            amount: due,
+            fee,
+            tier: self
+                .subscription_plan
+                .current_tier(self.user_subscription.cycles_charged),
+            charge_seq,
+            usage_units,
+            receipt
+        });
+
+        Ok(())
+    }
+
+    // the rent of the receipt is taken from the subscription account
+    pub fn write_receipt(&mut self, charge_seq: u64, amount: u64) -> Result<Pubkey> {
+        let charge_receipt = self.charge_receipt.to_account_info();
+
+        pay_receipt_rent(
+            &mut self.user_subscription,
+            &charge_receipt,
+            receipt_rent()?,
+        )?;
+        create_charge_receipt(
+            &charge_receipt,
+            &self.system_program.to_account_info(),
+            &mut self.user_subscription,
+            charge_seq,
+            amount,
+        )
+    }
+
+    pub fn available_balance(&self) -> u64 {
+        chargeable_balance(
+            self.user_subscription.billing_mode,
+            &self.subscriber_vault,
+            &self.subscriber_ata,
+            &self.subscription_plan.key(),
+        )
+    }
+
+    // returns the protocol fee taken from `amount`
+    pub fn transfer_tokens(
+        &mut self,
+        amount: u64,
+        payees: &[(AccountInfo<'info>, u16)],
+    ) -> Result<u64> {
+        let fee = self
+            .global_state
+            .protocol_fee(amount)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        match self.user_subscription.billing_mode {
+            BillingMode::Vault => {
+                let mint_key = self.mint.key();
+                let signer_seeds: &[&[&[u8]]] = &[&[
+                    SUBSCRIBER_VAULT_SEED,
+                    self.subscriber.key.as_ref(),
+                    mint_key.as_ref(),
+                    &[self.user_subscription.subscriber_vault_bump],
+                ]];
 
-        transfer_checked(ctx, self.subscription_plan.amount, self.mint.decimals)
+                pay_merchant(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.subscriber_vault.to_account_info(),
+                        to: self.merchant_ata.to_account_info(),
+                        mint: self.mint.to_account_info(),
+                        authority: self.subscriber_vault.to_account_info(),
+                    },
+                    self.fee_token_vault.to_account_info(),
+                    payees,
+                    signer_seeds,
+                    amount,
+                    fee,
+                    self.mint.decimals,
+                )?;
+            }
+            BillingMode::Delegated { .. } => {
+                // the plan signs as the approved delegate of the subscriber ata
+                let merchant = self.subscription_plan.merchant;
+                let name_hash = hash(self.subscription_plan.name.as_bytes());
+                let signer_seeds: &[&[&[u8]]] = &[&[
+                    PLAN_SEED,
+                    merchant.as_ref(),
+                    name_hash.as_ref(),
+                    &[self.subscription_plan.bump],
+                ]];
+
+                pay_merchant(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.subscriber_ata.to_account_info(),
+                        to: self.merchant_ata.to_account_info(),
+                        mint: self.mint.to_account_info(),
+                        authority: self.subscription_plan.to_account_info(),
+                    },
+                    self.fee_token_vault.to_account_info(),
+                    payees,
+                    signer_seeds,
+                    amount,
+                    fee,
+                    self.mint.decimals,
+                )?;
+            }
+        }
+
+        Ok(fee)
     }
 
-    // set next task id using introspection, next id is the first element from free tasks
+    // set next task id using introspection, the returned task takes the first of the free task ids
+    // passed to tuktuk's `run_task_v0`
     pub fn set_next_task_id(&mut self) -> Result<()> {
         let index = load_current_index_checked(&self.instructions.to_account_info())? as usize;
         let instruction = load_instruction_at_checked(index, &self.instructions.to_account_info())?;
-        let next_task_id = u16::from_le_bytes(instruction.data[12..14].try_into().unwrap());
+
+        require_keys_eq!(
+            instruction.program_id,
+            tuktuk_program::tuktuk::ID,
+            SubscriptionError::InvalidTaskId
+        );
+
+        let mut data = instruction
+            .data
+            .strip_prefix(RunTaskV0::DISCRIMINATOR)
+            .ok_or(SubscriptionError::InvalidTaskId)?;
+        let run_task = RunTaskV0::deserialize(&mut data)
+            .map_err(|_| error!(SubscriptionError::InvalidTaskId))?;
+
+        let next_task_id = *run_task
+            .args
+            .free_task_ids
+            .first()
+            .ok_or(SubscriptionError::InvalidTaskId)?;
 
         self.user_subscription.next_task_id = next_task_id;
         Ok(())
     }
 
     pub fn schedule_next_task(&mut self, timestamp: i64) -> Result<RunTaskReturnV0> {
-        let instructions = vec![Instruction {
-            program_id: crate::ID,
-            accounts: crate::accounts::ChargeUserRecurring {
+        let crank_reward = self.global_state.crank_reward;
+
+        if self.user_subscription.crank_reserve < crank_reward {
+            msg!("crank reserve is depleted, stopping automation");
+
+            return self.stop_automation();
+        }
+
+        // tuktuk pays the crank reward of returned tasks out of the task queue, refill it from
+        // the reserve held by the subscription account
+        self.user_subscription.crank_reserve -= crank_reward;
+        self.user_subscription.sub_lamports(crank_reward)?;
+        self.task_queue.add_lamports(crank_reward)?;
+
+        let instructions = vec![charge_instruction(
+            crate::accounts::ChargeUserRecurring {
                 subscriber: self.subscriber.key(),
                 merchant: self.merchant.key(),
                 user_subscription: self.user_subscription.key(),
                 subscription_plan: self.subscription_plan.key(),
                 subscriber_vault: self.subscriber_vault.key(),
+                subscriber_ata: self.subscriber_ata.key(),
                 merchant_ata: self.merchant_ata.key(),
+                accepted_mints: self.accepted_mints.key(),
+                global_state: self.global_state.key(),
+                fee_token_vault: self.fee_token_vault.key(),
+                task_queue: self.task_queue.key(),
+                // picks up a ledger created after this task was queued
+                billing_ledger: self
+                    .user_subscription
+                    .billing_ledger(&self.user_subscription.key()),
+                charge_receipt: self
+                    .user_subscription
+                    .next_receipt_address(&self.user_subscription.key()),
                 mint: self.mint.key(),
                 instructions: self.instructions.key(),
                 associated_token_program: self.associated_token_program.key(),
                 token_program: self.token_program.key(),
                 system_program: self.system_program.key(),
-            }
-            .to_account_metas(None),
-            data: crate::instruction::ChargeUserRecurring.data(),
-        }];
+            },
+            &self.subscription_plan.payout_split,
+        )];
 
         let (compiled_tx, _) = compile_transaction(instructions, vec![])?; // signer seeds?
 
@@ -186,11 +596,28 @@ impl<'info> ChargeUserRecurring<'info> {
             tasks: vec![TaskReturnV0 {
                 trigger: TriggerV0::Timestamp(timestamp),
                 transaction: TransactionSourceV0::CompiledV0(compiled_tx),
-                crank_reward: None,
+                crank_reward: Some(crank_reward),
                 free_tasks: 1,
                 description: "payment".to_string(),
             }],
             accounts: vec![],
         })
     }
+
+    // ends the task chain until the reserve is funded again, a pause or a cancellation at period
+    // end is picked up again then
+    pub fn stop_automation(&mut self) -> Result<RunTaskReturnV0> {
+        self.user_subscription.depleted_status = Some(self.user_subscription.status.clone());
+        self.user_subscription.status = Status::ReserveDepleted;
+
+        emit!(CrankReserveDepletedEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key()
+        });
+
+        Ok(RunTaskReturnV0 {
+            tasks: vec![],
+            accounts: vec![],
+        })
+    }
 }
@@ -0,0 +1,87 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::SubscriptionError,
+    events::SubscriptionTerminatedEvent,
+    states::{
+        BillingLedger, Status, SubscriptionPlan, TerminationReason, UserSubscription, LEDGER_SEED,
+        SUBSCRIPTION_SEED,
+    },
+    utils::remaining_allowance,
+};
+
+// permissionless cleanup of a failed subscription, the rent and crank reserve go back to the
+// subscriber. Failed subscriptions have no queued task, so there is nothing to dequeue
+#[derive(Accounts)]
+pub struct CloseFailedSubscription<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        address = user_subscription.subscriber @ SubscriptionError::InvalidSubscription
+    )]
+    /// CHECK: via address, receives the rent of the closed accounts
+    pub subscriber: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = subscriber,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), subscription_plan.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.status == Status::Failed @ SubscriptionError::SubscriptionNotFailed
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    #[account(
+        mut,
+        close = subscriber,
+        seeds = [LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = billing_ledger.bump
+    )]
+    pub billing_ledger: Option<Account<'info, BillingLedger>>,
+    #[account(mut)]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    #[account(
+        address = user_subscription.subscriber_ata @ SubscriptionError::InvalidSubscription
+    )]
+    /// CHECK: via address, only read for the allowance left to the plan, may be closed
+    pub subscriber_ata: UncheckedAccount<'info>,
+}
+
+impl<'info> CloseFailedSubscription<'info> {
+    pub fn close_failed_subscription(&mut self) -> Result<()> {
+        require!(
+            self.billing_ledger.is_some() == self.user_subscription.ledger_bump.is_some(),
+            SubscriptionError::MissingBillingLedger
+        );
+
+        // the subscriber may still reactivate until the cycle after the unpaid one would be due
+        let closable_at = self
+            .subscription_plan
+            .interval
+            .next_charge_ts(self.user_subscription.last_exec_ts)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        require!(
+            Clock::get()?.unix_timestamp >= closable_at,
+            SubscriptionError::ReactivationWindowOpen
+        );
+
+        self.subscription_plan.subscriber_count = self
+            .subscription_plan
+            .subscriber_count
+            .checked_sub(1)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        emit!(SubscriptionTerminatedEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key(),
+            terminated_by: self.signer.key(),
+            reason: TerminationReason::PaymentFailed,
+            remaining_allowance: remaining_allowance(
+                &self.subscriber_ata.to_account_info(),
+                &self.subscription_plan.key(),
+            )
+        });
+
+        Ok(())
+    }
+}
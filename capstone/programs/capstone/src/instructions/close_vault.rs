@@ -8,15 +8,16 @@ use anchor_spl::{
     },
 };
 
-use crate::states::{SUBSCRIBER_VAULT_SEED, USDC_PUBKEY};
+use crate::states::SUBSCRIBER_VAULT_SEED;
 
 #[derive(Accounts)]
 pub struct CloseVault<'info> {
     #[account(mut)]
     pub subscriber: Signer<'info>,
 
+    // not checked against the accepted mints, a vault of a mint removed since stays closable. The
+    // vault seeds tie it to the mint
     #[account(
-        address = USDC_PUBKEY,
         mint::token_program = token_program
     )]
     pub mint: InterfaceAccount<'info, Mint>,
@@ -30,7 +31,7 @@ pub struct CloseVault<'info> {
     pub subscriber_ata: InterfaceAccount<'info, TokenAccount>,
     #[account(
         mut,
-        seeds = [SUBSCRIBER_VAULT_SEED, subscriber.key.as_ref()],
+        seeds = [SUBSCRIBER_VAULT_SEED, subscriber.key.as_ref(), mint.key().as_ref()],
         token::mint = mint,
         token::authority = subscriber_vault,
         token::token_program = token_program,
@@ -46,9 +47,11 @@ pub struct CloseVault<'info> {
 
 impl<'info> CloseVault<'info> {
     pub fn close_vault(&mut self, bumps: &CloseVaultBumps) -> Result<()> {
+        let mint_key = self.mint.key();
         let signer_seeds: &[&[&[u8]]] = &[&[
             SUBSCRIBER_VAULT_SEED,
             self.subscriber.key.as_ref(),
+            mint_key.as_ref(),
             &[bumps.subscriber_vault],
         ]];
 
@@ -58,7 +61,7 @@ impl<'info> CloseVault<'info> {
                     self.token_program.to_account_info(),
                     TransferChecked {
                         from: self.subscriber_vault.to_account_info(),
-                        to: self.subscriber.to_account_info(),
+                        to: self.subscriber_ata.to_account_info(),
                         mint: self.mint.to_account_info(),
                         authority: self.subscriber_vault.to_account_info(),
                     },
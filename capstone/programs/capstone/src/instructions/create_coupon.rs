@@ -0,0 +1,83 @@
+use anchor_lang::{prelude::*, solana_program::hash::hash};
+
+use crate::{
+    error::SubscriptionError,
+    events::CouponCreatedEvent,
+    states::{Coupon, Discount, SubscriptionPlan, COUPON_SEED},
+};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CreateCouponArgs {
+    pub code: String,
+    pub discount: Discount,
+    pub duration_cycles: u16,
+    pub max_redemptions: u32,
+    pub expires_at: i64, // 0 for no expiry
+}
+
+#[derive(Accounts)]
+#[instruction(args: CreateCouponArgs)]
+pub struct CreateCoupon<'info> {
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+    #[account(
+        has_one = merchant @ SubscriptionError::InvalidSigner
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    #[account(
+        init,
+        payer = merchant,
+        space = Coupon::DISCRIMINATOR.len() + Coupon::INIT_SPACE,
+        seeds = [COUPON_SEED, subscription_plan.key().as_ref(), {hash(args.code.as_bytes()).as_ref()}],
+        bump
+    )]
+    pub coupon: Account<'info, Coupon>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateCoupon<'info> {
+    pub fn create_coupon(
+        &mut self,
+        args: CreateCouponArgs,
+        bumps: &CreateCouponBumps,
+    ) -> Result<()> {
+        require!(
+            !args.code.is_empty() && args.code.len() <= 32,
+            SubscriptionError::InvalidName
+        );
+        require!(
+            args.discount.is_valid() && args.duration_cycles > 0 && args.max_redemptions > 0,
+            SubscriptionError::InvalidCoupon
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            args.expires_at == 0 || args.expires_at > now,
+            SubscriptionError::InvalidSchedule
+        );
+
+        self.coupon.set_inner(Coupon {
+            subscription_plan: self.subscription_plan.key(),
+            code: args.code,
+            discount: args.discount,
+            duration_cycles: args.duration_cycles,
+            max_redemptions: args.max_redemptions,
+            redemptions: 0,
+            expires_at: args.expires_at,
+            bump: bumps.coupon,
+        });
+
+        emit!(CouponCreatedEvent {
+            merchant: self.merchant.key(),
+            subscription: self.subscription_plan.key(),
+            coupon: self.coupon.key(),
+            discount: args.discount,
+            duration_cycles: args.duration_cycles,
+            max_redemptions: args.max_redemptions,
+            expires_at: args.expires_at
+        });
+
+        Ok(())
+    }
+}
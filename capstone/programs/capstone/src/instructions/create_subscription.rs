@@ -11,7 +11,8 @@ use anchor_spl::{
 use crate::{
     error::SubscriptionError,
     states::{
-        GlobalState, SubscriptionPlan, GLOBAL_STATE_SEED, PLAN_SEED, USDC_PUBKEY, VAULT_SEED,
+        AcceptedMints, BillingInterval, GlobalState, Metering, RetryPolicy, SubscriptionPlan,
+        ACCEPTED_MINTS_SEED, GLOBAL_STATE_SEED, PLAN_SEED, VAULT_SEED,
     },
 };
 
@@ -19,8 +20,17 @@ use crate::{
 pub struct CreateSubscriptionArgs {
     pub name: String,
     pub amount: u64,
-    pub interval: i64,
+    pub interval: BillingInterval,
     pub max_failure_count: u8,
+    pub trial_period: i64,
+    pub intro_amount: u64,
+    pub intro_cycles: u16,
+    pub retry_policy: RetryPolicy,
+    pub grace_period: i64,
+    pub proration_enabled: bool,
+    pub metering: Option<Metering>, // usage billed on top of `amount`, reported by the oracle
+    // subscribers are charged by the merchant's `charge_batch` crank instead of their own tasks
+    pub batch_billing: bool,
 }
 
 #[derive(Accounts)]
@@ -37,9 +47,14 @@ pub struct CreateSubscription<'info> {
     )]
     pub subscription_plan: Account<'info, SubscriptionPlan>,
 
+    #[account(
+        seeds = [ACCEPTED_MINTS_SEED],
+        bump = accepted_mints.bump
+    )]
+    pub accepted_mints: Account<'info, AcceptedMints>,
     #[account(
         mint::token_program = token_program,
-        constraint = mint.key().eq(&USDC_PUBKEY) @ SubscriptionError::MintMismatch // for now only usdc
+        constraint = accepted_mints.contains(&mint.key()) @ SubscriptionError::MintNotAccepted
     )]
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
@@ -60,7 +75,8 @@ pub struct CreateSubscription<'info> {
 
     #[account(
         seeds = [GLOBAL_STATE_SEED],
-        bump = global_state.bump
+        bump = global_state.bump,
+        constraint = !global_state.paused @ SubscriptionError::ProtocolPaused
     )]
     pub global_state: Account<'info, GlobalState>,
 
@@ -78,6 +94,22 @@ impl<'info> CreateSubscription<'info> {
     ) -> Result<()> {
         require!(args.amount > 0, SubscriptionError::InvalidAmount);
         require!(args.name.len() != 0, SubscriptionError::InvalidName);
+        require!(
+            args.interval.is_valid() && args.trial_period >= 0,
+            SubscriptionError::InvalidSchedule
+        );
+        require!(
+            args.intro_cycles == 0 || args.intro_amount > 0,
+            SubscriptionError::InvalidAmount
+        );
+        require!(
+            args.retry_policy.is_valid() && args.grace_period >= 0,
+            SubscriptionError::InvalidRetryPolicy
+        );
+        require!(
+            !matches!(args.metering, Some(metering) if metering.unit_price == 0),
+            SubscriptionError::InvalidAmount
+        );
 
         self.subscription_plan.set_inner(SubscriptionPlan {
             merchant: self.merchant.key(),
@@ -87,7 +119,20 @@ impl<'info> CreateSubscription<'info> {
             amount: args.amount,
             active: true,
             interval: args.interval,
+            trial_period: args.trial_period,
+            intro_amount: args.intro_amount,
+            intro_cycles: args.intro_cycles,
             max_failure_count: args.max_failure_count,
+            retry_policy: args.retry_policy,
+            grace_period: args.grace_period,
+            proration_enabled: args.proration_enabled,
+            subscriber_count: 0,
+            pending_amount: None,
+            pending_amount_effective_ts: 0,
+            charge_seq: 0,
+            metering: args.metering,
+            payout_split: Vec::new(),
+            batch_billing: args.batch_billing,
             bump: bumps.subscription_plan,
         });
 
@@ -0,0 +1,207 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::sysvar::instructions::ID as INSTRUCTIONS_SYSVAR_ID,
+    system_program::{self, Transfer},
+};
+
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+use tuktuk_program::{
+    tuktuk::{cpi::accounts::QueueTaskV0, program::Tuktuk},
+    TaskQueueAuthorityV0, TriggerV0,
+};
+
+use crate::{
+    error::SubscriptionError,
+    events::CrankReserveFundedEvent,
+    states::{
+        AcceptedMints, GlobalState, Status, SubscriptionPlan, UserSubscription,
+        ACCEPTED_MINTS_SEED, FEE_TOKEN_VAULT_SEED, GLOBAL_STATE_SEED, QUEUE_AUTHORITY_SEED,
+        SUBSCRIBER_VAULT_SEED, SUBSCRIPTION_SEED,
+    },
+    utils::{charge_instruction, min_crank_reserve, queue_charge_task},
+};
+
+#[derive(Accounts)]
+#[instruction(amount: u64, task_id: u16)]
+pub struct FundCrankReserve<'info> {
+    #[account(mut)]
+    pub subscriber: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), subscription_plan.key().as_ref()],
+        bump = user_subscription.bump
+    )]
+    pub user_subscription: Box<Account<'info, UserSubscription>>,
+    pub subscription_plan: Box<Account<'info, SubscriptionPlan>>,
+    #[account(
+        seeds = [ACCEPTED_MINTS_SEED],
+        bump = accepted_mints.bump
+    )]
+    pub accepted_mints: Box<Account<'info, AcceptedMints>>,
+    #[account(
+        address = subscription_plan.mint @ SubscriptionError::MintMismatch,
+        mint::token_program = token_program
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        seeds = [SUBSCRIBER_VAULT_SEED, subscriber.key.as_ref(), mint.key().as_ref()],
+        bump = user_subscription.subscriber_vault_bump
+    )]
+    pub subscriber_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [FEE_TOKEN_VAULT_SEED, mint.key().as_ref()],
+        bump
+    )]
+    /// CHECK: via seeds, only passed on to the charge task
+    pub fee_token_vault: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump
+    )]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    // TUKTUK ACCOUNTS
+    #[account(
+        mut,
+        address = user_subscription.task_queue @ SubscriptionError::InvalidTaskQueue
+    )]
+    /// CHECK: via address
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [QUEUE_AUTHORITY_SEED],
+        bump = global_state.queue_authority_bump
+    )]
+    /// CHECK: via seeds
+    pub queue_authority: UncheckedAccount<'info>,
+    #[account(
+      seeds = [b"task_queue_authority", task_queue.key().as_ref(), queue_authority.key().as_ref()],
+      bump = task_queue_authority.bump_seed,
+      seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    pub task_queue_authority: Box<Account<'info, TaskQueueAuthorityV0>>,
+    #[account(
+        mut,
+        seeds = [b"task", task_queue.key().as_ref(), task_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    /// CHECK: via seeds, initialized in CPI, only used when automation was stopped
+    pub task: AccountInfo<'info>,
+
+    // PROGRAMS
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub tuktuk_program: Program<'info, Tuktuk>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> FundCrankReserve<'info> {
+    // tops up the crank reserve, a subscription stopped by an empty reserve is queued again at its
+    // due date
+    pub fn fund_crank_reserve(&mut self, amount: u64, task_id: u16) -> Result<()> {
+        require!(amount > 0, SubscriptionError::InvalidAmount);
+
+        let ctx = CpiContext::new(
+            self.system_program.to_account_info(),
+            Transfer {
+                from: self.subscriber.to_account_info(),
+                to: self.user_subscription.to_account_info(),
+            },
+        );
+
+        system_program::transfer(ctx, amount)?;
+
+        self.user_subscription.crank_reserve = self
+            .user_subscription
+            .crank_reserve
+            .checked_add(amount)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        if self.user_subscription.status == Status::ReserveDepleted {
+            self.restart(task_id)?;
+        }
+
+        emit!(CrankReserveFundedEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key(),
+            amount,
+            crank_reserve: self.user_subscription.crank_reserve
+        });
+
+        Ok(())
+    }
+
+    // the first task is paid by the subscriber, the reserve has to cover the ones after it and the
+    // receipt of the charge it runs
+    pub fn restart(&mut self, task_id: u16) -> Result<()> {
+        require!(
+            self.user_subscription.crank_reserve
+                >= min_crank_reserve(self.global_state.crank_reward)?,
+            SubscriptionError::InsufficientCrankReserve
+        );
+
+        // unpaid retries, a pause or a cancellation at period end pick up where they stopped
+        self.user_subscription.status = self
+            .user_subscription
+            .depleted_status
+            .take()
+            .unwrap_or(Status::Active);
+        self.user_subscription.next_task_id = task_id;
+
+        // a paused subscription is checked again once it resumes
+        let last_exec_ts = self.user_subscription.last_exec_ts;
+        let trigger_ts = self
+            .user_subscription
+            .resume_at
+            .map_or(last_exec_ts, |ts| ts.max(last_exec_ts));
+
+        queue_charge_task(
+            self.tuktuk_program.to_account_info(),
+            QueueTaskV0 {
+                payer: self.subscriber.to_account_info(),
+                queue_authority: self.queue_authority.to_account_info(),
+                task_queue: self.task_queue.to_account_info(),
+                task_queue_authority: self.task_queue_authority.to_account_info(),
+                task: self.task.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            self.global_state.queue_authority_bump,
+            task_id,
+            TriggerV0::Timestamp(trigger_ts),
+            self.global_state.crank_reward,
+            charge_instruction(
+                crate::accounts::ChargeUserRecurring {
+                    subscriber: self.subscriber.key(),
+                    merchant: self.subscription_plan.merchant.key(),
+                    user_subscription: self.user_subscription.key(),
+                    subscription_plan: self.subscription_plan.key(),
+                    merchant_ata: self.subscription_plan.merchant_ata.key(),
+                    accepted_mints: self.accepted_mints.key(),
+                    global_state: self.global_state.key(),
+                    fee_token_vault: self.fee_token_vault.key(),
+                    task_queue: self.task_queue.key(),
+                    billing_ledger: self
+                        .user_subscription
+                        .billing_ledger(&self.user_subscription.key()),
+                    charge_receipt: self
+                        .user_subscription
+                        .next_receipt_address(&self.user_subscription.key()),
+                    mint: self.mint.key(),
+                    subscriber_vault: self.subscriber_vault.key(),
+                    subscriber_ata: self.user_subscription.subscriber_ata,
+                    instructions: INSTRUCTIONS_SYSVAR_ID.key(),
+                    associated_token_program: self.associated_token_program.key(),
+                    token_program: self.token_program.key(),
+                    system_program: self.system_program.key(),
+                },
+                &self.subscription_plan.payout_split,
+            ),
+        )
+    }
+}
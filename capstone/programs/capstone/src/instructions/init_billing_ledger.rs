@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+use crate::states::{BillingLedger, UserSubscription, LEDGER_SEED, SUBSCRIPTION_SEED};
+
+#[derive(Accounts)]
+pub struct InitBillingLedger<'info> {
+    #[account(mut)]
+    pub subscriber: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), user_subscription.subscription.key().as_ref()],
+        bump = user_subscription.bump
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    #[account(
+        init,
+        payer = subscriber,
+        space = BillingLedger::DISCRIMINATOR.len() + BillingLedger::INIT_SPACE,
+        seeds = [LEDGER_SEED, user_subscription.key().as_ref()],
+        bump
+    )]
+    pub billing_ledger: Account<'info, BillingLedger>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitBillingLedger<'info> {
+    // the queued task does not know about the ledger yet, recording starts with the task after it
+    pub fn init_billing_ledger(&mut self, bumps: &InitBillingLedgerBumps) -> Result<()> {
+        self.billing_ledger.set_inner(BillingLedger {
+            user_subscription: self.user_subscription.key(),
+            head: 0,
+            entries: vec![],
+            bump: bumps.billing_ledger,
+        });
+
+        self.user_subscription.ledger_bump = Some(bumps.billing_ledger);
+
+        Ok(())
+    }
+}
@@ -4,7 +4,10 @@ use tuktuk_program::tuktuk::program::Tuktuk;
 use crate::{
     error::SubscriptionError,
     program::Capstone,
-    states::{GlobalState, FEES, GLOBAL_STATE_SEED, QUEUE_AUTHORITY_SEED},
+    states::{
+        AcceptedMints, GlobalState, ACCEPTED_MINTS_SEED, CRANK_REWARD, FEES, GLOBAL_STATE_SEED,
+        QUEUE_AUTHORITY_SEED,
+    },
 };
 
 #[derive(Accounts)]
@@ -19,6 +22,14 @@ pub struct Initialize<'info> {
         bump
     )]
     pub global_state: Account<'info, GlobalState>,
+    #[account(
+        init,
+        payer = signer,
+        seeds = [ACCEPTED_MINTS_SEED],
+        space = AcceptedMints::DISCRIMINATOR.len() + AcceptedMints::INIT_SPACE,
+        bump
+    )]
+    pub accepted_mints: Account<'info, AcceptedMints>,
     #[account(
         seeds = [QUEUE_AUTHORITY_SEED],
         bump
@@ -40,13 +51,24 @@ pub struct Initialize<'info> {
 impl<'info> Initialize<'info> {
     pub fn initialize(&mut self, bumps: &InitializeBumps) -> Result<()> {
         self.global_state.set_inner(GlobalState {
+            admin: self.signer.key(),
+            pending_admin: None,
             task_queue: self.task_queue.key(),
             queue_authority: self.queue_authority.key(),
             fees: FEES,
+            protocol_fee_bps: 0,
+            crank_reward: CRANK_REWARD,
+            paused: false,
             queue_authority_bump: bumps.queue_authority,
             bump: bumps.global_state,
         });
 
+        // mints are added later by the admin, see `add_accepted_mint`
+        self.accepted_mints.set_inner(AcceptedMints {
+            mints: vec![],
+            bump: bumps.accepted_mints,
+        });
+
         Ok(())
     }
 }
@@ -13,5 +13,71 @@ pub use charge_user_recurring::*;
 pub mod cancel_subscription;
 pub use cancel_subscription::*;
 
+pub mod terminate_subscription;
+pub use terminate_subscription::*;
+
+pub mod close_failed_subscription;
+pub use close_failed_subscription::*;
+
 pub mod close_vault;
 pub use close_vault::*;
+
+pub mod update_accepted_mints;
+pub use update_accepted_mints::*;
+
+pub mod update_plan;
+pub use update_plan::*;
+
+pub mod retire_plan;
+pub use retire_plan::*;
+
+pub mod reactivate_subscription;
+pub use reactivate_subscription::*;
+
+pub mod pause_subscription;
+pub use pause_subscription::*;
+
+pub mod resume_subscription;
+pub use resume_subscription::*;
+
+pub mod change_plan;
+pub use change_plan::*;
+
+pub mod update_config;
+pub use update_config::*;
+
+pub mod withdraw_fees;
+pub use withdraw_fees::*;
+
+pub mod transfer_admin;
+pub use transfer_admin::*;
+
+pub mod update_task_queue;
+pub use update_task_queue::*;
+
+pub mod fund_crank_reserve;
+pub use fund_crank_reserve::*;
+
+pub mod cancel_at_period_end;
+pub use cancel_at_period_end::*;
+
+pub mod verify_entitlement;
+pub use verify_entitlement::*;
+
+pub mod refund_charge;
+pub use refund_charge::*;
+
+pub mod init_billing_ledger;
+pub use init_billing_ledger::*;
+
+pub mod report_usage;
+pub use report_usage::*;
+
+pub mod set_spending_cap;
+pub use set_spending_cap::*;
+
+pub mod create_coupon;
+pub use create_coupon::*;
+
+pub mod charge_batch;
+pub use charge_batch::*;
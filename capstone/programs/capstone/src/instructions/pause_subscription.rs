@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::SubscriptionError,
+    events::SubscriptionPausedEvent,
+    states::{Status, UserSubscription, SUBSCRIPTION_SEED},
+};
+
+#[derive(Accounts)]
+pub struct PauseSubscription<'info> {
+    pub subscriber: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), user_subscription.subscription.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.status == Status::Active @ SubscriptionError::SubscriptionNotActive
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+}
+
+impl<'info> PauseSubscription<'info> {
+    // the queued task stays, it skips cycles until `resume_at` or a `resume_subscription` call,
+    // on batch billed plans `charge_batch` does the same
+    pub fn pause_subscription(&mut self, resume_at: Option<i64>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+
+        if let Some(ts) = resume_at {
+            require!(ts > now, SubscriptionError::InvalidSchedule);
+        }
+
+        self.user_subscription.status = Status::Paused;
+        self.user_subscription.resume_at = resume_at;
+
+        emit!(SubscriptionPausedEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.user_subscription.subscription.key(),
+            resume_at
+        });
+
+        Ok(())
+    }
+}
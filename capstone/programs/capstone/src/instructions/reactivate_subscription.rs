@@ -0,0 +1,416 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::sysvar::instructions::ID as INSTRUCTIONS_SYSVAR_ID,
+    system_program::{self, Transfer},
+};
+
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        transfer_checked, ApproveChecked, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+use tuktuk_program::{
+    tuktuk::{cpi::accounts::QueueTaskV0, program::Tuktuk},
+    TaskQueueAuthorityV0, TriggerV0,
+};
+
+use crate::{
+    error::SubscriptionError,
+    events::{ChargeEvent, SubscriptionReactivatedEvent},
+    states::{
+        AcceptedMints, BillingLedger, BillingMode, ChargeOutcome, GlobalState, LedgerEntry, Status,
+        SubscriptionPlan, UserSubscription, ACCEPTED_MINTS_SEED, FEE_TOKEN_VAULT_SEED,
+        GLOBAL_STATE_SEED, LEDGER_SEED, QUEUE_AUTHORITY_SEED, SUBSCRIBER_VAULT_SEED,
+        SUBSCRIPTION_SEED,
+    },
+    utils::{
+        approve_plan, charge_instruction, create_charge_receipt, min_crank_reserve, pay_merchant,
+        payout_accounts, queue_charge_task, receipt_rent,
+    },
+};
+
+#[derive(Accounts)]
+#[instruction(task_id: u16)]
+pub struct ReactivateSubscription<'info> {
+    #[account(mut)]
+    pub subscriber: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), subscription_plan.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = user_subscription.status == Status::Failed @ SubscriptionError::SubscriptionNotFailed
+    )]
+    pub user_subscription: Box<Account<'info, UserSubscription>>,
+    #[account(
+        mut,
+        constraint = subscription_plan.active @ SubscriptionError::InactivePlan
+    )]
+    pub subscription_plan: Box<Account<'info, SubscriptionPlan>>,
+    #[account(
+        seeds = [ACCEPTED_MINTS_SEED],
+        bump = accepted_mints.bump
+    )]
+    pub accepted_mints: Box<Account<'info, AcceptedMints>>,
+    #[account(
+        address = subscription_plan.mint @ SubscriptionError::MintMismatch,
+        mint::token_program = token_program,
+        constraint = accepted_mints.contains(&mint.key()) @ SubscriptionError::MintNotAccepted
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = subscriber,
+        associated_token::token_program = token_program
+    )]
+    pub subscriber_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [SUBSCRIBER_VAULT_SEED, subscriber.key.as_ref(), mint.key().as_ref()],
+        token::mint = mint,
+        token::authority = subscriber_vault,
+        token::token_program = token_program,
+        bump = user_subscription.subscriber_vault_bump
+    )]
+    pub subscriber_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        address = subscription_plan.merchant_ata
+    )]
+    pub merchant_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [FEE_TOKEN_VAULT_SEED, mint.key().as_ref()],
+        token::mint = mint,
+        token::authority = fee_token_vault,
+        token::token_program = token_program,
+        bump
+    )]
+    pub fee_token_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+    #[account(mut)]
+    /// CHECK: receipt of the outstanding cycle, checked against the subscription when written
+    pub charge_receipt: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = billing_ledger.bump
+    )]
+    pub billing_ledger: Option<Box<Account<'info, BillingLedger>>>,
+
+    #[account(
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump
+    )]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    // TUKTUK ACCOUNTS
+    #[account(
+        mut,
+        address = global_state.task_queue @ SubscriptionError::InvalidTaskQueue
+    )]
+    /// CHECK: via address
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [QUEUE_AUTHORITY_SEED],
+        bump = global_state.queue_authority_bump
+    )]
+    /// CHECK: via seeds
+    pub queue_authority: UncheckedAccount<'info>,
+    #[account(
+      seeds = [b"task_queue_authority", task_queue.key().as_ref(), queue_authority.key().as_ref()],
+      bump = task_queue_authority.bump_seed,
+      seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    pub task_queue_authority: Box<Account<'info, TaskQueueAuthorityV0>>,
+    #[account(
+        mut,
+        seeds = [b"task", task_queue.key().as_ref(), task_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    /// CHECK: via seeds, initialized in CPI
+    pub task: AccountInfo<'info>,
+
+    // PROGRAMS
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub tuktuk_program: Program<'info, Tuktuk>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ReactivateSubscription<'info> {
+    // charges the outstanding cycle right away and starts a new task chain from now
+    pub fn reactivate_subscription(
+        &mut self,
+        task_id: u16,
+        remaining_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let payees = payout_accounts(&self.subscription_plan.payout_split, remaining_accounts)
+            .ok_or(SubscriptionError::InvalidPayoutSplit)?;
+        let now = Clock::get()?.unix_timestamp;
+
+        require!(
+            self.billing_ledger.is_some() == self.user_subscription.ledger_bump.is_some(),
+            SubscriptionError::MissingBillingLedger
+        );
+
+        // the first task is paid by the subscriber, the reserve has to cover the ones after it
+        require!(
+            self.user_subscription.crank_reserve
+                >= min_crank_reserve(self.global_state.crank_reward)?,
+            SubscriptionError::InsufficientCrankReserve
+        );
+
+        // usage reported before the subscription failed is billed with the outstanding cycle
+        let quote = self
+            .user_subscription
+            .quote(&self.subscription_plan, now)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        let due = quote.due;
+
+        let fee = if due > 0 {
+            self.top_up_vault(due)?;
+            self.transfer_tokens(due, &payees)?
+        } else {
+            0
+        };
+
+        self.approve()?;
+
+        let cycle = self.user_subscription.cycles_charged;
+        let next_exec_ts = self
+            .subscription_plan
+            .interval
+            .next_charge_ts(now)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        self.user_subscription
+            .record_charge(&quote, next_exec_ts)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        self.user_subscription.next_task_id = task_id;
+        self.user_subscription.task_queue = self.task_queue.key();
+
+        let charge_seq = self
+            .subscription_plan
+            .next_charge_seq()
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        let receipt = if due > 0 {
+            Some(self.write_receipt(charge_seq, due)?)
+        } else {
+            None
+        };
+        self.record_in_ledger(cycle, now, due, charge_seq);
+
+        // queued after the receipt so the task writes the next one, batch billed plans pick the
+        // subscription up again with their next `charge_batch`
+        if !self.subscription_plan.batch_billing {
+            self.schedule(task_id, next_exec_ts)?;
+        }
+
+        emit!(ChargeEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key(),
+            amount: due,
+            fee,
+            tier: quote.tier,
+            charge_seq,
+            usage_units: quote.usage_units,
+            receipt
+        });
+
+        emit!(SubscriptionReactivatedEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key(),
+            amount: due,
+            next_exec_ts
+        });
+
+        Ok(())
+    }
+
+    // the outstanding cycle is the charge the subscription failed on, paid now
+    pub fn record_in_ledger(&mut self, cycle: u32, timestamp: i64, amount: u64, charge_seq: u64) {
+        if let Some(billing_ledger) = self.billing_ledger.as_mut() {
+            billing_ledger.record(LedgerEntry {
+                cycle,
+                timestamp,
+                amount,
+                mint: self.mint.key(),
+                outcome: ChargeOutcome::Paid,
+                charge_seq: Some(charge_seq),
+            });
+        }
+    }
+
+    // the subscriber pays the rent of the receipt
+    pub fn write_receipt(&mut self, charge_seq: u64, amount: u64) -> Result<Pubkey> {
+        let ctx = CpiContext::new(
+            self.system_program.to_account_info(),
+            Transfer {
+                from: self.subscriber.to_account_info(),
+                to: self.charge_receipt.to_account_info(),
+            },
+        );
+
+        system_program::transfer(ctx, receipt_rent()?)?;
+
+        create_charge_receipt(
+            &self.charge_receipt.to_account_info(),
+            &self.system_program.to_account_info(),
+            &mut self.user_subscription,
+            charge_seq,
+            amount,
+        )
+    }
+
+    // only moves the missing difference from the subscriber ata
+    pub fn top_up_vault(&mut self, amount: u64) -> Result<()> {
+        // delegated subscriptions are paid straight from the subscriber ata
+        if self.user_subscription.billing_mode != BillingMode::Vault {
+            return Ok(());
+        }
+
+        let missing = amount.saturating_sub(self.subscriber_vault.amount);
+
+        if missing == 0 {
+            return Ok(());
+        }
+
+        let ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: self.subscriber_ata.to_account_info(),
+                to: self.subscriber_vault.to_account_info(),
+                mint: self.mint.to_account_info(),
+                authority: self.subscriber.to_account_info(),
+            },
+        );
+
+        transfer_checked(ctx, missing, self.mint.decimals)
+    }
+
+    // renews the allowance of a delegated subscription for the plan it is billed on from now
+    pub fn approve(&mut self) -> Result<()> {
+        let BillingMode::Delegated { approved_cycles } = self.user_subscription.billing_mode else {
+            return Ok(());
+        };
+
+        let allowance = self
+            .subscription_plan
+            .allowance(approved_cycles, self.user_subscription.spending_cap)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        approve_plan(
+            self.token_program.to_account_info(),
+            ApproveChecked {
+                to: self.subscriber_ata.to_account_info(),
+                mint: self.mint.to_account_info(),
+                delegate: self.subscription_plan.to_account_info(),
+                authority: self.subscriber.to_account_info(),
+            },
+            &self.subscriber_ata,
+            None,
+            allowance,
+            self.mint.decimals,
+        )
+    }
+
+    // returns the protocol fee taken from `amount`
+    pub fn transfer_tokens(
+        &mut self,
+        amount: u64,
+        payees: &[(AccountInfo<'info>, u16)],
+    ) -> Result<u64> {
+        let fee = self
+            .global_state
+            .protocol_fee(amount)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        let mint_key = self.mint.key();
+        let vault_seeds: &[&[&[u8]]] = &[&[
+            SUBSCRIBER_VAULT_SEED,
+            self.subscriber.key.as_ref(),
+            mint_key.as_ref(),
+            &[self.user_subscription.subscriber_vault_bump],
+        ]];
+
+        // the subscriber signs this instruction, so delegated subscriptions need no approval here
+        let (from, authority, signer_seeds) = match self.user_subscription.billing_mode {
+            BillingMode::Vault => (
+                self.subscriber_vault.to_account_info(),
+                self.subscriber_vault.to_account_info(),
+                vault_seeds,
+            ),
+            BillingMode::Delegated { .. } => (
+                self.subscriber_ata.to_account_info(),
+                self.subscriber.to_account_info(),
+                &[] as &[&[&[u8]]],
+            ),
+        };
+
+        pay_merchant(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from,
+                to: self.merchant_ata.to_account_info(),
+                mint: self.mint.to_account_info(),
+                authority,
+            },
+            self.fee_token_vault.to_account_info(),
+            payees,
+            signer_seeds,
+            amount,
+            fee,
+            self.mint.decimals,
+        )?;
+
+        Ok(fee)
+    }
+
+    pub fn schedule(&mut self, task_id: u16, timestamp: i64) -> Result<()> {
+        queue_charge_task(
+            self.tuktuk_program.to_account_info(),
+            QueueTaskV0 {
+                payer: self.subscriber.to_account_info(),
+                queue_authority: self.queue_authority.to_account_info(),
+                task_queue: self.task_queue.to_account_info(),
+                task_queue_authority: self.task_queue_authority.to_account_info(),
+                task: self.task.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+            },
+            self.global_state.queue_authority_bump,
+            task_id,
+            TriggerV0::Timestamp(timestamp),
+            self.global_state.crank_reward,
+            charge_instruction(
+                crate::accounts::ChargeUserRecurring {
+                    subscriber: self.subscriber.key(),
+                    merchant: self.subscription_plan.merchant.key(),
+                    user_subscription: self.user_subscription.key(),
+                    subscription_plan: self.subscription_plan.key(),
+                    merchant_ata: self.merchant_ata.key(),
+                    accepted_mints: self.accepted_mints.key(),
+                    global_state: self.global_state.key(),
+                    fee_token_vault: self.fee_token_vault.key(),
+                    task_queue: self.task_queue.key(),
+                    billing_ledger: self
+                        .user_subscription
+                        .billing_ledger(&self.user_subscription.key()),
+                    charge_receipt: self
+                        .user_subscription
+                        .next_receipt_address(&self.user_subscription.key()),
+                    mint: self.mint.key(),
+                    subscriber_vault: self.subscriber_vault.key(),
+                    subscriber_ata: self.subscriber_ata.key(),
+                    instructions: INSTRUCTIONS_SYSVAR_ID.key(),
+                    associated_token_program: self.associated_token_program.key(),
+                    token_program: self.token_program.key(),
+                    system_program: self.system_program.key(),
+                },
+                &self.subscription_plan.payout_split,
+            ),
+        )
+    }
+}
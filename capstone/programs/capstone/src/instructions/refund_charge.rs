@@ -0,0 +1,104 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+use crate::{
+    error::SubscriptionError,
+    events::RefundEvent,
+    states::{ChargeReceipt, SubscriptionPlan, SUBSCRIBER_VAULT_SEED},
+};
+
+#[derive(Accounts)]
+pub struct RefundCharge<'info> {
+    pub merchant: Signer<'info>,
+    #[account(
+        has_one = merchant @ SubscriptionError::InvalidSigner
+    )]
+    pub subscription_plan: Box<Account<'info, SubscriptionPlan>>,
+    // the subscription itself is not needed, it may be closed by now
+    #[account(
+        mut,
+        has_one = subscription_plan @ SubscriptionError::InvalidReceipt
+    )]
+    pub charge_receipt: Box<Account<'info, ChargeReceipt>>,
+    #[account(
+        address = subscription_plan.mint @ SubscriptionError::MintMismatch,
+        mint::token_program = token_program
+    )]
+    pub mint: Box<InterfaceAccount<'info, Mint>>,
+    #[account(
+        mut,
+        address = subscription_plan.merchant_ata
+    )]
+    pub merchant_ata: Box<InterfaceAccount<'info, TokenAccount>>,
+    // the subscriber vault or the token account the charge was taken from
+    #[account(
+        mut,
+        token::mint = mint,
+        token::token_program = token_program
+    )]
+    pub destination: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    // PROGRAMS
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> RefundCharge<'info> {
+    // paid from the merchant ata, the protocol fee of the refunded charge is not returned. A
+    // charge can be refunded in several parts up to what it took
+    pub fn refund_charge(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, SubscriptionError::InvalidAmount);
+
+        let receipt = &self.charge_receipt;
+        let refunded = receipt
+            .refunded
+            .checked_add(amount)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+        require!(
+            refunded <= receipt.amount,
+            SubscriptionError::RefundExceedsCharge
+        );
+
+        let (subscriber_vault, _) = Pubkey::find_program_address(
+            &[
+                SUBSCRIBER_VAULT_SEED,
+                receipt.subscriber.as_ref(),
+                self.mint.key().as_ref(),
+            ],
+            &crate::ID,
+        );
+        let to_vault = self.destination.key() == subscriber_vault;
+        require!(
+            to_vault || self.destination.key() == receipt.subscriber_ata,
+            SubscriptionError::InvalidRefundDestination
+        );
+
+        let ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            TransferChecked {
+                from: self.merchant_ata.to_account_info(),
+                to: self.destination.to_account_info(),
+                mint: self.mint.to_account_info(),
+                authority: self.merchant.to_account_info(),
+            },
+        );
+
+        transfer_checked(ctx, amount, self.mint.decimals)?;
+
+        self.charge_receipt.refunded = refunded;
+
+        emit!(RefundEvent {
+            merchant: self.merchant.key(),
+            subscriber: self.charge_receipt.subscriber,
+            subscription: self.subscription_plan.key(),
+            receipt: self.charge_receipt.key(),
+            charge_seq: self.charge_receipt.charge_seq,
+            amount,
+            refunded,
+            to_vault
+        });
+
+        Ok(())
+    }
+}
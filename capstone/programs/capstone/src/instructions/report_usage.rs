@@ -0,0 +1,64 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::SubscriptionError,
+    events::UsageReportedEvent,
+    states::{Status, SubscriptionPlan, UserSubscription, SUBSCRIPTION_SEED},
+};
+
+#[derive(Accounts)]
+pub struct ReportUsage<'info> {
+    pub oracle: Signer<'info>,
+    #[account(
+        constraint = subscription_plan.metering.is_some_and(|metering| metering.oracle == oracle.key()) @ SubscriptionError::InvalidSigner
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    #[account(
+        mut,
+        seeds = [SUBSCRIPTION_SEED, user_subscription.subscriber.as_ref(), subscription_plan.key().as_ref()],
+        bump = user_subscription.bump,
+        constraint = matches!(user_subscription.status, Status::Active | Status::PastDue) @ SubscriptionError::SubscriptionNotActive
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+}
+
+impl<'info> ReportUsage<'info> {
+    // usage is billed in arrears, together with the base fee of the next charge
+    pub fn report_usage(&mut self, units: u64) -> Result<()> {
+        require!(units > 0, SubscriptionError::InvalidAmount);
+
+        let usage_units = self
+            .user_subscription
+            .usage_units
+            .checked_add(units)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        // the next charge has to stay within what the subscriber approved
+        if let Some(cap) = self.user_subscription.spending_cap {
+            let (base, _) = self.subscription_plan.charge_amount(
+                self.user_subscription.cycles_charged,
+                self.user_subscription.last_exec_ts,
+            );
+            let total = self
+                .subscription_plan
+                .metered_amount(base, usage_units, None)
+                .ok_or(SubscriptionError::ArithmeticError)?;
+
+            require!(total <= cap, SubscriptionError::SpendingCapExceeded);
+        }
+
+        self.user_subscription.usage_units = usage_units;
+
+        emit!(UsageReportedEvent {
+            oracle: self.oracle.key(),
+            subscriber: self.user_subscription.subscriber,
+            subscription: self.subscription_plan.key(),
+            cycle: self.user_subscription.cycles_charged,
+            units,
+            usage_units,
+            timestamp: Clock::get()?.unix_timestamp
+        });
+
+        Ok(())
+    }
+}
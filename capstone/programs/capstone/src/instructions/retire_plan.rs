@@ -0,0 +1,27 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::SubscriptionError, events::PlanRetiredEvent, states::SubscriptionPlan};
+
+#[derive(Accounts)]
+pub struct RetirePlan<'info> {
+    #[account(mut)]
+    pub merchant: Signer<'info>,
+    #[account(
+        mut,
+        close = merchant,
+        has_one = merchant @ SubscriptionError::InvalidSigner,
+        constraint = subscription_plan.subscriber_count == 0 @ SubscriptionError::PlanHasSubscribers
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+}
+
+impl<'info> RetirePlan<'info> {
+    pub fn retire_plan(&mut self) -> Result<()> {
+        emit!(PlanRetiredEvent {
+            merchant: self.merchant.key(),
+            subscription: self.subscription_plan.key()
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::SubscriptionError,
+    events::SpendingCapUpdatedEvent,
+    states::{SubscriptionPlan, UserSubscription, SUBSCRIPTION_SEED},
+};
+
+#[derive(Accounts)]
+pub struct SetSpendingCap<'info> {
+    pub subscriber: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), user_subscription.subscription.key().as_ref()],
+        bump = user_subscription.bump
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    #[account(
+        address = user_subscription.subscription @ SubscriptionError::InvalidSubscription
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+}
+
+impl<'info> SetSpendingCap<'info> {
+    // a delegated allowance is not changed here, it is renewed on the next reactivation or plan change
+    pub fn set_spending_cap(&mut self, spending_cap: Option<u64>) -> Result<()> {
+        let (base, _) = self.subscription_plan.charge_amount(
+            self.user_subscription.cycles_charged,
+            self.user_subscription.last_exec_ts,
+        );
+        let owed = self
+            .subscription_plan
+            .metered_amount(base, self.user_subscription.usage_units, None)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        match spending_cap {
+            Some(cap) => require!(cap >= owed, SubscriptionError::InvalidSpendingCap),
+            None => require!(
+                self.subscription_plan.metering.is_none(),
+                SubscriptionError::InvalidSpendingCap
+            ),
+        }
+
+        self.user_subscription.spending_cap = spending_cap;
+
+        emit!(SpendingCapUpdatedEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key(),
+            spending_cap
+        });
+
+        Ok(())
+    }
+}
@@ -1,35 +1,41 @@
 use anchor_lang::{
     prelude::*,
-    solana_program::{
-        instruction::Instruction, sysvar::instructions::ID as INSTRUCTIONS_SYSVAR_ID,
-    },
-    InstructionData,
+    solana_program::sysvar::instructions::ID as INSTRUCTIONS_SYSVAR_ID,
+    system_program::{self, Transfer},
 };
 
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+    token_interface::{
+        transfer_checked, ApproveChecked, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
 use tuktuk_program::{
-    compile_transaction,
-    tuktuk::{
-        cpi::{accounts::QueueTaskV0, queue_task_v0},
-        program::Tuktuk,
-    },
-    types::QueueTaskArgsV0,
-    TaskQueueAuthorityV0, TransactionSourceV0, TriggerV0,
+    tuktuk::{cpi::accounts::QueueTaskV0, program::Tuktuk},
+    TaskQueueAuthorityV0, TriggerV0,
 };
 
 use crate::{
     error::SubscriptionError,
-    events::SubscribeEvent,
+    events::{CouponRedeemedEvent, SubscribeEvent},
     states::{
-        GlobalState, Status, SubscriptionPlan, UserSubscription, GLOBAL_STATE_SEED,
+        AcceptedMints, BillingMode, Coupon, Discount, GlobalState, Status, SubscriptionPlan,
+        UserSubscription, ACCEPTED_MINTS_SEED, FEE_TOKEN_VAULT_SEED, GLOBAL_STATE_SEED,
         QUEUE_AUTHORITY_SEED, SUBSCRIBER_VAULT_SEED, SUBSCRIPTION_SEED,
     },
+    utils::{approve_plan, charge_instruction, min_crank_reserve, queue_charge_task},
 };
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct SubscribeArgs {
+    pub crank_reserve: u64, // lamports paying the crank rewards and receipts of later charges
+    pub task_id: u16,       // free id of the task queue, see `nextAvailableTaskIds` in tuktuk-sdk
+    pub billing_mode: BillingMode,
+    pub spending_cap: Option<u64>, // most a single charge may take, required for metered plans
+}
+
 #[derive(Accounts)]
+#[instruction(args: SubscribeArgs)]
 pub struct Subscribe<'info> {
     #[account(mut)]
     pub subscriber: Signer<'info>,
@@ -42,12 +48,24 @@ pub struct Subscribe<'info> {
     )]
     pub user_subscription: Account<'info, UserSubscription>,
     #[account(
+        mut,
         constraint = subscription_plan.active @ SubscriptionError::InactivePlan
     )]
     pub subscription_plan: Account<'info, SubscriptionPlan>,
+    #[account(
+        mut,
+        constraint = coupon.subscription_plan == subscription_plan.key() @ SubscriptionError::InvalidCoupon
+    )]
+    pub coupon: Option<Box<Account<'info, Coupon>>>,
+    #[account(
+        seeds = [ACCEPTED_MINTS_SEED],
+        bump = accepted_mints.bump
+    )]
+    pub accepted_mints: Account<'info, AcceptedMints>,
     #[account(
         address = subscription_plan.mint @ SubscriptionError::MintMismatch,
-        mint::token_program = token_program
+        mint::token_program = token_program,
+        constraint = accepted_mints.contains(&mint.key()) @ SubscriptionError::MintNotAccepted
     )]
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
@@ -60,7 +78,7 @@ pub struct Subscribe<'info> {
     #[account(
         init_if_needed,
         payer = subscriber,
-        seeds = [SUBSCRIBER_VAULT_SEED, subscriber.key.as_ref()],
+        seeds = [SUBSCRIBER_VAULT_SEED, subscriber.key.as_ref(), mint.key().as_ref()],
         token::mint = mint,
         token::authority = subscriber_vault,
         token::token_program = token_program,
@@ -68,15 +86,26 @@ pub struct Subscribe<'info> {
     )]
     pub subscriber_vault: InterfaceAccount<'info, TokenAccount>,
 
+    #[account(
+        seeds = [FEE_TOKEN_VAULT_SEED, mint.key().as_ref()],
+        bump
+    )]
+    /// CHECK: via seeds, only passed on to the charge task
+    pub fee_token_vault: UncheckedAccount<'info>,
+
     #[account(
         seeds = [GLOBAL_STATE_SEED],
-        bump = global_state.bump
+        bump = global_state.bump,
+        constraint = !global_state.paused @ SubscriptionError::ProtocolPaused
     )]
     pub global_state: Account<'info, GlobalState>,
 
     // TUKTUK ACCOUNTS
-    #[account(mut)]
-    /// CHECK: via signer, only can call this instruction
+    #[account(
+        mut,
+        address = global_state.task_queue @ SubscriptionError::InvalidTaskQueue
+    )]
+    /// CHECK: via address
     pub task_queue: UncheckedAccount<'info>,
     #[account(
         mut,
@@ -91,8 +120,13 @@ pub struct Subscribe<'info> {
       seeds::program = tuktuk_program::tuktuk::ID,
     )]
     pub task_queue_authority: Account<'info, TaskQueueAuthorityV0>,
-    #[account(mut)]
-    /// CHECK: Initialized in CPI
+    #[account(
+        mut,
+        seeds = [b"task", task_queue.key().as_ref(), args.task_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    /// CHECK: via seeds, initialized in CPI
     pub task: AccountInfo<'info>,
 
     // PROGRAMS
@@ -103,24 +137,86 @@ pub struct Subscribe<'info> {
 }
 
 impl<'info> Subscribe<'info> {
-    pub fn subscribe(&mut self, bumps: &SubscribeBumps) -> Result<()> {
+    pub fn subscribe(&mut self, args: SubscribeArgs, bumps: &SubscribeBumps) -> Result<()> {
         // think about extra security checks??
+        require!(
+            args.crank_reserve >= min_crank_reserve(self.global_state.crank_reward)?,
+            SubscriptionError::InsufficientCrankReserve
+        );
 
-        self.transfer(self.subscription_plan.amount)?;
+        let now = Clock::get()?.unix_timestamp;
+
+        // with a trial the first cycle is charged when it ends, otherwise right away
+        let (first_charge_ts, trigger) = if self.subscription_plan.trial_period > 0 {
+            let ts = now
+                .checked_add(self.subscription_plan.trial_period)
+                .ok_or(SubscriptionError::ArithmeticError)?;
+            (ts, TriggerV0::Timestamp(ts))
+        } else {
+            (now, TriggerV0::Now)
+        };
+
+        let discount = self.redeem_coupon(now)?;
+        let (amount, _) = self.subscription_plan.charge_amount(0, first_charge_ts);
+
+        match args.spending_cap {
+            Some(cap) => require!(cap >= amount, SubscriptionError::InvalidSpendingCap),
+            None => require!(
+                self.subscription_plan.metering.is_none(),
+                SubscriptionError::InvalidSpendingCap
+            ),
+        }
+
+        match args.billing_mode {
+            BillingMode::Vault => {
+                self.transfer(discount.map_or(amount, |(discount, _)| discount.apply(amount)))?
+            }
+            BillingMode::Delegated { approved_cycles } => {
+                require!(approved_cycles > 0, SubscriptionError::InvalidAmount);
+                self.approve(approved_cycles, args.spending_cap)?;
+            }
+        }
 
         self.user_subscription.set_inner(UserSubscription {
             subscriber: self.subscriber.key(),
             subscriber_ata: self.subscriber_ata.key(),
             subscription: self.subscription_plan.key(),
+            billing_mode: args.billing_mode,
             status: Status::Active,
             failure_count: 0,
-            next_task_id: 0,
-            last_exec_ts: Clock::get()?.unix_timestamp,
+            next_task_id: args.task_id,
+            task_queue: self.task_queue.key(),
+            last_exec_ts: first_charge_ts,
+            resume_at: None,
+            access_until: None,
+            retry_at: None,
+            cycles_charged: 0,
+            last_charge_amount: 0,
+            credit: 0,
+            usage_units: 0,
+            spending_cap: args.spending_cap,
+            discount: discount.map(|(discount, _)| discount),
+            discount_cycles: discount.map_or(0, |(_, cycles)| cycles),
+            crank_reserve: args.crank_reserve,
+            depleted_status: None,
+            next_receipt: self.subscription_plan.charge_seq,
+            ledger_bump: None,
             subscriber_vault_bump: bumps.subscriber_vault,
             bump: bumps.user_subscription,
         });
 
-        self.schedule(self.user_subscription.next_task_id)?;
+        self.fund_crank_reserve(args.crank_reserve)?;
+
+        self.subscription_plan.subscriber_count = self
+            .subscription_plan
+            .subscriber_count
+            .checked_add(1)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        // batch billed plans charge the first cycle with the next `charge_batch` after it is due
+        if !self.subscription_plan.batch_billing {
+            self.schedule(args.task_id, trigger)?;
+        }
 
         // emit events so that it can be used as trigger for merchant backend
         emit!(SubscribeEvent {
@@ -132,14 +228,56 @@ impl<'info> Subscribe<'info> {
         Ok(())
     }
 
+    // counts the redemption on-chain so a code can't be used past its limit
+    pub fn redeem_coupon(&mut self, now: i64) -> Result<Option<(Discount, u16)>> {
+        let Some(coupon) = self.coupon.as_mut() else {
+            return Ok(None);
+        };
+
+        require!(
+            coupon.is_redeemable(now),
+            SubscriptionError::CouponNotRedeemable
+        );
+
+        coupon.redemptions = coupon
+            .redemptions
+            .checked_add(1)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        emit!(CouponRedeemedEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key(),
+            coupon: coupon.key(),
+            redemptions: coupon.redemptions
+        });
+
+        Ok(Some((coupon.discount, coupon.duration_cycles)))
+    }
+
+    // the reserve is kept as extra lamports on the subscription account
+    pub fn fund_crank_reserve(&mut self, lamports: u64) -> Result<()> {
+        let ctx = CpiContext::new(
+            self.system_program.to_account_info(),
+            Transfer {
+                from: self.subscriber.to_account_info(),
+                to: self.user_subscription.to_account_info(),
+            },
+        );
+
+        system_program::transfer(ctx, lamports)
+    }
+
+    // only moves the missing difference, a vault that already covers the first cycle is left as is
     pub fn transfer(&mut self, amount: u64) -> Result<()> {
-        if self.subscriber_vault.amount > amount {
+        let missing = amount.saturating_sub(self.subscriber_vault.amount);
+
+        if missing == 0 {
             return Ok(());
         }
 
         msg!(
-            "no USDC in vault, adding {} USDC to vault for current plan cycle",
-            amount
+            "not enough tokens in vault, adding {} tokens to vault for current plan cycle",
+            missing
                 .checked_div((10 as u64).checked_pow(self.mint.decimals as u32).unwrap())
                 .unwrap()
         );
@@ -154,59 +292,70 @@ impl<'info> Subscribe<'info> {
             },
         );
 
-        transfer_checked(ctx, amount, self.mint.decimals)
+        transfer_checked(ctx, missing, self.mint.decimals)
     }
 
-    pub fn schedule(&mut self, task_id: u16) -> Result<()> {
-        let ixs = vec![Instruction {
-            program_id: crate::ID,
-            accounts: crate::accounts::ChargeUserRecurring {
-                subscriber: self.subscriber.key(),
-                merchant: self.subscription_plan.merchant.key(),
-                user_subscription: self.user_subscription.key(),
-                subscription_plan: self.subscription_plan.key(),
-                merchant_ata: self.subscription_plan.merchant_ata.key(),
-                mint: self.mint.key(),
-                subscriber_vault: self.subscriber_vault.key(),
-                instructions: INSTRUCTIONS_SYSVAR_ID.key(),
-                associated_token_program: self.associated_token_program.key(),
-                token_program: self.token_program.key(),
-                system_program: self.system_program.key(),
-            }
-            .to_account_metas(None),
-            data: crate::instruction::ChargeUserRecurring.data(),
-        }];
-
-        let (compiled_tx, _) = compile_transaction(ixs, vec![])?;
+    pub fn approve(&mut self, approved_cycles: u16, spending_cap: Option<u64>) -> Result<()> {
+        let allowance = self
+            .subscription_plan
+            .allowance(approved_cycles, spending_cap)
+            .ok_or(SubscriptionError::ArithmeticError)?;
 
-        let signer_seeds: &[&[&[u8]]] = &[&[
-            QUEUE_AUTHORITY_SEED,
-            &[self.global_state.queue_authority_bump],
-        ]];
+        approve_plan(
+            self.token_program.to_account_info(),
+            ApproveChecked {
+                to: self.subscriber_ata.to_account_info(),
+                mint: self.mint.to_account_info(),
+                delegate: self.subscription_plan.to_account_info(),
+                authority: self.subscriber.to_account_info(),
+            },
+            &self.subscriber_ata,
+            None,
+            allowance,
+            self.mint.decimals,
+        )
+    }
 
-        let ctx = CpiContext::new_with_signer(
+    pub fn schedule(&mut self, task_id: u16, trigger: TriggerV0) -> Result<()> {
+        queue_charge_task(
             self.tuktuk_program.to_account_info(),
             QueueTaskV0 {
-                payer: self.queue_authority.to_account_info(),
+                payer: self.subscriber.to_account_info(),
                 queue_authority: self.queue_authority.to_account_info(),
                 task_queue: self.task_queue.to_account_info(),
                 task_queue_authority: self.task_queue_authority.to_account_info(),
                 task: self.task.to_account_info(),
                 system_program: self.system_program.to_account_info(),
             },
-            signer_seeds,
-        );
-
-        queue_task_v0(
-            ctx,
-            QueueTaskArgsV0 {
-                id: task_id,
-                trigger: TriggerV0::Now,
-                transaction: TransactionSourceV0::CompiledV0(compiled_tx),
-                crank_reward: None,
-                free_tasks: 15, // this is for recursion, this task will queue one more task
-                description: "payment for subscription".to_string(),
-            },
+            self.global_state.queue_authority_bump,
+            task_id,
+            trigger,
+            self.global_state.crank_reward,
+            charge_instruction(
+                crate::accounts::ChargeUserRecurring {
+                    subscriber: self.subscriber.key(),
+                    merchant: self.subscription_plan.merchant.key(),
+                    user_subscription: self.user_subscription.key(),
+                    subscription_plan: self.subscription_plan.key(),
+                    merchant_ata: self.subscription_plan.merchant_ata.key(),
+                    accepted_mints: self.accepted_mints.key(),
+                    global_state: self.global_state.key(),
+                    fee_token_vault: self.fee_token_vault.key(),
+                    task_queue: self.task_queue.key(),
+                    billing_ledger: None,
+                    charge_receipt: self
+                        .user_subscription
+                        .next_receipt_address(&self.user_subscription.key()),
+                    mint: self.mint.key(),
+                    subscriber_vault: self.subscriber_vault.key(),
+                    subscriber_ata: self.subscriber_ata.key(),
+                    instructions: INSTRUCTIONS_SYSVAR_ID.key(),
+                    associated_token_program: self.associated_token_program.key(),
+                    token_program: self.token_program.key(),
+                    system_program: self.system_program.key(),
+                },
+                &self.subscription_plan.payout_split,
+            ),
         )
     }
 }
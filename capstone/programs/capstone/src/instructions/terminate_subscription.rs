@@ -0,0 +1,147 @@
+use anchor_lang::prelude::*;
+use tuktuk_program::{
+    tuktuk::{cpi::accounts::DequeueTaskV0, program::Tuktuk},
+    TaskQueueAuthorityV0,
+};
+
+use crate::{
+    error::SubscriptionError,
+    events::SubscriptionTerminatedEvent,
+    states::{
+        BillingLedger, GlobalState, SubscriptionPlan, TerminationReason, UserSubscription,
+        GLOBAL_STATE_SEED, LEDGER_SEED, QUEUE_AUTHORITY_SEED, SUBSCRIPTION_SEED,
+    },
+    utils::{dequeue_charge_task, remaining_allowance},
+};
+
+// merchant ends a subscription, e.g. before retiring the plan or to ban a subscriber, the rent and
+// crank reserve go back to the subscriber
+#[derive(Accounts)]
+pub struct TerminateSubscription<'info> {
+    pub merchant: Signer<'info>,
+    #[account(
+        mut,
+        address = user_subscription.subscriber @ SubscriptionError::InvalidSubscription
+    )]
+    /// CHECK: via address, receives the rent of the closed accounts
+    pub subscriber: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        close = subscriber,
+        seeds = [SUBSCRIPTION_SEED, subscriber.key.as_ref(), subscription_plan.key().as_ref()],
+        bump = user_subscription.bump
+    )]
+    pub user_subscription: Account<'info, UserSubscription>,
+    #[account(
+        mut,
+        close = subscriber,
+        seeds = [LEDGER_SEED, user_subscription.key().as_ref()],
+        bump = billing_ledger.bump
+    )]
+    pub billing_ledger: Option<Account<'info, BillingLedger>>,
+    #[account(
+        mut,
+        has_one = merchant @ SubscriptionError::InvalidSigner
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+    #[account(
+        address = user_subscription.subscriber_ata @ SubscriptionError::InvalidSubscription
+    )]
+    /// CHECK: via address, only read for the allowance left to the plan, may be closed
+    pub subscriber_ata: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    // TUKTUK
+    #[account(
+        mut,
+        address = user_subscription.task_queue @ SubscriptionError::InvalidTaskQueue
+    )]
+    /// CHECK: via address
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [QUEUE_AUTHORITY_SEED],
+        bump = global_state.queue_authority_bump
+    )]
+    /// CHECK: via seeds
+    pub queue_authority: UncheckedAccount<'info>,
+    #[account(
+      seeds = [b"task_queue_authority", task_queue.key().as_ref(), queue_authority.key().as_ref()],
+      bump = task_queue_authority.bump_seed,
+      seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    pub task_queue_authority: Account<'info, TaskQueueAuthorityV0>,
+    #[account(
+        mut,
+        seeds = [b"task", task_queue.key().as_ref(), user_subscription.next_task_id.to_le_bytes().as_ref()],
+        bump,
+        seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    /// CHECK: via seeds, queued task of the subscription
+    pub task: AccountInfo<'info>,
+
+    // PROGRAMS
+    pub tuktuk_program: Program<'info, Tuktuk>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> TerminateSubscription<'info> {
+    pub fn terminate_subscription(&mut self, reason: TerminationReason) -> Result<()> {
+        // the ledger would be left without its subscription, and its rent with it
+        require!(
+            self.billing_ledger.is_some() == self.user_subscription.ledger_bump.is_some(),
+            SubscriptionError::MissingBillingLedger
+        );
+
+        if self
+            .user_subscription
+            .has_queued_task(&self.subscription_plan)
+        {
+            self.dequeue_task()?;
+        }
+
+        // a delegated approval can only be revoked by the subscriber, it is left in place since
+        // nothing charges the plan's allowance once the subscription is closed. The event carries
+        // what is left of it so the subscriber can be told to revoke it
+        let remaining_allowance = remaining_allowance(
+            &self.subscriber_ata.to_account_info(),
+            &self.subscription_plan.key(),
+        );
+
+        self.subscription_plan.subscriber_count = self
+            .subscription_plan
+            .subscriber_count
+            .checked_sub(1)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        emit!(SubscriptionTerminatedEvent {
+            subscriber: self.subscriber.key(),
+            subscription: self.subscription_plan.key(),
+            terminated_by: self.merchant.key(),
+            reason,
+            remaining_allowance
+        });
+
+        Ok(())
+    }
+
+    pub fn dequeue_task(&mut self) -> Result<()> {
+        dequeue_charge_task(
+            self.tuktuk_program.to_account_info(),
+            DequeueTaskV0 {
+                queue_authority: self.queue_authority.to_account_info(),
+                rent_refund: self.task_queue.to_account_info(),
+                task_queue_authority: self.task_queue_authority.to_account_info(),
+                task_queue: self.task_queue.to_account_info(),
+                task: self.task.to_account_info(),
+            },
+            self.global_state.queue_authority_bump,
+        )
+    }
+}
@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::SubscriptionError,
+    events::{AdminChangedEvent, AdminProposedEvent},
+    states::{GlobalState, GLOBAL_STATE_SEED},
+};
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump,
+        constraint = global_state.admin == signer.key() @ SubscriptionError::InvalidSigner
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+impl<'info> ProposeAdmin<'info> {
+    // overwrites any earlier proposal, the current admin stays in control until it is accepted
+    pub fn propose_admin(&mut self, new_admin: Pubkey) -> Result<()> {
+        self.global_state.pending_admin = Some(new_admin);
+
+        emit!(AdminProposedEvent {
+            admin: self.global_state.admin,
+            pending_admin: new_admin
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump,
+        constraint = global_state.pending_admin == Some(signer.key()) @ SubscriptionError::InvalidSigner
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+impl<'info> AcceptAdmin<'info> {
+    pub fn accept_admin(&mut self) -> Result<()> {
+        let old_admin = self.global_state.admin;
+
+        self.global_state.admin = self.signer.key();
+        self.global_state.pending_admin = None;
+
+        emit!(AdminChangedEvent {
+            old_admin,
+            new_admin: self.signer.key()
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+
+use crate::{
+    error::SubscriptionError,
+    states::{
+        AcceptedMints, GlobalState, ACCEPTED_MINTS_SEED, FEE_TOKEN_VAULT_SEED, GLOBAL_STATE_SEED,
+        MAX_ACCEPTED_MINTS,
+    },
+};
+
+// used by both `add_accepted_mint` and `remove_accepted_mint`
+#[derive(Accounts)]
+pub struct UpdateAcceptedMints<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump,
+        constraint = global_state.admin == signer.key() @ SubscriptionError::InvalidSigner
+    )]
+    pub global_state: Account<'info, GlobalState>,
+    #[account(
+        mut,
+        seeds = [ACCEPTED_MINTS_SEED],
+        bump = accepted_mints.bump
+    )]
+    pub accepted_mints: Account<'info, AcceptedMints>,
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    // collects the protocol fee of every charge in this mint
+    #[account(
+        init_if_needed,
+        payer = signer,
+        seeds = [FEE_TOKEN_VAULT_SEED, mint.key().as_ref()],
+        token::mint = mint,
+        token::authority = fee_token_vault,
+        token::token_program = token_program,
+        bump
+    )]
+    pub fee_token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> UpdateAcceptedMints<'info> {
+    pub fn add_accepted_mint(&mut self) -> Result<()> {
+        let mint = self.mint.key();
+
+        require!(
+            !self.accepted_mints.contains(&mint),
+            SubscriptionError::MintAlreadyAccepted
+        );
+        require!(
+            self.accepted_mints.mints.len() < MAX_ACCEPTED_MINTS,
+            SubscriptionError::AcceptedMintsFull
+        );
+
+        self.accepted_mints.mints.push(mint);
+
+        Ok(())
+    }
+
+    // existing plans on this mint keep their data, but can no longer be subscribed to or charged
+    pub fn remove_accepted_mint(&mut self) -> Result<()> {
+        let mint = self.mint.key();
+
+        require!(
+            self.accepted_mints.contains(&mint),
+            SubscriptionError::MintNotAccepted
+        );
+
+        self.accepted_mints.mints.retain(|m| m != &mint);
+
+        Ok(())
+    }
+}
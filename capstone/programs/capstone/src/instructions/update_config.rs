@@ -0,0 +1,63 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::SubscriptionError,
+    events::ConfigUpdatedEvent,
+    states::{GlobalState, GLOBAL_STATE_SEED, MAX_BPS, MIN_CRANK_REWARD},
+};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct UpdateConfigArgs {
+    pub fees: Option<u64>,
+    pub protocol_fee_bps: Option<u16>,
+    pub crank_reward: Option<u64>,
+    pub paused: Option<bool>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump,
+        constraint = global_state.admin == signer.key() @ SubscriptionError::InvalidSigner
+    )]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+impl<'info> UpdateConfig<'info> {
+    // only the provided values are changed
+    pub fn update_config(&mut self, args: UpdateConfigArgs) -> Result<()> {
+        if let Some(fees) = args.fees {
+            self.global_state.fees = fees;
+        }
+
+        if let Some(protocol_fee_bps) = args.protocol_fee_bps {
+            require!(protocol_fee_bps <= MAX_BPS, SubscriptionError::InvalidFee);
+            self.global_state.protocol_fee_bps = protocol_fee_bps;
+        }
+
+        // a reward no crank turner takes would stall every charge task queued after it
+        if let Some(crank_reward) = args.crank_reward {
+            require!(
+                crank_reward >= MIN_CRANK_REWARD,
+                SubscriptionError::CrankRewardTooLow
+            );
+            self.global_state.crank_reward = crank_reward;
+        }
+
+        if let Some(paused) = args.paused {
+            self.global_state.paused = paused;
+        }
+
+        emit!(ConfigUpdatedEvent {
+            fees: self.global_state.fees,
+            protocol_fee_bps: self.global_state.protocol_fee_bps,
+            crank_reward: self.global_state.crank_reward,
+            paused: self.global_state.paused
+        });
+
+        Ok(())
+    }
+}
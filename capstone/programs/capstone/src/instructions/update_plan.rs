@@ -0,0 +1,135 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::TokenAccount;
+
+use crate::{
+    error::SubscriptionError,
+    events::{
+        PayoutSplitUpdatedEvent, PlanPausedEvent, PlanResumedEvent, PriceChangeScheduledEvent,
+    },
+    states::{PayoutShare, SubscriptionPlan, MAX_PAYOUT_RECIPIENTS},
+    utils::payout_accounts,
+};
+
+// used by `pause_plan`, `resume_plan`, `schedule_price_change` and `set_payout_split`
+#[derive(Accounts)]
+pub struct UpdatePlan<'info> {
+    pub merchant: Signer<'info>,
+    #[account(
+        mut,
+        has_one = merchant @ SubscriptionError::InvalidSigner
+    )]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+}
+
+impl<'info> UpdatePlan<'info> {
+    // queued charges keep running while paused, they just skip the cycle without charging
+    pub fn pause_plan(&mut self) -> Result<()> {
+        require!(
+            self.subscription_plan.active,
+            SubscriptionError::InactivePlan
+        );
+
+        self.subscription_plan.active = false;
+
+        emit!(PlanPausedEvent {
+            merchant: self.merchant.key(),
+            subscription: self.subscription_plan.key()
+        });
+
+        Ok(())
+    }
+
+    pub fn resume_plan(&mut self) -> Result<()> {
+        require!(
+            !self.subscription_plan.active,
+            SubscriptionError::PlanAlreadyActive
+        );
+
+        self.subscription_plan.active = true;
+
+        emit!(PlanResumedEvent {
+            merchant: self.merchant.key(),
+            subscription: self.subscription_plan.key()
+        });
+
+        Ok(())
+    }
+
+    // new price becomes effective one full interval from now, so every subscriber gets at least
+    // one cycle at the old price after the event is emitted
+    pub fn schedule_price_change(&mut self, new_amount: u64) -> Result<()> {
+        require!(new_amount > 0, SubscriptionError::InvalidAmount);
+
+        let now = Clock::get()?.unix_timestamp;
+        self.subscription_plan.apply_pending_amount(now);
+
+        let effective_ts = self
+            .subscription_plan
+            .interval
+            .one_interval_after(now)
+            .ok_or(SubscriptionError::ArithmeticError)?;
+
+        self.subscription_plan.pending_amount = Some(new_amount);
+        self.subscription_plan.pending_amount_effective_ts = effective_ts;
+
+        emit!(PriceChangeScheduledEvent {
+            merchant: self.merchant.key(),
+            subscription: self.subscription_plan.key(),
+            old_amount: self.subscription_plan.amount,
+            new_amount,
+            effective_ts
+        });
+
+        Ok(())
+    }
+
+    // an empty split sends every payout to `merchant_ata` again, otherwise the recipient token
+    // accounts are passed as remaining accounts in the same order so their mint can be checked.
+    // Already queued tasks carry the old recipients, they requeue themselves with the new ones
+    // once before charging
+    pub fn set_payout_split(
+        &mut self,
+        payout_split: Vec<PayoutShare>,
+        remaining_accounts: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(
+            payout_split.len() <= MAX_PAYOUT_RECIPIENTS,
+            SubscriptionError::InvalidPayoutSplit
+        );
+
+        if !payout_split.is_empty() {
+            let total_bps = payout_split.iter().try_fold(0u16, |total, share| {
+                if share.bps == 0 {
+                    return None;
+                }
+                total.checked_add(share.bps)
+            });
+            require!(
+                total_bps == Some(10_000),
+                SubscriptionError::InvalidPayoutSplit
+            );
+
+            payout_accounts(&payout_split, remaining_accounts)
+                .ok_or(SubscriptionError::InvalidPayoutSplit)?;
+
+            for info in &remaining_accounts[..payout_split.len()] {
+                let recipient = InterfaceAccount::<TokenAccount>::try_from(info)?;
+                require_keys_eq!(
+                    recipient.mint,
+                    self.subscription_plan.mint,
+                    SubscriptionError::MintMismatch
+                );
+            }
+        }
+
+        self.subscription_plan.payout_split = payout_split.clone();
+
+        emit!(PayoutSplitUpdatedEvent {
+            merchant: self.merchant.key(),
+            subscription: self.subscription_plan.key(),
+            payout_split
+        });
+
+        Ok(())
+    }
+}
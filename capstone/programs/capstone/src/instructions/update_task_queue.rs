@@ -0,0 +1,53 @@
+use anchor_lang::prelude::*;
+use tuktuk_program::TaskQueueAuthorityV0;
+
+use crate::{
+    error::SubscriptionError,
+    events::TaskQueueUpdatedEvent,
+    states::{GlobalState, GLOBAL_STATE_SEED, QUEUE_AUTHORITY_SEED},
+};
+
+#[derive(Accounts)]
+pub struct UpdateTaskQueue<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump,
+        constraint = global_state.admin == signer.key() @ SubscriptionError::InvalidSigner
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    // TUKTUK ACCOUNTS
+    /// CHECK: the queue authority must already be registered on it, see `task_queue_authority`
+    pub task_queue: UncheckedAccount<'info>,
+    #[account(
+        seeds = [QUEUE_AUTHORITY_SEED],
+        bump = global_state.queue_authority_bump
+    )]
+    /// CHECK: via seeds
+    pub queue_authority: UncheckedAccount<'info>,
+    #[account(
+      seeds = [b"task_queue_authority", task_queue.key().as_ref(), queue_authority.key().as_ref()],
+      bump = task_queue_authority.bump_seed,
+      seeds::program = tuktuk_program::tuktuk::ID,
+    )]
+    pub task_queue_authority: Account<'info, TaskQueueAuthorityV0>,
+}
+
+impl<'info> UpdateTaskQueue<'info> {
+    // only new tasks go to the new queue, queued tasks keep running on the queue stored in
+    // their `UserSubscription`
+    pub fn update_task_queue(&mut self) -> Result<()> {
+        let old_task_queue = self.global_state.task_queue;
+
+        self.global_state.task_queue = self.task_queue.key();
+
+        emit!(TaskQueueUpdatedEvent {
+            old_task_queue,
+            new_task_queue: self.task_queue.key()
+        });
+
+        Ok(())
+    }
+}
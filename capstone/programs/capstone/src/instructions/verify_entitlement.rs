@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::SubscriptionError,
+    states::{PricingTier, SubscriptionPlan, UserSubscription},
+};
+
+// returned through `set_return_data`, other programs can read it with the
+// `capstone-entitlement` crate
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct Entitlement {
+    pub active: bool,
+    pub plan: Pubkey,
+    pub paid_through: i64,
+    pub tier: PricingTier,
+}
+
+#[derive(Accounts)]
+pub struct VerifyEntitlement<'info> {
+    pub user_subscription: Account<'info, UserSubscription>,
+    #[account(address = user_subscription.subscription @ SubscriptionError::InvalidSubscription)]
+    pub subscription_plan: Account<'info, SubscriptionPlan>,
+}
+
+impl<'info> VerifyEntitlement<'info> {
+    pub fn verify_entitlement(&self) -> Result<Entitlement> {
+        let now = Clock::get()?.unix_timestamp;
+
+        let paid_through = self
+            .user_subscription
+            .access_until
+            .unwrap_or(self.user_subscription.last_exec_ts);
+
+        Ok(Entitlement {
+            active: self.user_subscription.is_entitled(now),
+            plan: self.subscription_plan.key(),
+            paid_through,
+            tier: self
+                .subscription_plan
+                .current_tier(self.user_subscription.cycles_charged),
+        })
+    }
+}
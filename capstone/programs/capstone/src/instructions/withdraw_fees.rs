@@ -0,0 +1,116 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+};
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+use crate::{
+    error::SubscriptionError,
+    events::FeesWithdrawnEvent,
+    states::{GlobalState, FEE_TOKEN_VAULT_SEED, GLOBAL_STATE_SEED, VAULT_SEED},
+};
+
+#[derive(Accounts)]
+pub struct WithdrawFees<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [GLOBAL_STATE_SEED],
+        bump = global_state.bump,
+        constraint = global_state.admin == signer.key() @ SubscriptionError::InvalidSigner
+    )]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(
+        mut,
+        seeds = [VAULT_SEED],
+        bump
+    )]
+    pub fees_vault: SystemAccount<'info>,
+
+    #[account(mint::token_program = token_program)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [FEE_TOKEN_VAULT_SEED, mint.key().as_ref()],
+        token::mint = mint,
+        token::authority = fee_token_vault,
+        token::token_program = token_program,
+        bump
+    )]
+    pub fee_token_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program
+    )]
+    pub signer_ata: InterfaceAccount<'info, TokenAccount>,
+
+    // PROGRAMS
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> WithdrawFees<'info> {
+    // drains the SOL fees vault and the fee token vault of `mint`
+    pub fn withdraw_fees(&mut self, bumps: &WithdrawFeesBumps) -> Result<()> {
+        let lamports = self.fees_vault.lamports();
+
+        if lamports > 0 {
+            let signer_seeds: &[&[&[u8]]] = &[&[VAULT_SEED, &[bumps.fees_vault]]];
+
+            transfer(
+                CpiContext::new_with_signer(
+                    self.system_program.to_account_info(),
+                    Transfer {
+                        from: self.fees_vault.to_account_info(),
+                        to: self.signer.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                lamports,
+            )?;
+        }
+
+        let amount = self.fee_token_vault.amount;
+
+        if amount > 0 {
+            let mint_key = self.mint.key();
+            let signer_seeds: &[&[&[u8]]] = &[&[
+                FEE_TOKEN_VAULT_SEED,
+                mint_key.as_ref(),
+                &[bumps.fee_token_vault],
+            ]];
+
+            transfer_checked(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.fee_token_vault.to_account_info(),
+                        to: self.signer_ata.to_account_info(),
+                        mint: self.mint.to_account_info(),
+                        authority: self.fee_token_vault.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                amount,
+                self.mint.decimals,
+            )?;
+        }
+
+        emit!(FeesWithdrawnEvent {
+            admin: self.signer.key(),
+            mint: self.mint.key(),
+            lamports,
+            amount
+        });
+
+        Ok(())
+    }
+}
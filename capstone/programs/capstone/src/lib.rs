@@ -3,12 +3,15 @@ use tuktuk_program::RunTaskReturnV0;
 
 declare_id!("ZtzPHWinzmfmxDBeoEUy2JDSt3qGp3pv1BuAFc6nrop");
 
-mod error;
-mod events;
-mod instructions;
-mod states;
+pub mod calendar;
+pub mod error;
+pub mod events;
+pub mod instructions;
+pub mod states;
+mod utils;
 
 use instructions::*;
+use states::{PayoutShare, TerminationReason};
 
 #[program]
 pub mod capstone {
@@ -26,19 +29,153 @@ pub mod capstone {
         ctx.accounts.charge_fees()
     }
 
-    pub fn subscribe(ctx: Context<Subscribe>) -> Result<()> {
-        ctx.accounts.subscribe(&ctx.bumps)
+    pub fn create_coupon(ctx: Context<CreateCoupon>, args: CreateCouponArgs) -> Result<()> {
+        ctx.accounts.create_coupon(args, &ctx.bumps)
     }
 
-    pub fn charge_user_recurring(ctx: Context<ChargeUserRecurring>) -> Result<RunTaskReturnV0> {
-        ctx.accounts.charge_user_recurring()
+    pub fn subscribe(ctx: Context<Subscribe>, args: SubscribeArgs) -> Result<()> {
+        ctx.accounts.subscribe(args, &ctx.bumps)
+    }
+
+    pub fn charge_user_recurring<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ChargeUserRecurring<'info>>,
+    ) -> Result<RunTaskReturnV0> {
+        ctx.accounts.charge_user_recurring(ctx.remaining_accounts)
+    }
+
+    pub fn charge_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ChargeBatch<'info>>,
+    ) -> Result<()> {
+        ctx.accounts.charge_batch(ctx.remaining_accounts)
     }
 
     pub fn cancel_subscription(ctx: Context<CancelSubscription>) -> Result<()> {
         ctx.accounts.cancel_subscription()
     }
 
+    pub fn terminate_subscription(
+        ctx: Context<TerminateSubscription>,
+        reason: TerminationReason,
+    ) -> Result<()> {
+        ctx.accounts.terminate_subscription(reason)
+    }
+
+    pub fn close_failed_subscription(ctx: Context<CloseFailedSubscription>) -> Result<()> {
+        ctx.accounts.close_failed_subscription()
+    }
+
+    pub fn init_billing_ledger(ctx: Context<InitBillingLedger>) -> Result<()> {
+        ctx.accounts.init_billing_ledger(&ctx.bumps)
+    }
+
+    pub fn cancel_at_period_end(ctx: Context<CancelAtPeriodEnd>) -> Result<()> {
+        ctx.accounts.cancel_at_period_end()
+    }
+
+    pub fn reactivate_subscription<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ReactivateSubscription<'info>>,
+        task_id: u16,
+    ) -> Result<()> {
+        ctx.accounts
+            .reactivate_subscription(task_id, ctx.remaining_accounts)
+    }
+
+    pub fn pause_subscription(
+        ctx: Context<PauseSubscription>,
+        resume_at: Option<i64>,
+    ) -> Result<()> {
+        ctx.accounts.pause_subscription(resume_at)
+    }
+
+    pub fn resume_subscription(ctx: Context<ResumeSubscription>, task_id: u16) -> Result<()> {
+        ctx.accounts.resume_subscription(task_id)
+    }
+
+    pub fn fund_crank_reserve(
+        ctx: Context<FundCrankReserve>,
+        amount: u64,
+        task_id: u16,
+    ) -> Result<()> {
+        ctx.accounts.fund_crank_reserve(amount, task_id)
+    }
+
+    pub fn set_spending_cap(ctx: Context<SetSpendingCap>, spending_cap: Option<u64>) -> Result<()> {
+        ctx.accounts.set_spending_cap(spending_cap)
+    }
+
+    pub fn report_usage(ctx: Context<ReportUsage>, units: u64) -> Result<()> {
+        ctx.accounts.report_usage(units)
+    }
+
+    pub fn change_plan<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ChangePlan<'info>>,
+        task_id: u16,
+    ) -> Result<()> {
+        ctx.accounts
+            .change_plan(task_id, &ctx.bumps, ctx.remaining_accounts)
+    }
+
+    pub fn verify_entitlement(ctx: Context<VerifyEntitlement>) -> Result<Entitlement> {
+        ctx.accounts.verify_entitlement()
+    }
+
     pub fn close_vault(ctx: Context<CloseVault>) -> Result<()> {
         ctx.accounts.close_vault(&ctx.bumps)
     }
+
+    pub fn add_accepted_mint(ctx: Context<UpdateAcceptedMints>) -> Result<()> {
+        ctx.accounts.add_accepted_mint()
+    }
+
+    pub fn remove_accepted_mint(ctx: Context<UpdateAcceptedMints>) -> Result<()> {
+        ctx.accounts.remove_accepted_mint()
+    }
+
+    pub fn update_config(ctx: Context<UpdateConfig>, args: UpdateConfigArgs) -> Result<()> {
+        ctx.accounts.update_config(args)
+    }
+
+    pub fn withdraw_fees(ctx: Context<WithdrawFees>) -> Result<()> {
+        ctx.accounts.withdraw_fees(&ctx.bumps)
+    }
+
+    pub fn update_task_queue(ctx: Context<UpdateTaskQueue>) -> Result<()> {
+        ctx.accounts.update_task_queue()
+    }
+
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.propose_admin(new_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        ctx.accounts.accept_admin()
+    }
+
+    pub fn pause_plan(ctx: Context<UpdatePlan>) -> Result<()> {
+        ctx.accounts.pause_plan()
+    }
+
+    pub fn resume_plan(ctx: Context<UpdatePlan>) -> Result<()> {
+        ctx.accounts.resume_plan()
+    }
+
+    pub fn set_payout_split<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdatePlan<'info>>,
+        payout_split: Vec<PayoutShare>,
+    ) -> Result<()> {
+        ctx.accounts
+            .set_payout_split(payout_split, ctx.remaining_accounts)
+    }
+
+    pub fn schedule_price_change(ctx: Context<UpdatePlan>, new_amount: u64) -> Result<()> {
+        ctx.accounts.schedule_price_change(new_amount)
+    }
+
+    pub fn refund_charge(ctx: Context<RefundCharge>, amount: u64) -> Result<()> {
+        ctx.accounts.refund_charge(amount)
+    }
+
+    pub fn retire_plan(ctx: Context<RetirePlan>) -> Result<()> {
+        ctx.accounts.retire_plan()
+    }
 }
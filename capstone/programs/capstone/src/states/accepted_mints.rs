@@ -0,0 +1,18 @@
+use anchor_lang::prelude::*;
+
+#[derive(InitSpace)]
+#[account]
+pub struct AcceptedMints {
+    #[max_len(10)]
+    pub mints: Vec<Pubkey>,
+    pub bump: u8,
+}
+
+impl AcceptedMints {
+    pub fn contains(&self, mint: &Pubkey) -> bool {
+        self.mints.contains(mint)
+    }
+}
+
+pub const ACCEPTED_MINTS_SEED: &[u8] = b"accepted_mints";
+pub const MAX_ACCEPTED_MINTS: usize = 10; // keep in sync with max_len above
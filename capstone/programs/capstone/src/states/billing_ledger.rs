@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum ChargeOutcome {
+    Paid,
+    Failed, // vault did not hold enough tokens, `amount` is what was due
+}
+
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Debug, PartialEq)]
+pub struct LedgerEntry {
+    pub cycle: u32, // cycles charged before this attempt
+    pub timestamp: i64,
+    pub amount: u64,
+    pub mint: Pubkey,
+    pub outcome: ChargeOutcome,
+    pub charge_seq: Option<u64>, // only set for paid charges, see `refund_charge`
+}
+
+// optional billing history of a subscription, keeps the last `LEDGER_CAPACITY` charge attempts
+#[derive(InitSpace)]
+#[account]
+pub struct BillingLedger {
+    pub user_subscription: Pubkey,
+    pub head: u8, // index the next entry is written to once the ledger is full
+    #[max_len(16)]
+    pub entries: Vec<LedgerEntry>,
+    pub bump: u8,
+}
+
+impl BillingLedger {
+    pub fn record(&mut self, entry: LedgerEntry) {
+        if self.entries.len() < LEDGER_CAPACITY {
+            self.entries.push(entry);
+        } else {
+            self.entries[self.head as usize] = entry;
+            self.head = ((self.head as usize + 1) % LEDGER_CAPACITY) as u8;
+        }
+    }
+}
+
+pub const LEDGER_SEED: &[u8] = b"ledger";
+pub const LEDGER_CAPACITY: usize = 16; // keep in sync with max_len above
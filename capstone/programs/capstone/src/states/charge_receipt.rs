@@ -0,0 +1,37 @@
+use anchor_lang::prelude::*;
+
+// written for every charge that moved tokens, refunds are checked against it so a charge can be
+// refunded in parts but never for more than it took. It outlives the subscription, a canceled or
+// terminated subscriber can still be refunded
+#[derive(InitSpace)]
+#[account]
+pub struct ChargeReceipt {
+    pub subscription_plan: Pubkey,
+    pub subscriber: Pubkey,
+    pub subscriber_ata: Pubkey, // refunds go here or to the subscriber vault
+    pub charge_seq: u64,
+    pub amount: u64,   // taken from the subscriber, including the protocol fee
+    pub refunded: u64, // never more than `amount`
+    pub charged_at: i64,
+    pub bump: u8,
+}
+
+impl ChargeReceipt {
+    pub fn address(user_subscription: &Pubkey, index: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                RECEIPT_SEED,
+                user_subscription.as_ref(),
+                &index.to_le_bytes(),
+            ],
+            &crate::ID,
+        )
+    }
+
+    pub fn refundable(&self) -> u64 {
+        self.amount.saturating_sub(self.refunded)
+    }
+}
+
+// receipts are numbered per subscription account, see `UserSubscription::next_receipt`
+pub const RECEIPT_SEED: &[u8] = b"receipt";
@@ -0,0 +1,51 @@
+use anchor_lang::prelude::*;
+
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum Discount {
+    Percentage { bps: u16 }, // share of the price taken off, in basis points
+    Fixed { amount: u64 },   // token amount taken off every discounted charge
+}
+
+impl Discount {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Discount::Percentage { bps } => *bps > 0 && *bps <= 10_000,
+            Discount::Fixed { amount } => *amount > 0,
+        }
+    }
+
+    // price after the discount, never below zero
+    pub fn apply(&self, amount: u64) -> u64 {
+        match self {
+            Discount::Percentage { bps } => {
+                let off = (amount as u128) * (*bps as u128) / 10_000;
+                amount - off as u64
+            }
+            Discount::Fixed { amount: off } => amount.saturating_sub(*off),
+        }
+    }
+}
+
+// discount code of a plan, its address is derived from the hashed code so each code is unique
+// per plan
+#[derive(InitSpace)]
+#[account]
+pub struct Coupon {
+    pub subscription_plan: Pubkey,
+    #[max_len(32)]
+    pub code: String,
+    pub discount: Discount,
+    pub duration_cycles: u16, // number of charged cycles the discount applies to
+    pub max_redemptions: u32,
+    pub redemptions: u32,
+    pub expires_at: i64, // no redemptions from this timestamp on, 0 for no expiry
+    pub bump: u8,
+}
+
+impl Coupon {
+    pub fn is_redeemable(&self, now: i64) -> bool {
+        (self.expires_at == 0 || now < self.expires_at) && self.redemptions < self.max_redemptions
+    }
+}
+
+pub const COUPON_SEED: &[u8] = b"coupon";
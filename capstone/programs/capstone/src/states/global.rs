@@ -3,12 +3,30 @@ use anchor_lang::prelude::*;
 #[account]
 #[derive(InitSpace)]
 pub struct GlobalState {
-    pub task_queue: Pubkey,
-    pub queue_authority: Pubkey, // PDA
+    pub admin: Pubkey,
+    pub pending_admin: Option<Pubkey>, // set by `propose_admin`, takes over once it accepts
+    pub task_queue: Pubkey,            // new charge tasks are queued here
+    pub queue_authority: Pubkey,       // PDA
     pub fees: u64,
+    pub protocol_fee_bps: u16, // cut of every recurring charge, sent to the mint's fee token vault
+    pub crank_reward: u64,     // lamports paid to crank operators for every charge task
+    pub paused: bool,          // blocks new plans and subscriptions, existing ones keep charging
     pub queue_authority_bump: u8,
     pub bump: u8,
 }
 
+impl GlobalState {
+    pub fn protocol_fee(&self, amount: u64) -> Option<u64> {
+        let fee = (amount as u128)
+            .checked_mul(self.protocol_fee_bps as u128)?
+            .checked_div(MAX_BPS as u128)?;
+
+        u64::try_from(fee).ok()
+    }
+}
+
 pub const GLOBAL_STATE_SEED: &[u8] = b"global";
 pub const QUEUE_AUTHORITY_SEED: &[u8] = b"queue_authority";
+pub const MAX_BPS: u16 = 10_000;
+pub const CRANK_REWARD: u64 = 10_000; // must stay above the min crank reward of the task queue
+pub const MIN_CRANK_REWARD: u64 = 5_000; // one signature fee, cranking must not cost the operator
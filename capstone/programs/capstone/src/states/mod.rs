@@ -6,3 +6,15 @@ pub use user_subscription::*;
 
 pub mod global;
 pub use global::*;
+
+pub mod accepted_mints;
+pub use accepted_mints::*;
+
+pub mod charge_receipt;
+pub use charge_receipt::*;
+
+pub mod billing_ledger;
+pub use billing_ledger::*;
+
+pub mod coupon;
+pub use coupon::*;
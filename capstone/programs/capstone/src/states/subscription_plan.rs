@@ -1,5 +1,156 @@
 use anchor_lang::prelude::*;
 
+use crate::calendar;
+
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum PricingTier {
+    Standard,
+    Introductory,
+    Trial, // nothing charged yet, only reported by `verify_entitlement`
+}
+
+// length of a billing cycle. Calendar intervals charge at 00:00 UTC of the anchor day, clamped to
+// the last day of shorter months, so every subscriber of the plan is billed on the same date and
+// the first cycle only runs until the next anchor
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum BillingInterval {
+    Seconds { seconds: i64 },
+    Days { days: u16 },
+    CalendarMonth { anchor_day: u8 },                  // 1..=31
+    CalendarYear { anchor_month: u8, anchor_day: u8 }, // 29 february is the 28th in common years
+}
+
+impl BillingInterval {
+    pub fn is_valid(&self) -> bool {
+        match *self {
+            BillingInterval::Seconds { seconds } => seconds > 0,
+            BillingInterval::Days { days } => days > 0,
+            BillingInterval::CalendarMonth { anchor_day } => (1..=31).contains(&anchor_day),
+            BillingInterval::CalendarYear {
+                anchor_month,
+                anchor_day,
+            } => {
+                (1..=12).contains(&anchor_month)
+                    // 2000 is a leap year, so 29 february is accepted
+                    && (1..=calendar::days_in_month(2000, anchor_month)).contains(&anchor_day)
+            }
+        }
+    }
+
+    // due date of the cycle after the one due at `ts`, or the first anchor after `ts`
+    pub fn next_charge_ts(&self, ts: i64) -> Option<i64> {
+        match *self {
+            BillingInterval::Seconds { seconds } => ts.checked_add(seconds),
+            BillingInterval::Days { days } => {
+                ts.checked_add((days as i64).checked_mul(calendar::SECONDS_PER_DAY)?)
+            }
+            BillingInterval::CalendarMonth { anchor_day } => calendar::next_monthly(ts, anchor_day),
+            BillingInterval::CalendarYear {
+                anchor_month,
+                anchor_day,
+            } => calendar::next_yearly(ts, anchor_month, anchor_day),
+        }
+    }
+
+    // start of the cycle that is due at `ts`
+    pub fn previous_charge_ts(&self, ts: i64) -> Option<i64> {
+        match *self {
+            BillingInterval::Seconds { seconds } => ts.checked_sub(seconds),
+            BillingInterval::Days { days } => {
+                ts.checked_sub((days as i64).checked_mul(calendar::SECONDS_PER_DAY)?)
+            }
+            BillingInterval::CalendarMonth { anchor_day } => {
+                calendar::previous_monthly(ts, anchor_day)
+            }
+            BillingInterval::CalendarYear {
+                anchor_month,
+                anchor_day,
+            } => calendar::previous_yearly(ts, anchor_month, anchor_day),
+        }
+    }
+
+    // one full interval after `ts`, ignoring the anchor
+    pub fn one_interval_after(&self, ts: i64) -> Option<i64> {
+        match *self {
+            BillingInterval::CalendarMonth { .. } => calendar::add_months(ts, 1),
+            BillingInterval::CalendarYear { .. } => calendar::add_months(ts, 12),
+            _ => self.next_charge_ts(ts),
+        }
+    }
+}
+
+// how failed charges are retried, delays are in seconds
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Debug, PartialEq)]
+pub enum RetryPolicy {
+    // retry `delay` seconds after every failed attempt
+    Fixed {
+        delay: i64,
+    },
+    // retry after `base_delay * 2^(attempt - 1)`, capped at `max_delay`
+    Exponential {
+        base_delay: i64,
+        max_delay: i64,
+    },
+    // retry at `due + offsets[attempt - 1]`, no retries left once the list runs out
+    Offsets {
+        #[max_len(8)]
+        offsets: Vec<i64>,
+    },
+}
+
+impl RetryPolicy {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            RetryPolicy::Fixed { delay } => *delay > 0,
+            RetryPolicy::Exponential {
+                base_delay,
+                max_delay,
+            } => *base_delay > 0 && max_delay >= base_delay,
+            RetryPolicy::Offsets { offsets } => {
+                !offsets.is_empty() && offsets[0] > 0 && offsets.windows(2).all(|w| w[0] < w[1])
+            }
+        }
+    }
+
+    // timestamp of the retry after `failure_count` failed attempts of the cycle due at `due_ts`
+    pub fn next_attempt_ts(&self, due_ts: i64, now: i64, failure_count: u8) -> Option<i64> {
+        let attempt = failure_count.checked_sub(1)? as u32;
+
+        match self {
+            RetryPolicy::Fixed { delay } => now.checked_add(*delay),
+            RetryPolicy::Exponential {
+                base_delay,
+                max_delay,
+            } => {
+                let delay = 2i64
+                    .checked_pow(attempt)
+                    .and_then(|factor| base_delay.checked_mul(factor))
+                    .map_or(*max_delay, |delay| delay.min(*max_delay));
+
+                now.checked_add(delay)
+            }
+            RetryPolicy::Offsets { offsets } => offsets
+                .get(attempt as usize)
+                .and_then(|offset| due_ts.checked_add(*offset))
+                .map(|ts| ts.max(now)),
+        }
+    }
+}
+
+// usage based pricing, `amount` stays the base fee of every cycle
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub struct Metering {
+    pub oracle: Pubkey, // only key allowed to report usage against subscriptions of the plan
+    pub unit_price: u64,
+}
+
+// share of every payout sent to a recipient token account, shares of a plan sum to 10_000 bps
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub struct PayoutShare {
+    pub recipient_ata: Pubkey,
+    pub bps: u16,
+}
+
 #[derive(InitSpace)]
 #[account]
 pub struct SubscriptionPlan {
@@ -9,13 +160,112 @@ pub struct SubscriptionPlan {
     pub amount: u64,
     pub active: bool,
     pub max_failure_count: u8,
+    pub retry_policy: RetryPolicy,
+    pub grace_period: i64, // seconds after the due date a subscription stays past-due, 0 for no limit
+    pub proration_enabled: bool, // credit unused time when subscribers change plans
     #[max_len(50)]
     pub name: String,
-    pub interval: i64,
+    pub interval: BillingInterval,
+    pub trial_period: i64, // seconds before the first charge, 0 for no trial
+    pub intro_amount: u64,
+    pub intro_cycles: u16, // number of cycles charged at `intro_amount`
+    pub subscriber_count: u32,
+    pub pending_amount: Option<u64>, // scheduled price change, see `schedule_price_change`
+    pub pending_amount_effective_ts: i64,
+    pub charge_seq: u64, // sequence number of the next charge, refunds refer to it
+    pub metering: Option<Metering>, // set for metered plans
+    #[max_len(MAX_PAYOUT_RECIPIENTS)]
+    pub payout_split: Vec<PayoutShare>, // empty when `merchant_ata` receives every payout
+    pub batch_billing: bool, // charged by `charge_batch` only, no task is queued per subscriber
     pub bump: u8,
 }
 
+impl SubscriptionPlan {
+    // price of a cycle starting at `cycle_start_ts`, a scheduled price change only applies to
+    // cycles that start after it became effective
+    pub fn amount_at(&self, cycle_start_ts: i64) -> u64 {
+        match self.pending_amount {
+            Some(amount) if cycle_start_ts >= self.pending_amount_effective_ts => amount,
+            _ => self.amount,
+        }
+    }
+
+    // amount and pricing tier for a subscriber that has already paid `cycles_charged` cycles
+    pub fn charge_amount(&self, cycles_charged: u32, cycle_start_ts: i64) -> (u64, PricingTier) {
+        if cycles_charged < self.intro_cycles as u32 {
+            (self.intro_amount, PricingTier::Introductory)
+        } else {
+            (self.amount_at(cycle_start_ts), PricingTier::Standard)
+        }
+    }
+
+    // tier of the cycle a subscription is currently in
+    pub fn current_tier(&self, cycles_charged: u32) -> PricingTier {
+        if cycles_charged == 0 && self.trial_period > 0 {
+            return PricingTier::Trial;
+        }
+
+        // without a trial the first cycle is reported before its charge has run
+        self.charge_amount(cycles_charged.saturating_sub(1), 0).1
+    }
+
+    // base fee plus the reported usage, never more than the subscriber's spending cap
+    pub fn metered_amount(
+        &self,
+        base: u64,
+        usage_units: u64,
+        spending_cap: Option<u64>,
+    ) -> Option<u64> {
+        let usage = match &self.metering {
+            Some(metering) => metering.unit_price.checked_mul(usage_units)?,
+            None => 0,
+        };
+        let total = base.checked_add(usage)?;
+
+        Some(spending_cap.map_or(total, |cap| total.min(cap)))
+    }
+
+    // what the plan is approved to pull for `cycles` cycles in delegated billing mode, priced at
+    // the spending cap if there is one, otherwise at the latest scheduled amount
+    pub fn allowance(&self, cycles: u16, spending_cap: Option<u64>) -> Option<u64> {
+        spending_cap
+            .unwrap_or(self.amount_at(i64::MAX))
+            .checked_mul(cycles as u64)
+    }
+
+    // when the cycle due at `due_ts` is retried after `failure_count` failed attempts, `None` once
+    // the retries ran out or the next one would fall after the grace period
+    pub fn retry_ts(&self, due_ts: i64, now: i64, failure_count: u8) -> Option<i64> {
+        if failure_count > self.max_failure_count {
+            return None;
+        }
+
+        self.retry_policy
+            .next_attempt_ts(due_ts, now, failure_count)
+            .filter(|ts| self.grace_period == 0 || due_ts.saturating_add(self.grace_period) >= *ts)
+    }
+
+    // sequence number for a new charge on this plan
+    pub fn next_charge_seq(&mut self) -> Option<u64> {
+        let seq = self.charge_seq;
+        self.charge_seq = seq.checked_add(1)?;
+        Some(seq)
+    }
+
+    // moves an already effective price change into `amount`
+    pub fn apply_pending_amount(&mut self, now: i64) {
+        if let Some(amount) = self.pending_amount {
+            if now >= self.pending_amount_effective_ts {
+                self.amount = amount;
+                self.pending_amount = None;
+                self.pending_amount_effective_ts = 0;
+            }
+        }
+    }
+}
+
 pub const PLAN_SEED: &[u8] = b"plan";
 pub const VAULT_SEED: &[u8] = b"fees_vault";
+pub const FEE_TOKEN_VAULT_SEED: &[u8] = b"fee_token_vault"; // one per accepted mint
+pub const MAX_PAYOUT_RECIPIENTS: usize = 5;
 pub const FEES: u64 = 10_000_000; // 0.01 SOL per subscription, for now no automation charges (maybe add in future)
-pub const USDC_PUBKEY: Pubkey = pubkey!("4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU");
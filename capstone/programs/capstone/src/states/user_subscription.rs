@@ -1,10 +1,48 @@
 use anchor_lang::prelude::*;
 
-#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, PartialEq)]
+use crate::states::{ChargeReceipt, Discount, PricingTier, SubscriptionPlan, LEDGER_SEED};
+
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Debug, PartialEq)]
 pub enum Status {
     Active,
+    PastDue, // last charge failed, retries are scheduled
+    Paused,  // paused by the subscriber, queued charges skip their cycle
     Failed,
-    Canceled, // todo: future
+    ReserveDepleted, // crank reserve ran out, no task is queued until it is funded again
+    Canceled,        // canceled at period end, the last queued task closes the account
+}
+
+#[derive(InitSpace, AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum BillingMode {
+    Vault, // charges are paid from the subscriber vault PDA
+    // charges are pulled from the subscriber ata, the plan is its approved token delegate
+    Delegated { approved_cycles: u16 },
+}
+
+// what the next charge of a subscription takes, see `UserSubscription::quote`
+pub struct ChargeQuote {
+    pub amount: u64, // base price of the cycle after the coupon discount
+    pub total: u64,  // with the metered usage, within the spending cap
+    pub due: u64,    // what is transferred once the prorated credit is used up
+    pub tier: PricingTier,
+    pub usage_units: u64,
+}
+
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum BatchChargeOutcome {
+    Charged,
+    NotDue,
+    InsufficientFunds, // a retry is set in `retry_at`, or the subscription failed
+    Skipped,           // not chargeable, e.g. paused, failed, closed or a paused plan
+}
+
+// reason code of `SubscriptionTerminatedEvent`, given by the merchant or set by the cleanup crank
+#[derive(AnchorDeserialize, AnchorSerialize, Clone, Copy, Debug, PartialEq)]
+pub enum TerminationReason {
+    PlanRetiring,
+    Abuse,
+    Other,
+    PaymentFailed, // always used by `close_failed_subscription`
 }
 
 #[derive(InitSpace)]
@@ -13,13 +51,126 @@ pub struct UserSubscription {
     pub subscriber: Pubkey,
     pub subscriber_ata: Pubkey,
     pub subscription: Pubkey,
+    pub billing_mode: BillingMode,
     pub status: Status,
     pub failure_count: u8,
     pub last_exec_ts: i64,
+    pub resume_at: Option<i64>,    // only set while paused
+    pub access_until: Option<i64>, // only set once canceled at period end
+    pub retry_at: Option<i64>, // next attempt of a past due cycle, only kept on batch billed plans
+    pub cycles_charged: u32,
+    pub last_charge_amount: u64, // full price of the current cycle, used for proration
+    pub credit: u64,             // prorated credit applied to the next charges
+    pub usage_units: u64, // metered usage reported since the last charge, billed with the next one
+    pub spending_cap: Option<u64>, // most a single charge may take, required for metered plans
+    pub discount: Option<Discount>, // copied from the redeemed coupon
+    pub discount_cycles: u16, // charged cycles the discount still applies to
+    pub crank_reserve: u64, // lamports held by this account to pay the crank reward of each task
+    pub depleted_status: Option<Status>, // status to restore once a depleted reserve is funded
     pub next_task_id: u16,
+    pub task_queue: Pubkey, // queue holding the charge task, may differ from the global one after a swap
+    // index of the receipt the next charge writes, starts at the plan's charge sequence so a
+    // resubscription never reuses the receipt address of an earlier one
+    pub next_receipt: u64,
+    pub ledger_bump: Option<u8>, // set once the subscriber created a billing ledger
     pub subscriber_vault_bump: u8,
     pub bump: u8,
 }
 
+impl UserSubscription {
+    // whether a charge task for this subscription is still in the tuktuk queue, subscriptions of
+    // batch billed plans never have one
+    pub fn has_queued_task(&self, plan: &SubscriptionPlan) -> bool {
+        !plan.batch_billing
+            && matches!(
+                self.status,
+                Status::Active | Status::PastDue | Status::Paused | Status::Canceled
+            )
+    }
+
+    // address of the billing ledger, if the subscriber created one
+    pub fn billing_ledger(&self, user_subscription: &Pubkey) -> Option<Pubkey> {
+        let bump = self.ledger_bump?;
+
+        Pubkey::create_program_address(
+            &[LEDGER_SEED, user_subscription.as_ref(), &[bump]],
+            &crate::ID,
+        )
+        .ok()
+    }
+
+    // receipt the next charge writes, passed to the charge task before it exists
+    pub fn next_receipt_address(&self, user_subscription: &Pubkey) -> Pubkey {
+        ChargeReceipt::address(user_subscription, self.next_receipt).0
+    }
+
+    // whether the subscriber should have access to the service right now
+    pub fn is_entitled(&self, now: i64) -> bool {
+        match self.status {
+            // charges are kept up to date by the task chain, retries are still running
+            Status::Active | Status::PastDue => true,
+            Status::Paused | Status::ReserveDepleted => now < self.last_exec_ts,
+            Status::Canceled => self.access_until.is_some_and(|ts| now < ts),
+            Status::Failed => false,
+        }
+    }
+
+    // price of the cycle starting at `cycle_start_ts`, usage of the cycle before it is billed in
+    // arrears with the base fee
+    pub fn quote(&self, plan: &SubscriptionPlan, cycle_start_ts: i64) -> Option<ChargeQuote> {
+        let (amount, tier) = plan.charge_amount(self.cycles_charged, cycle_start_ts);
+        let amount = self.discounted(amount);
+        let total = plan.metered_amount(amount, self.usage_units, self.spending_cap)?;
+
+        Some(ChargeQuote {
+            amount,
+            total,
+            due: total.saturating_sub(self.credit),
+            tier,
+            usage_units: self.usage_units,
+        })
+    }
+
+    // books a paid cycle, the next one is due at `next_exec_ts`
+    pub fn record_charge(&mut self, quote: &ChargeQuote, next_exec_ts: i64) -> Option<()> {
+        self.credit = self.credit.saturating_sub(quote.total);
+        self.last_charge_amount = quote.amount;
+        self.usage_units = 0;
+        self.use_discount_cycle();
+        self.failure_count = 0;
+        self.retry_at = None;
+        self.status = Status::Active;
+        self.cycles_charged = self.cycles_charged.checked_add(1)?;
+        self.last_exec_ts = next_exec_ts;
+
+        Some(())
+    }
+
+    // base price of the next charge after a coupon discount
+    pub fn discounted(&self, amount: u64) -> u64 {
+        match self.discount {
+            Some(discount) if self.discount_cycles > 0 => discount.apply(amount),
+            _ => amount,
+        }
+    }
+
+    // called after every paid cycle, the discount is dropped once it ran out
+    pub fn use_discount_cycle(&mut self) {
+        self.discount_cycles = self.discount_cycles.saturating_sub(1);
+
+        if self.discount_cycles == 0 {
+            self.discount = None;
+        }
+    }
+
+    // ends a pause, a cycle that is already paid for keeps its due date, otherwise billing
+    // restarts from `now`
+    pub fn resume(&mut self, now: i64) {
+        self.status = Status::Active;
+        self.resume_at = None;
+        self.last_exec_ts = self.last_exec_ts.max(now);
+    }
+}
+
 pub const SUBSCRIPTION_SEED: &[u8] = b"subscription";
 pub const SUBSCRIBER_VAULT_SEED: &[u8] = b"user_vault";
@@ -0,0 +1,345 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{instruction::Instruction, program_option::COption},
+    system_program::{allocate, assign, Allocate, Assign},
+    InstructionData,
+};
+use anchor_spl::token_interface::{
+    approve_checked, revoke, transfer_checked, ApproveChecked, Revoke, TokenAccount,
+    TransferChecked,
+};
+use tuktuk_program::{
+    compile_transaction,
+    tuktuk::cpi::{
+        accounts::{DequeueTaskV0, QueueTaskV0},
+        dequeue_task_v0, queue_task_v0,
+    },
+    types::QueueTaskArgsV0,
+    TransactionSourceV0, TriggerV0,
+};
+
+use crate::{
+    error::SubscriptionError,
+    states::{
+        BillingMode, ChargeReceipt, PayoutShare, UserSubscription, QUEUE_AUTHORITY_SEED,
+        RECEIPT_SEED,
+    },
+};
+
+// instruction tuktuk runs for every cycle of a subscription, the recipients of the plan's payout
+// split follow as remaining accounts
+pub fn charge_instruction(
+    accounts: crate::accounts::ChargeUserRecurring,
+    payout_split: &[PayoutShare],
+) -> Instruction {
+    let mut metas = accounts.to_account_metas(None);
+    metas.extend(
+        payout_split
+            .iter()
+            .map(|share| AccountMeta::new(share.recipient_ata, false)),
+    );
+
+    Instruction {
+        program_id: crate::ID,
+        accounts: metas,
+        data: crate::instruction::ChargeUserRecurring.data(),
+    }
+}
+
+// recipient token accounts of a payout split, expected in order as the first remaining accounts
+pub fn payout_accounts<'info>(
+    payout_split: &[PayoutShare],
+    remaining_accounts: &[AccountInfo<'info>],
+) -> Option<Vec<(AccountInfo<'info>, u16)>> {
+    if remaining_accounts.len() < payout_split.len() {
+        return None;
+    }
+
+    payout_split
+        .iter()
+        .zip(remaining_accounts)
+        .map(|(share, info)| {
+            (info.key() == share.recipient_ata && info.is_writable)
+                .then(|| (info.clone(), share.bps))
+        })
+        .collect()
+}
+
+// what a charge can take right now, in delegated mode this is capped by the allowance of `plan`
+pub fn chargeable_balance(
+    billing_mode: BillingMode,
+    subscriber_vault: &TokenAccount,
+    subscriber_ata: &TokenAccount,
+    plan: &Pubkey,
+) -> u64 {
+    match billing_mode {
+        BillingMode::Vault => subscriber_vault.amount,
+        BillingMode::Delegated { .. } => {
+            if subscriber_ata.delegate != COption::Some(*plan) {
+                return 0;
+            }
+
+            subscriber_ata.amount.min(subscriber_ata.delegated_amount)
+        }
+    }
+}
+
+// queues the first task of a charge chain, every later task is returned by `charge_user_recurring`
+// and paid for out of the subscription's crank reserve
+pub fn queue_charge_task<'info>(
+    tuktuk_program: AccountInfo<'info>,
+    accounts: QueueTaskV0<'info>,
+    queue_authority_bump: u8,
+    task_id: u16,
+    trigger: TriggerV0,
+    crank_reward: u64,
+    charge: Instruction, // see `charge_instruction`
+) -> Result<()> {
+    let (compiled_tx, _) = compile_transaction(vec![charge], vec![])?;
+
+    let signer_seeds: &[&[&[u8]]] = &[&[QUEUE_AUTHORITY_SEED, &[queue_authority_bump]]];
+
+    let ctx = CpiContext::new_with_signer(tuktuk_program, accounts, signer_seeds);
+
+    queue_task_v0(
+        ctx,
+        QueueTaskArgsV0 {
+            id: task_id,
+            trigger,
+            transaction: TransactionSourceV0::CompiledV0(compiled_tx),
+            crank_reward: Some(crank_reward),
+            free_tasks: 15, // this is for recursion, this task will queue one more task
+            description: "payment for subscription".to_string(),
+        },
+    )
+}
+
+// removes a queued charge task, used when a subscription stops or moves to a new task
+pub fn dequeue_charge_task<'info>(
+    tuktuk_program: AccountInfo<'info>,
+    accounts: DequeueTaskV0<'info>,
+    queue_authority_bump: u8,
+) -> Result<()> {
+    let signer_seeds: &[&[&[u8]]] = &[&[QUEUE_AUTHORITY_SEED, &[queue_authority_bump]]];
+
+    let ctx = CpiContext::new_with_signer(tuktuk_program, accounts, signer_seeds);
+
+    dequeue_task_v0(ctx)
+}
+
+// pays a charge to the merchant, `fee` of it goes to the protocol fee token vault instead, with
+// `payees` set the rest is split between them instead of going to `accounts.to`
+#[allow(clippy::too_many_arguments)]
+pub fn pay_merchant<'info>(
+    token_program: AccountInfo<'info>,
+    accounts: TransferChecked<'info>,
+    fee_token_vault: AccountInfo<'info>,
+    payees: &[(AccountInfo<'info>, u16)],
+    signer_seeds: &[&[&[u8]]],
+    amount: u64,
+    fee: u64,
+    decimals: u8,
+) -> Result<()> {
+    if fee > 0 {
+        let ctx = CpiContext::new_with_signer(
+            token_program.clone(),
+            TransferChecked {
+                from: accounts.from.clone(),
+                mint: accounts.mint.clone(),
+                to: fee_token_vault,
+                authority: accounts.authority.clone(),
+            },
+            signer_seeds,
+        );
+
+        transfer_checked(ctx, fee, decimals)?;
+    }
+
+    let payout = amount - fee;
+
+    if payees.is_empty() {
+        let ctx = CpiContext::new_with_signer(token_program, accounts, signer_seeds);
+
+        return transfer_checked(ctx, payout, decimals);
+    }
+
+    // shares are rounded down, the first recipient gets the remainder
+    let mut shares: Vec<u64> = payees
+        .iter()
+        .map(|(_, bps)| (payout as u128 * *bps as u128 / 10_000) as u64)
+        .collect();
+    shares[0] += payout - shares.iter().sum::<u64>();
+
+    for ((recipient, _), share) in payees.iter().zip(shares) {
+        if share == 0 {
+            continue;
+        }
+
+        let ctx = CpiContext::new_with_signer(
+            token_program.clone(),
+            TransferChecked {
+                from: accounts.from.clone(),
+                mint: accounts.mint.clone(),
+                to: recipient.clone(),
+                authority: accounts.authority.clone(),
+            },
+            signer_seeds,
+        );
+
+        transfer_checked(ctx, share, decimals)?;
+    }
+
+    Ok(())
+}
+
+// lets the plan pull charges from the subscriber ata. The ata has a single delegate, an allowance
+// still held by anyone but the plan itself or the plan it `replaces` is never taken over
+pub fn approve_plan<'info>(
+    token_program: AccountInfo<'info>,
+    accounts: ApproveChecked<'info>,
+    subscriber_ata: &TokenAccount,
+    replaces: Option<Pubkey>,
+    allowance: u64,
+    decimals: u8,
+) -> Result<()> {
+    if let COption::Some(delegate) = subscriber_ata.delegate {
+        require!(
+            subscriber_ata.delegated_amount == 0
+                || delegate == accounts.delegate.key()
+                || Some(delegate) == replaces,
+            SubscriptionError::AtaAlreadyDelegated
+        );
+    }
+
+    let ctx = CpiContext::new(token_program, accounts);
+
+    approve_checked(ctx, allowance, decimals)
+}
+
+// what is left of the approval of `plan` over the subscriber ata, zero once the ata is closed
+pub fn remaining_allowance(subscriber_ata: &AccountInfo, plan: &Pubkey) -> u64 {
+    let Ok(data) = subscriber_ata.try_borrow_data() else {
+        return 0;
+    };
+
+    TokenAccount::try_deserialize(&mut &data[..])
+        .ok()
+        .filter(|ata| ata.delegate == COption::Some(*plan))
+        .map_or(0, |ata| ata.delegated_amount)
+}
+
+// removes the approval of `plan` over the subscriber ata, an approval of another plan is kept
+pub fn revoke_plan<'info>(
+    token_program: AccountInfo<'info>,
+    subscriber_ata: &InterfaceAccount<'info, TokenAccount>,
+    subscriber: AccountInfo<'info>,
+    plan: &Pubkey,
+) -> Result<()> {
+    if subscriber_ata.delegate != COption::Some(*plan) {
+        return Ok(());
+    }
+
+    let ctx = CpiContext::new(
+        token_program,
+        Revoke {
+            source: subscriber_ata.to_account_info(),
+            authority: subscriber,
+        },
+    );
+
+    revoke(ctx)
+}
+
+// least a crank reserve has to hold for the next charge, the reward of the task queued after it
+// and the rent of its receipt
+pub fn min_crank_reserve(crank_reward: u64) -> Result<u64> {
+    Ok(crank_reward.saturating_add(receipt_rent()?))
+}
+
+pub fn receipt_rent() -> Result<u64> {
+    Ok(
+        Rent::get()?
+            .minimum_balance(ChargeReceipt::DISCRIMINATOR.len() + ChargeReceipt::INIT_SPACE),
+    )
+}
+
+// charges run by the crank have no signer to pay the receipt, its rent comes out of the
+// subscription account and is taken from the crank reserve first
+pub fn pay_receipt_rent<'info>(
+    user_subscription: &mut Account<'info, UserSubscription>,
+    charge_receipt: &AccountInfo<'info>,
+    rent: u64,
+) -> Result<()> {
+    user_subscription.crank_reserve = user_subscription.crank_reserve.saturating_sub(rent);
+    user_subscription.sub_lamports(rent)?;
+    charge_receipt.add_lamports(rent)?;
+
+    Ok(())
+}
+
+// writes the receipt of a charge the subscription just paid and numbers the next one, the
+// receipt account must already hold its rent
+pub fn create_charge_receipt<'info>(
+    charge_receipt: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    user_subscription: &mut Account<'info, UserSubscription>,
+    charge_seq: u64,
+    amount: u64,
+) -> Result<Pubkey> {
+    let user_subscription_key = user_subscription.key();
+    let index = user_subscription.next_receipt;
+    let (address, bump) = ChargeReceipt::address(&user_subscription_key, index);
+    require_keys_eq!(
+        charge_receipt.key(),
+        address,
+        SubscriptionError::InvalidReceipt
+    );
+
+    let index_bytes = index.to_le_bytes();
+    let signer_seeds: &[&[&[u8]]] = &[&[
+        RECEIPT_SEED,
+        user_subscription_key.as_ref(),
+        &index_bytes,
+        &[bump],
+    ]];
+    let space = ChargeReceipt::DISCRIMINATOR.len() + ChargeReceipt::INIT_SPACE;
+
+    allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            Allocate {
+                account_to_allocate: charge_receipt.clone(),
+            },
+            signer_seeds,
+        ),
+        space as u64,
+    )?;
+    assign(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            Assign {
+                account_to_assign: charge_receipt.clone(),
+            },
+            signer_seeds,
+        ),
+        &crate::ID,
+    )?;
+
+    let receipt = ChargeReceipt {
+        subscription_plan: user_subscription.subscription,
+        subscriber: user_subscription.subscriber,
+        subscriber_ata: user_subscription.subscriber_ata,
+        charge_seq,
+        amount,
+        refunded: 0,
+        charged_at: Clock::get()?.unix_timestamp,
+        bump,
+    };
+    receipt.try_serialize(&mut &mut charge_receipt.try_borrow_mut_data()?[..])?;
+
+    user_subscription.next_receipt = index
+        .checked_add(1)
+        .ok_or(SubscriptionError::ArithmeticError)?;
+
+    Ok(address)
+}
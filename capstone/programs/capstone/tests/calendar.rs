@@ -0,0 +1,299 @@
+//! Properties of the calendar date math, checked for every day (at a varying time of day) of
+//! more than a full 400-year gregorian cycle rather than sampled at random.
+
+use capstone::{
+    calendar::{
+        add_months, anchor_in_month, civil_from_days, date_of, days_from_civil, days_in_month,
+        is_leap_year, next_monthly, next_yearly, previous_monthly, previous_yearly,
+        SECONDS_PER_DAY,
+    },
+    states::BillingInterval,
+};
+
+const DAY: i64 = SECONDS_PER_DAY;
+
+// 1600-01-01 to 2401-01-01, covers 1600 and 2000 (leap) and 1700, 1800, 1900, 2100 (common)
+fn sweep_days() -> std::ops::Range<i64> {
+    days_from_civil(1600, 1, 1)..days_from_civil(2401, 1, 1)
+}
+
+// every day of the sweep at a pseudo random time of day, including midnight
+fn sweep_timestamps(step: usize) -> impl Iterator<Item = i64> {
+    sweep_days().step_by(step).map(|days| match days % 5 {
+        0 => days * DAY,
+        _ => days * DAY + (days * 7_919).rem_euclid(DAY),
+    })
+}
+
+fn ymd(year: i64, month: u8, day: u8) -> i64 {
+    days_from_civil(year, month, day) * DAY
+}
+
+#[test]
+fn days_and_dates_round_trip() {
+    let mut expected = civil_from_days(sweep_days().start);
+    assert_eq!(expected, (1600, 1, 1));
+
+    for days in sweep_days() {
+        let date = civil_from_days(days);
+        assert_eq!(date, expected, "day {days}");
+        assert_eq!(days_from_civil(date.0, date.1, date.2), days);
+
+        // the next day is one day later in the same month, or the first of the next one
+        let (year, month, day) = date;
+        expected = if day < days_in_month(year, month) {
+            (year, month, day + 1)
+        } else if month < 12 {
+            (year, month + 1, 1)
+        } else {
+            (year + 1, 1, 1)
+        };
+    }
+
+    assert_eq!(civil_from_days(0), (1970, 1, 1));
+    assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    assert_eq!(date_of(-1), (1969, 12, 31));
+}
+
+#[test]
+fn leap_years_have_a_29th_of_february() {
+    for year in 1600..=2400 {
+        let length = days_from_civil(year + 1, 1, 1) - days_from_civil(year, 1, 1);
+
+        assert_eq!(length, if is_leap_year(year) { 366 } else { 365 });
+        assert_eq!(days_in_month(year, 2) == 29, is_leap_year(year));
+    }
+
+    assert!(is_leap_year(2000) && is_leap_year(2024));
+    assert!(!is_leap_year(1900) && !is_leap_year(2100) && !is_leap_year(2025));
+}
+
+#[test]
+fn next_monthly_is_the_first_anchor_after() {
+    for anchor_day in 1..=31 {
+        for ts in sweep_timestamps(7) {
+            let next = next_monthly(ts, anchor_day).unwrap();
+            let (year, month, day) = date_of(next);
+
+            assert!(next > ts);
+            assert_eq!(next.rem_euclid(DAY), 0, "charges at midnight");
+            assert_eq!(day, anchor_day.min(days_in_month(year, month)));
+            assert!(next - ts <= 31 * DAY);
+
+            // no anchor is skipped
+            assert!(previous_monthly(next, anchor_day).unwrap() <= ts);
+        }
+    }
+}
+
+#[test]
+fn previous_monthly_is_the_last_anchor_before() {
+    for anchor_day in 1..=31 {
+        for ts in sweep_timestamps(7) {
+            let previous = previous_monthly(ts, anchor_day).unwrap();
+
+            assert!(previous < ts);
+            assert!(next_monthly(previous, anchor_day).unwrap() >= ts);
+            assert_eq!(
+                next_monthly(previous, anchor_day),
+                Some(anchor_after(previous, anchor_day))
+            );
+        }
+    }
+}
+
+// the anchor in the month after the one of `ts`
+fn anchor_after(ts: i64, anchor_day: u8) -> i64 {
+    let (year, month, _) = date_of(ts);
+    let (year, month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+
+    anchor_in_month(year, month, anchor_day).unwrap()
+}
+
+#[test]
+fn monthly_charges_do_not_drift_after_short_months() {
+    for anchor_day in 1..=31 {
+        let start = anchor_in_month(1600, 1, anchor_day).unwrap();
+        let mut due = start;
+
+        // 400 years of charges, each one in the next month on the anchor day or its last day
+        for cycle in 1..=4_800i64 {
+            due = next_monthly(due, anchor_day).unwrap();
+
+            let (year, month, day) = date_of(due);
+            assert_eq!((year - 1600) * 12 + month as i64 - 1, cycle);
+            assert_eq!(day, anchor_day.min(days_in_month(year, month)));
+            assert_eq!(previous_monthly(due, anchor_day), {
+                let (year, month) = if month == 1 {
+                    (year - 1, 12)
+                } else {
+                    (year, month - 1)
+                };
+                anchor_in_month(year, month, anchor_day)
+            });
+        }
+
+        // the gregorian calendar repeats every 146_097 days
+        assert_eq!(due - start, 146_097 * DAY);
+    }
+
+    // anchored on the 31st: leap february, then back to the 31st
+    let mut due = ymd(2024, 1, 31);
+    let expected = [
+        ymd(2024, 2, 29),
+        ymd(2024, 3, 31),
+        ymd(2024, 4, 30),
+        ymd(2024, 5, 31),
+    ];
+    for expected in expected {
+        due = next_monthly(due, 31).unwrap();
+        assert_eq!(due, expected);
+    }
+    assert_eq!(next_monthly(ymd(2025, 1, 31), 31), Some(ymd(2025, 2, 28)));
+    assert_eq!(next_monthly(ymd(2025, 2, 28), 31), Some(ymd(2025, 3, 31)));
+}
+
+#[test]
+fn yearly_anchors_on_the_29th_of_february_follow_leap_years() {
+    let mut due = ymd(1600, 2, 29);
+
+    for year in 1601..=2400 {
+        let next = next_yearly(due, 2, 29).unwrap();
+        let expected = if is_leap_year(year) { 29 } else { 28 };
+
+        assert_eq!(date_of(next), (year, 2, expected));
+        assert!(next - due == 365 * DAY || next - due == 366 * DAY);
+        assert_eq!(previous_yearly(next, 2, 29), Some(due));
+
+        due = next;
+    }
+
+    assert_eq!(next_yearly(ymd(2024, 2, 29), 2, 29), Some(ymd(2025, 2, 28)));
+    assert_eq!(next_yearly(ymd(2027, 2, 28), 2, 29), Some(ymd(2028, 2, 29)));
+}
+
+#[test]
+fn next_yearly_is_the_first_anchor_after() {
+    for (anchor_month, anchor_day) in [(1, 1), (2, 28), (2, 29), (6, 30), (12, 31)] {
+        for ts in sweep_timestamps(3) {
+            let next = next_yearly(ts, anchor_month, anchor_day).unwrap();
+            let (year, month, day) = date_of(next);
+
+            assert!(next > ts);
+            assert_eq!(next.rem_euclid(DAY), 0);
+            assert_eq!(month, anchor_month);
+            assert_eq!(day, anchor_day.min(days_in_month(year, month)));
+            assert!(next - ts <= 366 * DAY);
+            assert!(previous_yearly(next, anchor_month, anchor_day).unwrap() <= ts);
+        }
+    }
+}
+
+#[test]
+fn add_months_keeps_the_day_and_time() {
+    for ts in sweep_timestamps(1) {
+        let (year, month, day) = date_of(ts);
+        let later = add_months(ts, 1).unwrap();
+        let (next_year, next_month, next_day) = date_of(later);
+
+        assert_eq!(later.rem_euclid(DAY), ts.rem_euclid(DAY));
+        assert_eq!(
+            (next_year * 12 + next_month as i64) - (year * 12 + month as i64),
+            1
+        );
+        assert_eq!(next_day, day.min(days_in_month(next_year, next_month)));
+
+        // 400 years later is the same date
+        assert_eq!(add_months(ts, 4_800), Some(ts + 146_097 * DAY));
+    }
+
+    assert_eq!(add_months(ymd(2024, 2, 29), 12), Some(ymd(2025, 2, 28)));
+    assert_eq!(add_months(ymd(2023, 12, 31), 2), Some(ymd(2024, 2, 29)));
+}
+
+#[test]
+fn billing_intervals() {
+    let now = ymd(2026, 1, 31) + 3_600;
+
+    let seconds = BillingInterval::Seconds { seconds: 120 };
+    assert_eq!(seconds.next_charge_ts(now), Some(now + 120));
+    assert_eq!(seconds.previous_charge_ts(now), Some(now - 120));
+    assert_eq!(seconds.one_interval_after(now), Some(now + 120));
+
+    let days = BillingInterval::Days { days: 30 };
+    assert_eq!(days.next_charge_ts(now), Some(now + 30 * DAY));
+    assert_eq!(days.previous_charge_ts(now), Some(now - 30 * DAY));
+
+    let monthly = BillingInterval::CalendarMonth { anchor_day: 31 };
+    assert_eq!(monthly.next_charge_ts(now), Some(ymd(2026, 2, 28)));
+    assert_eq!(
+        monthly.previous_charge_ts(ymd(2026, 2, 28)),
+        Some(ymd(2026, 1, 31))
+    );
+    assert_eq!(
+        monthly.one_interval_after(now),
+        Some(ymd(2026, 2, 28) + 3_600)
+    );
+
+    let yearly = BillingInterval::CalendarYear {
+        anchor_month: 2,
+        anchor_day: 29,
+    };
+    assert_eq!(yearly.next_charge_ts(now), Some(ymd(2026, 2, 28)));
+    assert_eq!(
+        yearly.one_interval_after(now),
+        Some(ymd(2027, 1, 31) + 3_600)
+    );
+
+    // overflowing intervals fail instead of wrapping
+    assert_eq!(days.next_charge_ts(i64::MAX - DAY), None);
+    assert_eq!(seconds.previous_charge_ts(i64::MIN), None);
+}
+
+#[test]
+fn invalid_billing_intervals() {
+    let valid = [
+        BillingInterval::Seconds { seconds: 1 },
+        BillingInterval::Days { days: 1 },
+        BillingInterval::CalendarMonth { anchor_day: 1 },
+        BillingInterval::CalendarMonth { anchor_day: 31 },
+        BillingInterval::CalendarYear {
+            anchor_month: 2,
+            anchor_day: 29,
+        },
+        BillingInterval::CalendarYear {
+            anchor_month: 12,
+            anchor_day: 31,
+        },
+    ];
+    let invalid = [
+        BillingInterval::Seconds { seconds: 0 },
+        BillingInterval::Seconds { seconds: -60 },
+        BillingInterval::Days { days: 0 },
+        BillingInterval::CalendarMonth { anchor_day: 0 },
+        BillingInterval::CalendarMonth { anchor_day: 32 },
+        BillingInterval::CalendarYear {
+            anchor_month: 0,
+            anchor_day: 1,
+        },
+        BillingInterval::CalendarYear {
+            anchor_month: 13,
+            anchor_day: 1,
+        },
+        BillingInterval::CalendarYear {
+            anchor_month: 2,
+            anchor_day: 30,
+        },
+        BillingInterval::CalendarYear {
+            anchor_month: 4,
+            anchor_day: 31,
+        },
+    ];
+
+    assert!(valid.iter().all(BillingInterval::is_valid));
+    assert!(!invalid.iter().any(BillingInterval::is_valid));
+}
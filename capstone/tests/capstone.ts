@@ -56,6 +56,9 @@ describe("capstone", () => {
     "BPFLoaderUpgradeab1e11111111111111111111111"
   );
 
+  // lamports for the crank rewards and receipts of the next 10 charges
+  const crankReserve = new anchor.BN(20_000_000);
+
   const USDC_MINT = new anchor.web3.PublicKey(
     "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v"
   );
@@ -77,6 +80,11 @@ describe("capstone", () => {
     program.programId
   );
 
+  const [acceptedMintsPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("accepted_mints")],
+    program.programId
+  );
+
   const tuktukConfig = tuktukConfigKey()[0];
 
   const programDataAccount = anchor.web3.PublicKey.findProgramAddressSync(
@@ -112,8 +120,17 @@ describe("capstone", () => {
       program.programId
     );
 
+  const [subscriber1LedgerPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("ledger"), subscriber1SubscriptionPda.toBuffer()],
+    program.programId
+  );
+
   const [subscriber1VaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("vault"), subscriber1.publicKey.toBuffer()],
+    [
+      Buffer.from("user_vault"),
+      subscriber1.publicKey.toBuffer(),
+      USDC_MINT.toBuffer(),
+    ],
     program.programId
   );
 
@@ -216,6 +233,158 @@ describe("capstone", () => {
     } else {
       taskQueue = globalState.taskQueue;
     }
+
+    const acceptedMints = await program.account.acceptedMints.fetch(
+      acceptedMintsPda
+    );
+
+    if (!acceptedMints.mints.some((mint) => mint.equals(USDC_MINT))) {
+      await program.methods
+        .addAcceptedMint()
+        .accounts({
+          signer,
+          mint: USDC_MINT,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+    }
+  });
+
+  describe("accepted mints", () => {
+    it("should fail to add a mint that is already accepted", async () => {
+      try {
+        await program.methods
+          .addAcceptedMint()
+          .accounts({
+            signer,
+            mint: USDC_MINT,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+        assert.fail("Transaction should have failed with MintAlreadyAccepted");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("MintAlreadyAccepted");
+      }
+    });
+  });
+
+  describe("config", () => {
+    it("admin can update the protocol fee", async () => {
+      await program.methods
+        .updateConfig({
+          fees: null,
+          protocolFeeBps: 100,
+          crankReward: null,
+          paused: null,
+        })
+        .accounts({ signer })
+        .rpc();
+
+      const globalState = await program.account.globalState.fetch(
+        globalStatePda
+      );
+      assert.equal(globalState.protocolFeeBps, 100);
+    });
+
+    it("should fail to set a protocol fee above 10000 bps", async () => {
+      try {
+        await program.methods
+          .updateConfig({
+            fees: null,
+            protocolFeeBps: 10_001,
+            crankReward: null,
+            paused: null,
+          })
+          .accounts({ signer })
+          .rpc();
+        assert.fail("Transaction should have failed with InvalidFee");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("InvalidFee");
+      }
+    });
+
+    it("should fail to set a crank reward below the minimum", async () => {
+      try {
+        await program.methods
+          .updateConfig({
+            fees: null,
+            protocolFeeBps: null,
+            crankReward: new anchor.BN(0),
+            paused: null,
+          })
+          .accounts({ signer })
+          .rpc();
+        assert.fail("Transaction should have failed with CrankRewardTooLow");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("CrankRewardTooLow");
+      }
+    });
+
+    it("should fail to create a plan while the protocol is paused", async () => {
+      await program.methods
+        .updateConfig({
+          fees: null,
+          protocolFeeBps: null,
+          crankReward: null,
+          paused: true,
+        })
+        .accounts({ signer })
+        .rpc();
+
+      try {
+        await program.methods
+          .createSubscription({
+            name: "paused plan",
+            amount: taskAmount,
+            interval: { seconds: { seconds: new anchor.BN(120) } },
+            maxFailureCount: 1,
+            trialPeriod: new anchor.BN(0),
+            introAmount: new anchor.BN(0),
+            introCycles: 0,
+            retryPolicy: { fixed: { delay: new anchor.BN(60) } },
+            gracePeriod: new anchor.BN(0),
+            prorationEnabled: true,
+            metering: null,
+            batchBilling: false,
+          })
+          .accountsPartial({
+            merchant: signer,
+            mint: USDC_MINT,
+            subscriptionPlan: anchor.web3.PublicKey.findProgramAddressSync(
+              [Buffer.from("plan"), signer.toBuffer(), hashString("paused plan")],
+              program.programId
+            )[0],
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+        assert.fail("Transaction should have failed with ProtocolPaused");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("ProtocolPaused");
+      } finally {
+        await program.methods
+          .updateConfig({
+            fees: null,
+            protocolFeeBps: null,
+            crankReward: null,
+            paused: false,
+          })
+          .accounts({ signer })
+          .rpc();
+      }
+    });
+
+    it("admin can be handed over in two steps", async () => {
+      await program.methods.proposeAdmin(signer).accounts({ signer }).rpc();
+
+      let globalState = await program.account.globalState.fetch(globalStatePda);
+      assert.ok(globalState.pendingAdmin.equals(signer));
+
+      await program.methods.acceptAdmin().accounts({ signer }).rpc();
+
+      globalState = await program.account.globalState.fetch(globalStatePda);
+      assert.ok(globalState.admin.equals(signer));
+      assert.isNull(globalState.pendingAdmin);
+    });
   });
 
   describe("create subscription", () => {
@@ -224,11 +393,20 @@ describe("capstone", () => {
         .createSubscription({
           name,
           amount: taskAmount,
-          interval: new anchor.BN(120),
+          interval: { seconds: { seconds: new anchor.BN(120) } },
           maxFailureCount: 1,
+          trialPeriod: new anchor.BN(0),
+          introAmount: new anchor.BN(0),
+          introCycles: 0,
+          retryPolicy: { fixed: { delay: new anchor.BN(60) } },
+          gracePeriod: new anchor.BN(0),
+          prorationEnabled: true,
+          metering: null,
+          batchBilling: false,
         })
         .accountsStrict({
           merchant: signer,
+          acceptedMints: acceptedMintsPda,
           mint: USDC_MINT,
           subscriptionPlan: subscriptionPlanPda,
           feesVault: feesPda,
@@ -251,7 +429,7 @@ describe("capstone", () => {
         new anchor.BN(1_000_000).toString()
       );
       assert.equal(
-        subscription.interval.toString(),
+        subscription.interval.seconds.seconds.toString(),
         new anchor.BN(120).toString()
       );
       assert.equal(subscription.maxFailureCount, 1);
@@ -270,11 +448,20 @@ describe("capstone", () => {
           .createSubscription({
             name: newPlanName,
             amount: new anchor.BN(0),
-            interval: new anchor.BN(100),
+            interval: { seconds: { seconds: new anchor.BN(100) } },
             maxFailureCount: 1,
+            trialPeriod: new anchor.BN(0),
+            introAmount: new anchor.BN(0),
+            introCycles: 0,
+            retryPolicy: { fixed: { delay: new anchor.BN(60) } },
+            gracePeriod: new anchor.BN(0),
+            prorationEnabled: true,
+            metering: null,
+            batchBilling: false,
           })
           .accountsStrict({
             merchant: signer,
+            acceptedMints: acceptedMintsPda,
             mint: USDC_MINT,
             subscriptionPlan: testPlanPda,
             feesVault: feesPda,
@@ -304,11 +491,20 @@ describe("capstone", () => {
           .createSubscription({
             name: newPlanName,
             amount: taskAmount,
-            interval: new anchor.BN(100),
+            interval: { seconds: { seconds: new anchor.BN(100) } },
             maxFailureCount: 1,
+            trialPeriod: new anchor.BN(0),
+            introAmount: new anchor.BN(0),
+            introCycles: 0,
+            retryPolicy: { fixed: { delay: new anchor.BN(60) } },
+            gracePeriod: new anchor.BN(0),
+            prorationEnabled: true,
+            metering: null,
+            batchBilling: false,
           })
           .accountsStrict({
             merchant: signer,
+            acceptedMints: acceptedMintsPda,
             mint: USDC_MINT,
             subscriptionPlan: testPlanPda,
             feesVault: feesPda,
@@ -324,6 +520,49 @@ describe("capstone", () => {
         expect(err.error.errorCode.code).to.include("InvalidName");
       }
     });
+
+    it("should fail to create subscription with an invalid interval", async () => {
+      const newPlanName = "invalid interval";
+
+      const invalidIntervals = [
+        { seconds: { seconds: new anchor.BN(0) } },
+        { calendarMonth: { anchorDay: 32 } },
+        { calendarYear: { anchorMonth: 2, anchorDay: 30 } },
+      ];
+
+      for (const interval of invalidIntervals) {
+        try {
+          await program.methods
+            .createSubscription({
+              name: newPlanName,
+              amount: taskAmount,
+              interval,
+              maxFailureCount: 1,
+              trialPeriod: new anchor.BN(0),
+              introAmount: new anchor.BN(0),
+              introCycles: 0,
+              retryPolicy: { fixed: { delay: new anchor.BN(60) } },
+              gracePeriod: new anchor.BN(0),
+              prorationEnabled: true,
+              metering: null,
+              batchBilling: false,
+            })
+            .accountsPartial({
+              merchant: signer,
+              mint: USDC_MINT,
+              subscriptionPlan: anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("plan"), signer.toBuffer(), hashString(newPlanName)],
+                program.programId
+              )[0],
+              tokenProgram: TOKEN_PROGRAM_ID,
+            })
+            .rpc();
+          assert.fail("Transaction should have failed with InvalidSchedule");
+        } catch (err) {
+          expect(err.error.errorCode.code).to.include("InvalidSchedule");
+        }
+      }
+    });
   });
 
   describe("subscribe", () => {
@@ -339,11 +578,17 @@ describe("capstone", () => {
       )[0];
 
       await program.methods
-        .subscribe()
+        .subscribe({
+          crankReserve,
+          taskId: nextTask,
+          billingMode: { vault: {} },
+          spendingCap: null,
+        })
         .accountsPartial({
           subscriber: subscriber1.publicKey,
           subscriberAta: subscriber1Ata,
           subscriptionPlan: subscriptionPlanPda,
+          coupon: null,
           mint: USDC_MINT,
           task: taskKey(taskQueue, nextTask)[0],
           globalState: globalStatePda,
@@ -360,6 +605,46 @@ describe("capstone", () => {
         userSubs.status.active !== undefined,
         "Subscription status should be Active"
       );
+      assert.equal(
+        userSubs.crankReserve.toString(),
+        crankReserve.toString()
+      );
+      assert.equal(userSubs.nextTaskId, nextTask);
+    });
+
+    it("subscriber is entitled right after subscribing", async () => {
+      const entitlement = await program.methods
+        .verifyEntitlement()
+        .accounts({
+          userSubscription: subscriber1SubscriptionPda,
+          subscriptionPlan: subscriptionPlanPda,
+        })
+        .view();
+
+      assert.isTrue(entitlement.active);
+      assert.ok(entitlement.plan.equals(subscriptionPlanPda));
+    });
+
+    it("subscriber can create a billing ledger", async () => {
+      await program.methods
+        .initBillingLedger()
+        .accounts({
+          subscriber: subscriber1.publicKey,
+          userSubscription: subscriber1SubscriptionPda,
+        })
+        .signers([subscriber1])
+        .rpc();
+
+      const ledger = await program.account.billingLedger.fetch(
+        subscriber1LedgerPda
+      );
+      assert.ok(ledger.userSubscription.equals(subscriber1SubscriptionPda));
+      assert.equal(ledger.entries.length, 0);
+
+      const userSubs = await program.account.userSubscription.fetch(
+        subscriber1SubscriptionPda
+      );
+      assert.isNotNull(userSubs.ledgerBump);
     });
 
     it("should fail to subscribe to the same plan twice", async () => {
@@ -374,11 +659,17 @@ describe("capstone", () => {
 
       try {
         await program.methods
-          .subscribe()
+          .subscribe({
+            crankReserve,
+            taskId: nextTask,
+            billingMode: { vault: {} },
+            spendingCap: null,
+          })
           .accountsPartial({
             subscriber: subscriber1.publicKey,
             subscriberAta: subscriber1Ata,
             subscriptionPlan: subscriptionPlanPda,
+            coupon: null,
             mint: USDC_MINT,
             task: taskKey(taskQueue, nextTask)[0],
             globalState: globalStatePda,
@@ -396,6 +687,561 @@ describe("capstone", () => {
     });
   });
 
+  describe("charge batch", () => {
+    const batchName = "batch plan";
+    const [batchPlanPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("plan"), signer.toBuffer(), hashString(batchName)],
+      program.programId
+    );
+
+    it("should fail to batch a plan whose subscribers have their own tasks", async () => {
+      const before = await program.account.userSubscription.fetch(
+        subscriber1SubscriptionPda
+      );
+      const [receiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("receipt"),
+          subscriber1SubscriptionPda.toBuffer(),
+          before.nextReceipt.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+      const [ledgerPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("ledger"), subscriber1SubscriptionPda.toBuffer()],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .chargeBatch()
+          .accountsPartial({
+            merchant: signer,
+            subscriptionPlan: subscriptionPlanPda,
+            mint: USDC_MINT,
+            globalState: globalStatePda,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .remainingAccounts([
+            {
+              pubkey: subscriber1SubscriptionPda,
+              isSigner: false,
+              isWritable: true,
+            },
+            { pubkey: subscriber1VaultPda, isSigner: false, isWritable: true },
+            { pubkey: subscriber1Ata, isSigner: false, isWritable: true },
+            { pubkey: receiptPda, isSigner: false, isWritable: true },
+            { pubkey: ledgerPda, isSigner: false, isWritable: true },
+          ])
+          .rpc();
+        assert.fail("Transaction should have failed with NotBatchBilled");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("NotBatchBilled");
+      }
+
+      const after = await program.account.userSubscription.fetch(
+        subscriber1SubscriptionPda
+      );
+      assert.equal(after.cyclesCharged, before.cyclesCharged);
+      assert.equal(after.lastExecTs.toString(), before.lastExecTs.toString());
+    });
+
+    it("merchant can create a batch billed plan", async () => {
+      await program.methods
+        .createSubscription({
+          name: batchName,
+          amount: taskAmount,
+          interval: { seconds: { seconds: new anchor.BN(120) } },
+          maxFailureCount: 1,
+          trialPeriod: new anchor.BN(0),
+          introAmount: new anchor.BN(0),
+          introCycles: 0,
+          retryPolicy: { fixed: { delay: new anchor.BN(60) } },
+          gracePeriod: new anchor.BN(0),
+          prorationEnabled: false,
+          metering: null,
+          batchBilling: true,
+        })
+        .accountsPartial({
+          merchant: signer,
+          mint: USDC_MINT,
+          subscriptionPlan: batchPlanPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+      const plan = await program.account.subscriptionPlan.fetch(batchPlanPda);
+      assert.ok(plan.batchBilling);
+    });
+
+    it("should fail to batch an incomplete subscription group", async () => {
+      try {
+        await program.methods
+          .chargeBatch()
+          .accountsPartial({
+            merchant: signer,
+            subscriptionPlan: batchPlanPda,
+            mint: USDC_MINT,
+            globalState: globalStatePda,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .remainingAccounts([
+            {
+              pubkey: subscriber1SubscriptionPda,
+              isSigner: false,
+              isWritable: true,
+            },
+          ])
+          .rpc();
+        assert.fail("Transaction should have failed with InvalidSubscription");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("InvalidSubscription");
+      }
+    });
+  });
+
+  describe("metered billing", () => {
+    const meteredName = "metered plan";
+    const [meteredPlanPda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("plan"), signer.toBuffer(), hashString(meteredName)],
+      program.programId
+    );
+    const [subscriber2SubscriptionPda] =
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("subscription"),
+          subscriber2.publicKey.toBuffer(),
+          meteredPlanPda.toBuffer(),
+        ],
+        program.programId
+      );
+
+    const subscribeToMeteredPlan = async (
+      spendingCap: anchor.BN | null
+    ) => {
+      const taskQueueAcc = await tuktukProgram.account.taskQueueV0.fetch(
+        taskQueue
+      );
+      const nextTask = nextAvailableTaskIds(
+        taskQueueAcc.taskBitmap,
+        1,
+        false
+      )[0];
+
+      await program.methods
+        .subscribe({
+          crankReserve,
+          taskId: nextTask,
+          billingMode: { vault: {} },
+          spendingCap,
+        })
+        .accountsPartial({
+          subscriber: subscriber2.publicKey,
+          subscriberAta: subscriber2Ata,
+          subscriptionPlan: meteredPlanPda,
+          coupon: null,
+          mint: USDC_MINT,
+          task: taskKey(taskQueue, nextTask)[0],
+          globalState: globalStatePda,
+          taskQueue,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([subscriber2])
+        .rpc();
+    };
+
+    it("merchant can create a metered plan", async () => {
+      await program.methods
+        .createSubscription({
+          name: meteredName,
+          amount: taskAmount,
+          interval: { seconds: { seconds: new anchor.BN(120) } },
+          maxFailureCount: 1,
+          trialPeriod: new anchor.BN(0),
+          introAmount: new anchor.BN(0),
+          introCycles: 0,
+          retryPolicy: { fixed: { delay: new anchor.BN(60) } },
+          gracePeriod: new anchor.BN(0),
+          prorationEnabled: false,
+          metering: { oracle: signer, unitPrice: new anchor.BN(1_000) },
+          batchBilling: false,
+        })
+        .accountsPartial({
+          merchant: signer,
+          mint: USDC_MINT,
+          subscriptionPlan: meteredPlanPda,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+      const plan = await program.account.subscriptionPlan.fetch(
+        meteredPlanPda
+      );
+      assert.ok(plan.metering.oracle.equals(signer));
+      assert.equal(plan.metering.unitPrice.toString(), "1000");
+    });
+
+    it("should fail to subscribe to a metered plan without a spending cap", async () => {
+      try {
+        await subscribeToMeteredPlan(null);
+        assert.fail("Transaction should have failed with InvalidSpendingCap");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("InvalidSpendingCap");
+      }
+    });
+
+    it("oracle can report usage within the spending cap", async () => {
+      await subscribeToMeteredPlan(new anchor.BN(2_000_000));
+
+      await program.methods
+        .reportUsage(new anchor.BN(500))
+        .accountsPartial({
+          oracle: signer,
+          subscriptionPlan: meteredPlanPda,
+          userSubscription: subscriber2SubscriptionPda,
+        })
+        .rpc();
+
+      const userSubs = await program.account.userSubscription.fetch(
+        subscriber2SubscriptionPda
+      );
+      assert.equal(userSubs.usageUnits.toString(), "500");
+    });
+
+    it("should fail to report usage past the spending cap", async () => {
+      try {
+        await program.methods
+          .reportUsage(new anchor.BN(600))
+          .accountsPartial({
+            oracle: signer,
+            subscriptionPlan: meteredPlanPda,
+            userSubscription: subscriber2SubscriptionPda,
+          })
+          .rpc();
+        assert.fail("Transaction should have failed with SpendingCapExceeded");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("SpendingCapExceeded");
+      }
+    });
+
+    it("should fail to report usage from another key", async () => {
+      try {
+        await program.methods
+          .reportUsage(new anchor.BN(1))
+          .accountsPartial({
+            oracle: subscriber2.publicKey,
+            subscriptionPlan: meteredPlanPda,
+            userSubscription: subscriber2SubscriptionPda,
+          })
+          .signers([subscriber2])
+          .rpc();
+        assert.fail("Transaction should have failed with InvalidSigner");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("InvalidSigner");
+      }
+    });
+  });
+
+  describe("plan lifecycle", () => {
+    it("merchant can pause and resume a plan", async () => {
+      await program.methods
+        .pausePlan()
+        .accounts({ merchant: signer, subscriptionPlan: subscriptionPlanPda })
+        .rpc();
+
+      let plan = await program.account.subscriptionPlan.fetch(
+        subscriptionPlanPda
+      );
+      assert.isFalse(plan.active);
+
+      await program.methods
+        .resumePlan()
+        .accounts({ merchant: signer, subscriptionPlan: subscriptionPlanPda })
+        .rpc();
+
+      plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+      assert.isTrue(plan.active);
+    });
+
+    it("merchant can schedule a price change", async () => {
+      const newAmount = new anchor.BN(2_000_000);
+
+      await program.methods
+        .schedulePriceChange(newAmount)
+        .accounts({ merchant: signer, subscriptionPlan: subscriptionPlanPda })
+        .rpc();
+
+      const plan = await program.account.subscriptionPlan.fetch(
+        subscriptionPlanPda
+      );
+      assert.equal(plan.amount.toString(), taskAmount.toString());
+      assert.equal(plan.pendingAmount.toString(), newAmount.toString());
+    });
+
+    it("should fail to refund a charge that has no receipt", async () => {
+      const subscription = await program.account.userSubscription.fetch(
+        subscriber1SubscriptionPda
+      );
+      // receipt the next charge of the subscription will write
+      const [receiptPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("receipt"),
+          subscriber1SubscriptionPda.toBuffer(),
+          subscription.nextReceipt.toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .refundCharge(taskAmount)
+          .accountsPartial({
+            merchant: signer,
+            subscriptionPlan: subscriptionPlanPda,
+            chargeReceipt: receiptPda,
+            mint: USDC_MINT,
+            merchantAta,
+            destination: subscriber1Ata,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+        assert.fail("Transaction should have failed with AccountNotInitialized");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("AccountNotInitialized");
+      }
+    });
+
+    it("merchant can create a coupon for a plan", async () => {
+      const code = "WELCOME50";
+      const [couponPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("coupon"),
+          subscriptionPlanPda.toBuffer(),
+          hashString(code),
+        ],
+        program.programId
+      );
+
+      await program.methods
+        .createCoupon({
+          code,
+          discount: { percentage: { bps: 5_000 } },
+          durationCycles: 3,
+          maxRedemptions: 100,
+          expiresAt: new anchor.BN(0),
+        })
+        .accountsPartial({
+          merchant: signer,
+          subscriptionPlan: subscriptionPlanPda,
+          coupon: couponPda,
+        })
+        .rpc();
+
+      const coupon = await program.account.coupon.fetch(couponPda);
+      assert.equal(coupon.code, code);
+      assert.equal(coupon.durationCycles, 3);
+      assert.equal(coupon.redemptions, 0);
+    });
+
+    it("should fail to create a coupon with more than 100% off", async () => {
+      const code = "FREEMONEY";
+      const [couponPda] = anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("coupon"),
+          subscriptionPlanPda.toBuffer(),
+          hashString(code),
+        ],
+        program.programId
+      );
+
+      try {
+        await program.methods
+          .createCoupon({
+            code,
+            discount: { percentage: { bps: 10_001 } },
+            durationCycles: 1,
+            maxRedemptions: 1,
+            expiresAt: new anchor.BN(0),
+          })
+          .accountsPartial({
+            merchant: signer,
+            subscriptionPlan: subscriptionPlanPda,
+            coupon: couponPda,
+          })
+          .rpc();
+        assert.fail("Transaction should have failed with InvalidCoupon");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("InvalidCoupon");
+      }
+    });
+
+    it("merchant can split payouts between several token accounts", async () => {
+      await program.methods
+        .setPayoutSplit([
+          { recipientAta: merchantAta, bps: 7_000 },
+          { recipientAta: subscriber2Ata, bps: 3_000 },
+        ])
+        .accounts({
+          merchant: signer,
+          subscriptionPlan: subscriptionPlanPda,
+        })
+        .remainingAccounts([
+          { pubkey: merchantAta, isSigner: false, isWritable: true },
+          { pubkey: subscriber2Ata, isSigner: false, isWritable: true },
+        ])
+        .rpc();
+
+      let plan = await program.account.subscriptionPlan.fetch(
+        subscriptionPlanPda
+      );
+      assert.equal(plan.payoutSplit.length, 2);
+      assert.equal(plan.payoutSplit[1].bps, 3_000);
+
+      // back to paying the merchant ata only
+      await program.methods
+        .setPayoutSplit([])
+        .accounts({
+          merchant: signer,
+          subscriptionPlan: subscriptionPlanPda,
+        })
+        .rpc();
+
+      plan = await program.account.subscriptionPlan.fetch(subscriptionPlanPda);
+      assert.equal(plan.payoutSplit.length, 0);
+    });
+
+    it("should fail to set a payout split that does not sum to 10000 bps", async () => {
+      try {
+        await program.methods
+          .setPayoutSplit([{ recipientAta: merchantAta, bps: 9_000 }])
+          .accounts({
+            merchant: signer,
+            subscriptionPlan: subscriptionPlanPda,
+          })
+          .remainingAccounts([
+            { pubkey: merchantAta, isSigner: false, isWritable: true },
+          ])
+          .rpc();
+        assert.fail("Transaction should have failed with InvalidPayoutSplit");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("InvalidPayoutSplit");
+      }
+    });
+
+    it("should fail to retire a plan that still has subscribers", async () => {
+      try {
+        await program.methods
+          .retirePlan()
+          .accounts({ merchant: signer, subscriptionPlan: subscriptionPlanPda })
+          .rpc();
+        assert.fail("Transaction should have failed with PlanHasSubscribers");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("PlanHasSubscribers");
+      }
+    });
+  });
+
+  describe("pause subscription", () => {
+    it("subscriber can pause a subscription", async () => {
+      const resumeAt = new anchor.BN(Math.floor(Date.now() / 1000) + 3600);
+
+      await program.methods
+        .pauseSubscription(resumeAt)
+        .accounts({
+          subscriber: subscriber1.publicKey,
+          userSubscription: subscriber1SubscriptionPda,
+        })
+        .signers([subscriber1])
+        .rpc();
+
+      const userSubs = await program.account.userSubscription.fetch(
+        subscriber1SubscriptionPda
+      );
+      assert.isTrue(
+        userSubs.status.paused !== undefined,
+        "Subscription status should be Paused"
+      );
+      assert.equal(userSubs.resumeAt.toString(), resumeAt.toString());
+    });
+  });
+
+  describe("cancel at period end", () => {
+    it("subscriber keeps access until the paid period ends", async () => {
+      await program.methods
+        .cancelAtPeriodEnd()
+        .accounts({
+          subscriber: subscriber1.publicKey,
+          userSubscription: subscriber1SubscriptionPda,
+          subscriptionPlan: subscriptionPlanPda,
+          subscriberAta: subscriber1Ata,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([subscriber1])
+        .rpc();
+
+      const userSubs = await program.account.userSubscription.fetch(
+        subscriber1SubscriptionPda
+      );
+      assert.isTrue(
+        userSubs.status.canceled !== undefined,
+        "Subscription status should be Canceled"
+      );
+      assert.equal(
+        userSubs.accessUntil.toString(),
+        userSubs.lastExecTs.toString()
+      );
+    });
+  });
+
+  describe("terminate subscription", () => {
+    it("should fail to terminate from a key that is not the plan's merchant", async () => {
+      const userSubs = await program.account.userSubscription.fetch(
+        subscriber1SubscriptionPda
+      );
+
+      try {
+        await program.methods
+          .terminateSubscription({ abuse: {} })
+          .accountsPartial({
+            merchant: subscriber2.publicKey,
+            subscriber: subscriber1.publicKey,
+            userSubscription: subscriber1SubscriptionPda,
+            billingLedger: subscriber1LedgerPda,
+            subscriptionPlan: subscriptionPlanPda,
+            subscriberAta: subscriber1Ata,
+            taskQueue,
+            task: taskKey(taskQueue, userSubs.nextTaskId)[0],
+          })
+          .signers([subscriber2])
+          .rpc();
+        assert.fail("Transaction should have failed with InvalidSigner");
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("InvalidSigner");
+      }
+    });
+
+    it("should fail to close a subscription that has not failed", async () => {
+      try {
+        await program.methods
+          .closeFailedSubscription()
+          .accountsPartial({
+            signer: subscriber2.publicKey,
+            subscriber: subscriber1.publicKey,
+            userSubscription: subscriber1SubscriptionPda,
+            billingLedger: subscriber1LedgerPda,
+            subscriptionPlan: subscriptionPlanPda,
+            subscriberAta: subscriber1Ata,
+          })
+          .signers([subscriber2])
+          .rpc();
+        assert.fail(
+          "Transaction should have failed with SubscriptionNotFailed"
+        );
+      } catch (err) {
+        expect(err.error.errorCode.code).to.include("SubscriptionNotFailed");
+      }
+    });
+  });
+
   describe("cancel subscription", () => {
     it("subscriber1 can cancel a subscription", async () => {
       console.log("\nwaiting for tuktuk to charge for one cycle...\n");
@@ -412,6 +1258,9 @@ describe("capstone", () => {
         .accountsPartial({
           subscriber: subscriber1.publicKey,
           userSubscription: subscriber1SubscriptionPda,
+          billingLedger: subscriber1LedgerPda,
+          subscriptionPlan: subscriptionPlanPda,
+          subscriberAta: subscriber1Ata,
           taskQueue,
           tokenProgram: TOKEN_PROGRAM_ID,
           task,
@@ -425,6 +1274,27 @@ describe("capstone", () => {
         );
 
       expect(cancelledSubscription).to.be.null;
+
+      const closedLedger = await program.account.billingLedger.fetchNullable(
+        subscriber1LedgerPda
+      );
+      expect(closedLedger).to.be.null;
+    });
+  });
+
+  describe("withdraw fees", () => {
+    it("admin can withdraw collected fees", async () => {
+      await program.methods
+        .withdrawFees()
+        .accounts({
+          signer,
+          mint: USDC_MINT,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+      const feesVaultBalance = await provider.connection.getBalance(feesPda);
+      assert.equal(feesVaultBalance, 0);
     });
   });
 
@@ -434,6 +1304,7 @@ describe("capstone", () => {
         .closeVault()
         .accounts({
           subscriber: subscriber1.publicKey,
+          mint: USDC_MINT,
           tokenProgram: TOKEN_PROGRAM_ID,
         })
         .signers([subscriber1])
@@ -442,11 +1313,6 @@ describe("capstone", () => {
   });
 
   it("subscriber2 cannot close subscriber1's vault (Wrong Signer)", async () => {
-    const [subscriber1VaultPda] = anchor.web3.PublicKey.findProgramAddressSync(
-      [Buffer.from("vault"), subscriber1.publicKey.toBuffer()],
-      program.programId
-    );
-
     try {
       await program.methods
         .closeVault()
@@ -74,6 +74,9 @@ describe("capstone", () => {
         .accountsPartial({
           subscriber: subscriber.publicKey,
           userSubscription,
+          billingLedger: null,
+          subscriptionPlan: userSubscriptionAccount.subscription,
+          subscriberAta: userSubscriptionAccount.subscriberAta,
           taskQueue,
           tokenProgram: TOKEN_PROGRAM_ID,
           task,
@@ -27,12 +27,17 @@ describe("capstone", () => {
 
   console.log("subscriber: ", subscriber.publicKey.toBase58());
 
+  const USDC_MINT = new anchor.web3.PublicKey(
+    "4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU" // for devnet
+  );
+
   describe("close vault", () => {
     it("user can close his vault", async () => {
       let s = await program.methods
         .closeVault()
         .accounts({
           subscriber: subscriber.publicKey,
+          mint: USDC_MINT,
           tokenProgram: TOKEN_PROGRAM_ID,
         })
         .signers([subscriber])
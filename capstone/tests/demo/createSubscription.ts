@@ -32,6 +32,11 @@ describe("capstone", () => {
     program.programId
   );
 
+  const [acceptedMintsPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("accepted_mints")],
+    program.programId
+  );
+
   const USDC_MINT = new anchor.web3.PublicKey(
     "4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU" // for devnet
   );
@@ -51,11 +56,20 @@ describe("capstone", () => {
         .createSubscription({
           name,
           amount: new anchor.BN(1_000_000), // 1 USDC
-          interval: new anchor.BN(120),
+          interval: { seconds: { seconds: new anchor.BN(120) } },
           maxFailureCount: 2,
+          trialPeriod: new anchor.BN(0),
+          introAmount: new anchor.BN(0),
+          introCycles: 0,
+          retryPolicy: { fixed: { delay: new anchor.BN(60) } },
+          gracePeriod: new anchor.BN(0),
+          prorationEnabled: true,
+          metering: null,
+          batchBilling: false,
         })
         .accountsStrict({
           merchant: signer,
+          acceptedMints: acceptedMintsPda,
           mint: USDC_MINT,
           subscriptionPlan: subscriptionPlanPda,
           feesVault: feesPda,
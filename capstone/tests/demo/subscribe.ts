@@ -42,6 +42,9 @@ describe("capstone", () => {
       program.programId
     );
 
+  // lamports for the crank rewards of the next 10 charge tasks
+  const crankReserve = new anchor.BN(100_000);
+
   const USDC_MINT = new anchor.web3.PublicKey(
     "4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU" // for devnet
   );
@@ -83,10 +86,16 @@ describe("capstone", () => {
         );
 
       let s = await program.methods
-        .subscribe()
+        .subscribe({
+          crankReserve,
+          taskId: nextTask,
+          billingMode: { vault: {} },
+          spendingCap: null,
+        })
         .accountsPartial({
           subscriber: subscriber.publicKey,
           subscriptionPlan,
+          coupon: null,
           mint: USDC_MINT,
           task: taskKey(taskQueue, nextTask)[0],
           globalState: globalStatePda,
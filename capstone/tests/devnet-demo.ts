@@ -62,6 +62,14 @@ describe("capstone", () => {
     program.programId
   );
 
+  const [acceptedMintsPda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("accepted_mints")],
+    program.programId
+  );
+
+  // lamports for the crank rewards and receipts of the next 10 charges
+  const crankReserve = new anchor.BN(20_000_000);
+
   const USDC_MINT = new anchor.web3.PublicKey(
     "4zMMC9srt5Ri5X14GAgXhaHii3GnPAEERYPJgZJDncDU" // for devnet
   );
@@ -98,11 +106,20 @@ describe("capstone", () => {
         .createSubscription({
           name,
           amount: new anchor.BN(1_000_000), // 1 USDC
-          interval: new anchor.BN(120),
+          interval: { seconds: { seconds: new anchor.BN(120) } },
           maxFailureCount: 1,
+          trialPeriod: new anchor.BN(0),
+          introAmount: new anchor.BN(0),
+          introCycles: 0,
+          retryPolicy: { fixed: { delay: new anchor.BN(60) } },
+          gracePeriod: new anchor.BN(0),
+          prorationEnabled: true,
+          metering: null,
+          batchBilling: false,
         })
         .accountsStrict({
           merchant: signer,
+          acceptedMints: acceptedMintsPda,
           mint: USDC_MINT,
           subscriptionPlan: subscriptionPlanPda,
           feesVault: feesPda,
@@ -130,7 +147,7 @@ describe("capstone", () => {
         new anchor.BN(1_000_000).toString()
       );
       assert.equal(
-        subscription.interval.toString(),
+        subscription.interval.seconds.seconds.toString(),
         new anchor.BN(120).toString()
       );
       assert.equal(subscription.maxFailureCount, 1);
@@ -160,11 +177,17 @@ describe("capstone", () => {
       )[0];
 
       await program.methods
-        .subscribe()
+        .subscribe({
+          crankReserve,
+          taskId: nextTask,
+          billingMode: { vault: {} },
+          spendingCap: null,
+        })
         .accountsPartial({
           userSubscription: userSubscriptionPda,
           subscriber: subscriber.publicKey,
           subscriptionPlan: subscriptionPlanPda,
+          coupon: null,
           mint: USDC_MINT,
           task: taskKey(taskQueue, nextTask)[0],
           globalState: globalStatePda,
@@ -207,6 +230,9 @@ describe("capstone", () => {
         .accountsPartial({
           subscriber: subscriber.publicKey,
           userSubscription: userSubscriptionPda,
+          billingLedger: null,
+          subscriptionPlan: subscriptionPlanPda,
+          subscriberAta: userSubs.subscriberAta,
           taskQueue,
           tokenProgram: TOKEN_PROGRAM_ID,
           task,
@@ -222,6 +248,7 @@ describe("capstone", () => {
         .closeVault()
         .accounts({
           subscriber: subscriber.publicKey,
+          mint: USDC_MINT,
           tokenProgram: TOKEN_PROGRAM_ID,
         })
         .signers([subscriber])